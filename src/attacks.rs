@@ -0,0 +1,109 @@
+use super::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Pin {
+    pub pinned: u64,
+    pub pinner: u64,
+    /// Squares between the king and the pinner plus the pinner itself,
+    /// the only squares the pinned piece can move to.
+    pub ray: u64,
+}
+
+impl ChessBoard{
+    /// Pieces of `colour` that attack `square`.
+    pub fn attackers_of(&self, square:u64, colour:ChessColour)->u64{
+        get_attackers(square, get_all_piece_map(*self), *self)&get_piece_map(colour, *self)
+    }
+
+    /// Every square attacked by `colour`, including squares holding its own pieces.
+    pub fn attacked_squares(&self, colour:ChessColour)->u64{
+        get_capture_map(colour, *self)
+    }
+
+    /// Pieces giving check to the side to move.
+    pub fn checkers(&self)->u64{
+        let king=get_king_pos(self.current_move, *self);
+        self.attackers_of(king, get_op_col(self.current_move))
+    }
+
+    pub fn is_in_check(&self)->bool{
+        self.checkers()>0
+    }
+
+    /// Pieces of `colour` that cannot leave the line between their king and an enemy slider.
+    ///
+    /// Only a lone piece on the line counts as pinned. When an en passant capture would
+    /// take both pawns off the king's rank at once and expose it to a rook or queen,
+    /// no pin is reported; the capture is still left out of the legal moves.
+    pub fn pinned_pieces(&self, colour:ChessColour)->Vec<Pin>{
+        let board=*self;
+        let king=get_king_pos(colour, board);
+        let mut out=Vec::new();
+        if king==0{return out;}
+        let occupied=get_all_piece_map(board);
+        let own=get_piece_map(colour, board);
+        for piece in board.pieces{
+            if piece.is_captured||piece.colour==colour{continue;}
+            let straight=matches!(piece.kind, ChessPieceKind::Rook|ChessPieceKind::Queen);
+            let diagonal=matches!(piece.kind, ChessPieceKind::Bishop|ChessPieceKind::Queen);
+            let line=if straight&&(get_rook_attacks(piece.pos, king)&king)>0{
+                get_rook_attacks(piece.pos, king)&get_rook_attacks(king, piece.pos)
+            }
+            else if diagonal&&(get_bishop_attacks(piece.pos, king)&king)>0{
+                get_bishop_attacks(piece.pos, king)&get_bishop_attacks(king, piece.pos)
+            }
+            else{
+                continue;
+            };
+            let blockers=line&occupied;
+            if blockers.count_ones()==1&&(blockers&own)>0{
+                out.push(Pin{pinned: blockers, pinner: piece.pos, ray: line|piece.pos});
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChessColour::{Black, White};
+    use ChessPieceKind::*;
+
+    #[test]
+    fn test_start_position_attacks(){
+        let b=new_board();
+        assert_eq!(b.attacked_squares(White)&0xFF0000, 0xFF0000);
+        assert_eq!(b.attacked_squares(White)&0xFF000000, 0);
+        assert!(!b.is_in_check());
+    }
+    #[test]
+    fn test_double_check(){
+        let b=board_from_template(&[(White,King,1,5),(Black,King,8,8),(Black,Rook,8,5),(Black,Knight,3,4)], White);
+        assert_eq!(b.checkers(), get_u64_pos(8,5)|get_u64_pos(3,4));
+        assert_eq!(b.attackers_of(get_u64_pos(1,5), Black).count_ones(), 2);
+    }
+    #[test]
+    fn test_king_cannot_take_defended_piece(){
+        let b=board_from_template(&[(White,King,1,5),(Black,King,8,8),(Black,Queen,2,5),(Black,Pawn,3,4)], White);
+        let king=get_piece_bit_mask(get_u64_pos(1,5), b).unwrap();
+        assert_eq!(get_moves(king, b)&get_u64_pos(2,5), 0);
+        assert!(b.is_in_check());
+    }
+    #[test]
+    fn test_pin_ray(){
+        let b=board_from_template(&[(White,King,1,1),(White,Knight,3,3),(Black,King,8,8),(Black,Bishop,5,5),(Black,Rook,1,8),(White,Bishop,1,4),(White,Rook,1,5)], White);
+        let pins=b.pinned_pieces(White);
+        assert_eq!(pins, vec![Pin{pinned: get_u64_pos(3,3), pinner: get_u64_pos(5,5), ray: get_u64_pos(2,2)|get_u64_pos(3,3)|get_u64_pos(4,4)|get_u64_pos(5,5)}]);
+    }
+    #[test]
+    fn test_en_passant_rank_pin(){
+        let b=ChessBoard::from_fen("8/8/8/K2pP2r/8/8/8/7k w - d6 0 2").unwrap();
+        assert!(b.pinned_pieces(White).is_empty());
+        let moves=get_legal_moves(b);
+        assert!(moves.contains(&new_move(get_u64_pos(5,5), get_u64_pos(6,5))));
+        assert!(!moves.contains(&new_move(get_u64_pos(5,5), get_u64_pos(6,4))));
+        let unpinned=ChessBoard::from_fen("8/8/8/K2pP3/8/8/8/7k w - d6 0 2").unwrap();
+        assert!(get_legal_moves(unpinned).contains(&new_move(get_u64_pos(5,5), get_u64_pos(6,4))));
+    }
+}
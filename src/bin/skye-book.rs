@@ -0,0 +1,58 @@
+use skye_chess::book_builder::BookBuilder;
+use std::process::ExitCode;
+
+const USAGE:&str="usage: skye-book [--max-ply N] [--min-games N] -o BOOK.bin GAMES.pgn...";
+
+fn main()->ExitCode{
+    let mut builder=BookBuilder::new();
+    let mut output:Option<String>=None;
+    let mut inputs:Vec<String>=Vec::new();
+    let mut args=std::env::args().skip(1);
+    while let Some(arg)=args.next(){
+        let mut number=|name:&str| match args.next().and_then(|value| value.parse::<usize>().ok()){
+            Some(value)=>Ok(value),
+            None=>Err(format!("{} takes a number", name)),
+        };
+        let parsed=match arg.as_str(){
+            "--max-ply"=>number("--max-ply").map(|value| builder.max_ply=value),
+            "--min-games"=>number("--min-games").map(|value| builder.min_games=value as u32),
+            "-o"|"--output"=>args.next().map(|path| output=Some(path)).ok_or("-o takes a file name".to_string()),
+            "-h"|"--help"=>{
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _=>{
+                inputs.push(arg);
+                Ok(())
+            }
+        };
+        if let Err(err)=parsed{
+            eprintln!("{}\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    }
+    let Some(output)=output.filter(|_| !inputs.is_empty()) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    for path in inputs.iter(){
+        let text=match std::fs::read(path){
+            Ok(bytes)=>String::from_utf8_lossy(&bytes).into_owned(),
+            Err(err)=>{
+                eprintln!("{}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        };
+        for err in builder.add_pgn(&text){
+            eprintln!("{}: skipped game: {}", path, err);
+        }
+    }
+    let book=builder.build();
+    if let Err(err)=book.save(&output){
+        eprintln!("{}: {}", output, err);
+        return ExitCode::FAILURE;
+    }
+    println!("wrote {} entries to {}", book.entries.len(), output);
+    ExitCode::SUCCESS
+}
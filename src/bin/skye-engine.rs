@@ -0,0 +1,32 @@
+use skye_chess::engine::Engine;
+use std::io::BufRead;
+use std::process::ExitCode;
+
+const USAGE:&str="usage: skye-engine
+Speaks UCI or CECP (XBoard) on standard input and output, chosen by the first command.";
+
+fn main()->ExitCode{
+    if let Some(arg)=std::env::args().nth(1){
+        if arg=="-h"||arg=="--help"{
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        eprintln!("unknown argument {}\n{}", arg, USAGE);
+        return ExitCode::FAILURE;
+    }
+    let mut engine=Engine::new(std::io::stdout());
+    for line in std::io::stdin().lock().lines(){
+        let handled=line.and_then(|line| engine.handle(&line));
+        match handled{
+            Ok(true)=>{}
+            Ok(false)=>return ExitCode::SUCCESS,
+            Err(err)=>{
+                eprintln!("{}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // The GUI went away without `quit`.
+    let _=engine.handle("quit");
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,121 @@
+use skye_chess::engine_match::{openings_from_epd, openings_from_pgn, run_match, MatchOptions, Player, SearchPlayer, Sprt, SprtStatus, UciPlayer};
+use skye_chess::search::SearchLimits;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE:&str="usage: skye-match --engine CMD --engine CMD [--games N] [--depth N] [--movetime MS] [--nodes N]
+       [--openings FILE.epd|FILE.pgn] [--plies N] [--resign CP] [--draw CP] [--max-plies N]
+       [--sprt ELO0 ELO1] [--pgn OUT.pgn]
+An engine of `skye` plays the built-in search; any other is a UCI command line.";
+
+fn get_player(command:&str)->Result<Box<dyn Player>, String>{
+    if command=="skye"{
+        return Ok(Box::new(SearchPlayer::new("skye")));
+    }
+    let mut words=command.split_whitespace();
+    let path=words.next().ok_or("empty engine command")?;
+    let args:Vec<String>=words.map(|word| word.to_string()).collect();
+    match UciPlayer::spawn(path, &args, &[]){
+        Ok(player)=>Ok(Box::new(player)),
+        Err(err)=>Err(format!("{}: {}", path, err)),
+    }
+}
+
+fn main()->ExitCode{
+    let mut options=MatchOptions{limits: SearchLimits::default(), ..Default::default()};
+    let mut engines:Vec<String>=Vec::new();
+    let mut openings_path:Option<String>=None;
+    let mut pgn_path:Option<String>=None;
+    let mut args=std::env::args().skip(1);
+    while let Some(arg)=args.next(){
+        let mut number=|name:&str| match args.next().and_then(|value| value.parse::<f64>().ok()){
+            Some(value)=>Ok(value),
+            None=>Err(format!("{} takes a number", name)),
+        };
+        let parsed=match arg.as_str(){
+            "--games"=>number("--games").map(|value| options.games=value as usize),
+            "--depth"=>number("--depth").map(|value| options.limits.depth=Some(value as u8)),
+            "--movetime"=>number("--movetime").map(|value| options.limits.movetime=Some(Duration::from_millis(value as u64))),
+            "--nodes"=>number("--nodes").map(|value| options.limits.nodes=Some(value as u64)),
+            "--plies"=>number("--plies").map(|value| options.opening_plies=Some(value as usize)),
+            "--resign"=>number("--resign").map(|value| options.adjudication.resign_score=Some(value as i32)),
+            "--draw"=>number("--draw").map(|value| options.adjudication.draw_score=Some(value as i32)),
+            "--max-plies"=>number("--max-plies").map(|value| options.adjudication.max_plies=Some(value as usize)),
+            "--sprt"=>number("--sprt").and_then(|elo0| number("--sprt").map(|elo1| options.sprt=Some(Sprt::new(elo0, elo1)))),
+            "--engine"=>args.next().map(|command| engines.push(command)).ok_or("--engine takes a command".to_string()),
+            "--openings"=>args.next().map(|path| openings_path=Some(path)).ok_or("--openings takes a file name".to_string()),
+            "--pgn"=>args.next().map(|path| pgn_path=Some(path)).ok_or("--pgn takes a file name".to_string()),
+            "-h"|"--help"=>{
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _=>Err(format!("unknown argument {}", arg)),
+        };
+        if let Err(err)=parsed{
+            eprintln!("{}\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    }
+    if options.limits==SearchLimits::default(){
+        options.limits=MatchOptions::default().limits;
+    }
+    if engines.len()!=2{
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let openings=match openings_path{
+        Some(path)=>match std::fs::read(&path){
+            Ok(bytes)=>{
+                let text=String::from_utf8_lossy(&bytes);
+                if path.ends_with(".pgn") {openings_from_pgn(&text)} else {openings_from_epd(&text)}
+            }
+            Err(err)=>{
+                eprintln!("{}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None=>Vec::new(),
+    };
+    let mut players=Vec::new();
+    for command in engines.iter(){
+        match get_player(command){
+            Ok(player)=>players.push(player),
+            Err(err)=>{
+                eprintln!("{}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let (first, second)=players.split_at_mut(1);
+    let (first, second)=(&mut first[0], &mut second[0]);
+    println!("{} vs {}", first.name(), second.name());
+    let result=run_match(first.as_mut(), second.as_mut(), &openings, &options, |game, score| {
+        let tag=|name:&str| game.get_tag(name).unwrap_or("?").to_string();
+        println!("game {}: {} - {} {} ({})", tag("Round"), tag("White"), tag("Black"), game.result().as_pgn(), score);
+    });
+    let result=match result{
+        Ok(result)=>result,
+        Err(err)=>{
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("{}", result.score);
+    if let Some(sprt)=options.sprt{
+        let (lower, upper)=sprt.bounds();
+        let status=match sprt.status(&result.score){
+            SprtStatus::Continue=>"undecided",
+            SprtStatus::AcceptH0=>"H0 accepted",
+            SprtStatus::AcceptH1=>"H1 accepted",
+        };
+        println!("SPRT llr {:.2} ({:.2}, {:.2}) {}", sprt.llr(&result.score), lower, upper, status);
+    }
+    if let Some(path)=pgn_path{
+        if let Err(err)=std::fs::write(&path, result.pgn()){
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,329 @@
+use skye_chess::chess960::new_board_960;
+use skye_chess::clock::Clock;
+use skye_chess::game::Game;
+use skye_chess::search::{search, SearchLimits};
+use skye_chess::*;
+use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const RESET:&str="\x1b[0m";
+const LIGHT_SQUARE:&str="\x1b[48;5;180m";
+const DARK_SQUARE:&str="\x1b[48;5;137m";
+const LAST_MOVE:&str="\x1b[48;5;143m";
+const DESTINATION:&str="\x1b[48;5;71m";
+const CHECKED_KING:&str="\x1b[48;5;167m";
+const WHITE_PIECE:&str="\x1b[1;97m";
+const BLACK_PIECE:&str="\x1b[1;30m";
+
+const HELP:&str="\
+moves      e4, Nf3, O-O, e7e8q ...   square   e2 shows where that piece can go
+flip       turn the board around      undo     take back a move
+new [fen]  start again                960 [n]  Chess960 start position n (random if left out)
+engine white|black|off                go       let the engine move now
+think <s>  engine seconds per move    time <tc> clock such as 300+2 or 40/5400+30:1800
+draw       offer or accept a draw     resign   give up the game for the side to move
+fen        print the position         pgn      print the game
+help       show this text             quit     leave";
+
+// Filled glyphs for both sides read better on coloured squares; the colour comes from the foreground.
+fn get_glyph(kind:ChessPieceKind)->char{
+    match kind{
+        ChessPieceKind::King=>'♚',
+        ChessPieceKind::Queen=>'♛',
+        ChessPieceKind::Rook=>'♜',
+        ChessPieceKind::Bishop=>'♝',
+        ChessPieceKind::Knight=>'♞',
+        ChessPieceKind::Pawn=>'♟',
+    }
+}
+
+fn format_duration(time:Duration)->String{
+    let secs=time.as_secs();
+    if secs<20{
+        format!("{}:{:02}.{}", secs/60, secs%60, time.subsec_millis()/100)
+    }
+    else{
+        format!("{}:{:02}", secs/60, secs%60)
+    }
+}
+
+struct Tui {
+    game: Game,
+    flipped: bool,
+    engine: Option<ChessColour>,
+    engine_time: Duration,
+    highlight: u64,
+    message: String,
+    turn_start: Instant,
+}
+
+impl Tui{
+    fn new()->Tui{
+        Tui{
+            game: Game::new(),
+            flipped: false,
+            engine: None,
+            engine_time: Duration::from_secs(1),
+            highlight: 0,
+            message: "type help for the list of commands".to_string(),
+            turn_start: Instant::now(),
+        }
+    }
+
+    fn render_board(&self)->Vec<String>{
+        let board=self.game.board;
+        let last=self.game.moves.last().map(|mv| mv.from|mv.to).unwrap_or(0);
+        let checked_king=if board.is_in_check() {king_of(board)} else {0};
+        let mut lines=Vec::new();
+        for row in 0..8{
+            let rank=if self.flipped {row+1} else {8-row};
+            let mut line=format!(" {} ", rank);
+            for col in 0..8{
+                let file=if self.flipped {8-col} else {col+1};
+                let pos=get_u64_pos(rank, file);
+                let background=if (pos&checked_king)>0{
+                    CHECKED_KING
+                }
+                else if (pos&self.highlight)>0{
+                    DESTINATION
+                }
+                else if (pos&last)>0{
+                    LAST_MOVE
+                }
+                else if (rank+file)%2==1{
+                    LIGHT_SQUARE
+                }
+                else{
+                    DARK_SQUARE
+                };
+                let cell=match board.pieces.iter().find(|p| !p.is_captured&&p.pos==pos){
+                    Some(piece)=>{
+                        let colour=if piece.colour==ChessColour::White {WHITE_PIECE} else {BLACK_PIECE};
+                        format!("{}{} {} ", background, colour, get_glyph(piece.kind))
+                    }
+                    None if (pos&self.highlight)>0=>format!("{} · ", background),
+                    None=>format!("{}   ", background),
+                };
+                line.push_str(&cell);
+            }
+            line.push_str(RESET);
+            lines.push(line);
+        }
+        let files:String=(0..8).map(|col| {
+            let file=if self.flipped {7-col} else {col};
+            format!(" {} ", (b'a'+file) as char)
+        }).collect();
+        lines.push(format!("   {}", files));
+        lines
+    }
+
+    fn render_panel(&self)->Vec<String>{
+        let mut lines=Vec::new();
+        let board=self.game.board;
+        for col in [ChessColour::Black, ChessColour::White]{
+            let name=if col==ChessColour::White {"White"} else {"Black"};
+            let engine=if self.engine==Some(col) {" (engine)"} else {""};
+            let marker=if board.current_move==col&&!self.game.is_over() {"▶"} else {" "};
+            let time=match &self.game.clock{
+                Some(clock) if board.current_move==col&&!self.game.is_over()=>format!("  {}", format_duration(clock.remaining_after(col, self.turn_start.elapsed()))),
+                Some(clock)=>format!("  {}", format_duration(clock.remaining(col))),
+                None=>String::new(),
+            };
+            lines.push(format!("{} {}{}{}", marker, name, engine, time));
+        }
+        lines.push(String::new());
+        let mut replay=self.game.start;
+        let mut sans=Vec::new();
+        for mv in self.game.moves.iter(){
+            sans.push((replay.move_number, replay.current_move, replay.to_san(*mv)));
+            replay.make_move(*mv);
+        }
+        let mut rows:Vec<String>=Vec::new();
+        for (number, col, san) in sans{
+            if col==ChessColour::White||rows.is_empty(){
+                let prefix=if col==ChessColour::White {format!("{:>3}. {:<8}", number, san)} else {format!("{:>3}. {:<8}{}", number, "...", san)};
+                rows.push(prefix);
+            }
+            else if let Some(row)=rows.last_mut(){
+                row.push_str(&san);
+            }
+        }
+        let shown=rows.len().saturating_sub(6);
+        lines.extend(rows.into_iter().skip(shown));
+        lines
+    }
+
+    fn render(&self){
+        let board_lines=self.render_board();
+        let panel=self.render_panel();
+        let mut out=String::from("\x1b[2J\x1b[H");
+        for (idx, line) in board_lines.iter().enumerate(){
+            out.push_str(line);
+            if let Some(side)=panel.get(idx){
+                out.push_str("   ");
+                out.push_str(side);
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+        if self.game.is_over(){
+            let reason=self.game.termination().map(|t| format!("{:?}", t)).unwrap_or_default();
+            out.push_str(&format!("Game over: {} ({})\n", self.game.result().as_pgn(), reason));
+        }
+        else if self.game.board.is_in_check(){
+            out.push_str("Check!\n");
+        }
+        if !self.message.is_empty(){
+            out.push_str(&self.message);
+            out.push('\n');
+        }
+        out.push_str("> ");
+        print!("{}", out);
+        let _=io::stdout().flush();
+    }
+
+    fn play(&mut self, mv:Move){
+        let elapsed=self.turn_start.elapsed();
+        let played=if self.game.clock.is_some() {self.game.make_timed_move(mv, elapsed)} else {self.game.make_move(mv)};
+        if !played&&!self.game.is_over(){
+            self.message="that move is not legal here".to_string();
+        }
+        self.turn_start=Instant::now();
+        self.highlight=0;
+    }
+
+    fn engine_move(&mut self){
+        if self.game.is_over(){return;}
+        let result=search(self.game.board, SearchLimits{movetime: Some(self.engine_time), ..Default::default()});
+        if let Some(mv)=result.best_move{
+            self.message=format!("engine: {} (depth {}, score {}, {} nodes)", self.game.board.to_san(mv), result.depth, result.score, result.nodes);
+            self.play(mv);
+        }
+    }
+
+    fn restart(&mut self, board:ChessBoard){
+        let clock=self.game.clock.clone();
+        self.game=Game::from_board(board);
+        self.game.clock=clock.map(|clock| Clock::new(clock.stages));
+        self.turn_start=Instant::now();
+        self.highlight=0;
+    }
+
+    fn command(&mut self, line:&str)->bool{
+        self.message.clear();
+        let mut words=line.split_whitespace();
+        let Some(first)=words.next() else {
+            return true;
+        };
+        let rest:Vec<&str>=words.collect();
+        let side=self.game.board.current_move;
+        match first{
+            "quit"|"exit"=>return false,
+            "help"=>self.message=HELP.to_string(),
+            "flip"=>self.flipped^=true,
+            "undo"=>{
+                self.game.undo();
+                if self.engine==Some(self.game.board.current_move){
+                    self.game.undo();
+                }
+                self.highlight=0;
+            }
+            "new"=>{
+                if rest.is_empty(){
+                    self.restart(new_board());
+                }
+                else{
+                    match ChessBoard::from_fen(&rest.join(" ")){
+                        Ok(board)=>self.restart(board),
+                        Err(err)=>self.message=err.to_string(),
+                    }
+                }
+            }
+            "960"=>{
+                let index=rest.first().and_then(|n| n.parse().ok()).unwrap_or_else(|| (SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos()%960) as u16);
+                match new_board_960(index){
+                    Some(board)=>{
+                        self.restart(board);
+                        self.message=format!("Chess960 position {}", index);
+                    }
+                    None=>self.message="positions are numbered 0 to 959".to_string(),
+                }
+            }
+            "engine"=>{
+                self.engine=match rest.first().copied(){
+                    Some("white")=>Some(ChessColour::White),
+                    Some("black")=>Some(ChessColour::Black),
+                    _=>None,
+                };
+            }
+            "go"=>self.engine_move(),
+            "think"=>match rest.first().and_then(|s| s.parse::<f64>().ok()){
+                Some(secs) if secs>0.0=>self.engine_time=Duration::from_secs_f64(secs),
+                _=>self.message="think takes a number of seconds".to_string(),
+            },
+            "time"=>match rest.first().and_then(|tc| Clock::from_pgn_time_control(tc)){
+                Some(clock)=>{
+                    self.game.clock=Some(clock);
+                    self.turn_start=Instant::now();
+                }
+                None=>self.message="time control should look like 300+2".to_string(),
+            },
+            "draw"=>{
+                if self.game.offer_draw(side){
+                    self.message=if self.game.is_over() {"draw agreed".to_string()} else {"draw offered".to_string()};
+                }
+            }
+            "resign"=>{
+                self.game.resign(side);
+            }
+            "fen"=>self.message=self.game.board.to_fen(),
+            "pgn"=>self.message=self.game.to_pgn(),
+            text=>{
+                let board=self.game.board;
+                // A bare square is a pawn move when empty and a request for that piece's moves otherwise.
+                let selected=get_square_from_name(text).and_then(|pos| board.pieces.into_iter().find(|p| !p.is_captured&&p.pos==pos));
+                if let Some(piece)=selected{
+                    self.highlight=filter_moves_check(piece, board);
+                }
+                else if let Some(mv)=board.parse_san(text).or_else(|| board.parse_uci(text)){
+                    self.play(mv);
+                }
+                else{
+                    self.message=format!("unknown command or illegal move '{}'", text);
+                }
+            }
+        }
+        true
+    }
+}
+
+fn king_of(board:ChessBoard)->u64{
+    board.pieces.iter()
+        .filter(|p| !p.is_captured&&p.kind==ChessPieceKind::King&&p.colour==board.current_move)
+        .fold(0, |out, p| out|p.pos)
+}
+
+fn main(){
+    let mut tui=Tui::new();
+    let stdin=io::stdin();
+    let mut lines=stdin.lock().lines();
+    loop{
+        if tui.engine==Some(tui.game.board.current_move)&&!tui.game.is_over(){
+            tui.render();
+            tui.engine_move();
+            continue;
+        }
+        tui.render();
+        let Some(Ok(line))=lines.next() else {
+            break;
+        };
+        if tui.game.check_flag(tui.turn_start.elapsed()){
+            tui.message="flag fell".to_string();
+            continue;
+        }
+        if !tui.command(line.trim()){
+            break;
+        }
+    }
+    println!();
+}
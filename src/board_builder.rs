@@ -0,0 +1,173 @@
+use super::*;
+use crate::fen::{set_castling_right, set_chess960, set_ep_square};
+use crate::validate::ValidationError;
+
+/// Sets up an arbitrary position square by square. Squares are single-bit masks as
+/// returned by `get_square_from_name`, and pieces are colour and kind pairs.
+#[derive(Clone, Debug)]
+pub struct BoardBuilder {
+    squares: [Option<(ChessColour, ChessPieceKind)>;64],
+    side_to_move: ChessColour,
+    // Indexed by colour, then king side first.
+    castling: [[bool;2];2],
+    ep_square: Option<u64>,
+}
+
+impl Default for BoardBuilder{
+    fn default()->BoardBuilder{
+        BoardBuilder{squares: [None;64], side_to_move: ChessColour::White, castling: [[false;2];2], ep_square: None}
+    }
+}
+
+fn get_colour_index(col:ChessColour)->usize{
+    if col==ChessColour::White {0} else {1}
+}
+
+fn get_square_index(square:u64)->usize{
+    assert!(square.count_ones()==1, "a square is a single-bit mask");
+    square.trailing_zeros() as usize
+}
+
+impl BoardBuilder{
+    /// An empty board with White to move and no castling rights.
+    pub fn new()->BoardBuilder{
+        BoardBuilder::default()
+    }
+
+    /// Starts from an existing position, keeping its side to move, castling rights
+    /// and en passant square.
+    pub fn from_board(board:&ChessBoard)->BoardBuilder{
+        let mut out=BoardBuilder::new();
+        for piece in board.pieces.iter().filter(|p| !p.is_captured){
+            out.put(piece.pos, (piece.colour, piece.kind));
+        }
+        out.set_side_to_move(board.current_move);
+        for col in [ChessColour::White, ChessColour::Black]{
+            let Some(king)=get_piece_bit_mask(get_king_pos(col, *board), *board) else {continue;};
+            for king_side in [true, false]{
+                out.set_castling(col, king_side, get_castling_rook(king, king_side, *board).is_some());
+            }
+        }
+        let ep=get_ep_capture_spots(get_op_col(board.current_move), *board);
+        out.set_ep_square(if ep==0 {None} else {Some(ep)});
+        out
+    }
+
+    pub fn put(&mut self, square:u64, piece:(ChessColour, ChessPieceKind))->&mut BoardBuilder{
+        self.squares[get_square_index(square)]=Some(piece);
+        self
+    }
+
+    pub fn remove(&mut self, square:u64)->&mut BoardBuilder{
+        self.squares[get_square_index(square)]=None;
+        self
+    }
+
+    pub fn get(&self, square:u64)->Option<(ChessColour, ChessPieceKind)>{
+        self.squares[get_square_index(square)]
+    }
+
+    /// Empties the board, dropping castling rights and the en passant square too.
+    pub fn clear(&mut self)->&mut BoardBuilder{
+        let side_to_move=self.side_to_move;
+        *self=BoardBuilder::new();
+        self.side_to_move=side_to_move;
+        self
+    }
+
+    pub fn set_side_to_move(&mut self, col:ChessColour)->&mut BoardBuilder{
+        self.side_to_move=col;
+        self
+    }
+
+    /// Allows or forbids castling on one side. As with `KQkq` in FEN, the right goes
+    /// to the outermost rook on that side of the king.
+    pub fn set_castling(&mut self, col:ChessColour, king_side:bool, allowed:bool)->&mut BoardBuilder{
+        self.castling[get_colour_index(col)][if king_side {0} else {1}]=allowed;
+        self
+    }
+
+    /// The square a pawn of the side not to move has just passed over, if any.
+    pub fn set_ep_square(&mut self, square:Option<u64>)->&mut BoardBuilder{
+        self.ep_square=square;
+        self
+    }
+
+    /// The position, if it passes `ChessBoard::validate`.
+    pub fn build(&self)->Result<ChessBoard, ValidationError>{
+        let mut template:Vec<(ChessColour, ChessPieceKind, u8, u8)>=Vec::new();
+        for (idx, square) in self.squares.iter().enumerate(){
+            let Some((col, kind))=*square else {continue;};
+            template.push((col, kind, idx as u8/8+1, 8-idx as u8%8));
+        }
+        let mut board=board_from_template(&template, self.side_to_move);
+        for piece in board.pieces.iter_mut(){
+            if matches!(piece.kind, ChessPieceKind::King|ChessPieceKind::Rook){
+                piece.has_moved=true;
+            }
+        }
+        for col in [ChessColour::White, ChessColour::Black]{
+            for (side, c) in ['k', 'q'].into_iter().enumerate(){
+                if !self.castling[get_colour_index(col)][side]{continue;}
+                let c=if col==ChessColour::White {c.to_ascii_uppercase()} else {c};
+                if !set_castling_right(&mut board, c){
+                    return Err(ValidationError::InvalidCastling(col));
+                }
+            }
+        }
+        set_chess960(&mut board);
+        if let Some(square)=self.ep_square{
+            if !set_ep_square(&mut board, square){
+                return Err(ValidationError::InvalidEnPassant);
+            }
+        }
+        board.rule_repetition[0]=hash_board_state(board);
+        board.validate()?;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name:&str)->u64{
+        get_square_from_name(name).unwrap()
+    }
+
+    #[test]
+    fn test_build(){
+        let mut builder=BoardBuilder::new();
+        builder.put(square("e1"), (ChessColour::White, ChessPieceKind::King))
+            .put(square("h1"), (ChessColour::White, ChessPieceKind::Rook))
+            .put(square("e8"), (ChessColour::Black, ChessPieceKind::King))
+            .set_castling(ChessColour::White, true, true)
+            .set_side_to_move(ChessColour::Black);
+        assert_eq!(builder.build().unwrap().to_fen(), "4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+        builder.remove(square("e8"));
+        assert_eq!(builder.build().err(), Some(ValidationError::MissingKing(ChessColour::Black)));
+        builder.set_castling(ChessColour::White, false, true);
+        builder.put(square("e8"), (ChessColour::Black, ChessPieceKind::King));
+        assert_eq!(builder.build().err(), Some(ValidationError::InvalidCastling(ChessColour::White)));
+        builder.clear();
+        assert_eq!(builder.get(square("e1")), None);
+        assert_eq!(builder.build().err(), Some(ValidationError::MissingKing(ChessColour::White)));
+    }
+    #[test]
+    fn test_from_board(){
+        let fen="rnbqkbnr/pppp1ppp/8/8/3Pp3/8/PPP1PPPP/RNBQKBNR b Kq d3 0 1";
+        let board=ChessBoard::from_fen(fen).unwrap();
+        assert_eq!(BoardBuilder::from_board(&board).build().unwrap().to_fen(), fen);
+        // Nine queens once every pawn has promoted.
+        let mut builder=BoardBuilder::new();
+        builder.put(square("a8"), (ChessColour::Black, ChessPieceKind::King))
+            .put(square("a1"), (ChessColour::White, ChessPieceKind::King));
+        for name in ["b2", "c2", "d2", "e2", "f2", "h2", "b3", "c3", "d3"]{
+            builder.put(square(name), (ChessColour::White, ChessPieceKind::Queen));
+        }
+        let board=builder.build().unwrap();
+        assert_eq!(get_kind_map(ChessPieceKind::Queen, ChessColour::White, board).count_ones(), 9);
+        builder.set_ep_square(Some(square("e3")));
+        assert_eq!(builder.build().err(), Some(ValidationError::InvalidEnPassant));
+    }
+}
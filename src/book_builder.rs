@@ -0,0 +1,154 @@
+use super::*;
+use crate::game::{Game, GameResult};
+use crate::pgn::{read_pgn, PgnError};
+use crate::polyglot::{encode_move, Book, BookEntry};
+use std::collections::HashMap;
+
+// Score for the side that played the move, as in Polyglot's own `make-book`.
+const WIN_SCORE:u32=2;
+const DRAW_SCORE:u32=1;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct MoveStats {
+    pub games: u32,
+    pub score: u32,
+}
+
+/// Collects move statistics from finished games and turns them into a Polyglot book.
+#[derive(Clone, Debug)]
+pub struct BookBuilder {
+    /// Moves past this many plies into a game are not recorded.
+    pub max_ply: usize,
+    /// Moves seen in fewer games are left out of the book.
+    pub min_games: u32,
+    stats: HashMap<(u64, u16), MoveStats>,
+}
+
+impl Default for BookBuilder{
+    fn default()->BookBuilder{
+        BookBuilder{max_ply: 40, min_games: 1, stats: HashMap::new()}
+    }
+}
+
+impl BookBuilder{
+    pub fn new()->BookBuilder{
+        BookBuilder::default()
+    }
+
+    /// Records the moves of a finished game; unfinished games are ignored.
+    pub fn add_game(&mut self, game:&Game)->bool{
+        let winner=match game.result(){
+            GameResult::WhiteWins=>Some(ChessColour::White),
+            GameResult::BlackWins=>Some(ChessColour::Black),
+            GameResult::Draw=>None,
+            GameResult::Ongoing=>return false,
+        };
+        let mut board=game.start;
+        for mv in game.moves.iter().take(self.max_ply){
+            let stats=self.stats.entry((board.polyglot_key(), encode_move(*mv, board))).or_default();
+            stats.games+=1;
+            stats.score+=match winner{
+                Some(col) if col==board.current_move=>WIN_SCORE,
+                Some(_)=>0,
+                None=>DRAW_SCORE,
+            };
+            board.make_move(*mv);
+        }
+        true
+    }
+
+    /// Records every game of a PGN database, returning the errors of games that
+    /// could not be read.
+    pub fn add_pgn(&mut self, text:&str)->Vec<PgnError>{
+        let mut errors=Vec::new();
+        for game in read_pgn(text){
+            match game{
+                Ok(game)=>{
+                    self.add_game(&game);
+                }
+                Err(err)=>errors.push(err),
+            }
+        }
+        errors
+    }
+
+    pub fn stats(&self, board:ChessBoard, mv:Move)->Option<MoveStats>{
+        self.stats.get(&(board.polyglot_key(), encode_move(mv, board))).copied()
+    }
+
+    /// The book sorted by key, heaviest move first within a position. Weights are
+    /// the moves' scores, scaled down if needed to fit Polyglot's 16 bits; moves that
+    /// never scored are dropped.
+    pub fn build(&self)->Book{
+        let max_score=self.stats.values().map(|stats| stats.score).max().unwrap_or(0);
+        let scale=(f64::from(u16::MAX)/f64::from(max_score.max(1))).min(1.0);
+        let mut entries:Vec<BookEntry>=self.stats.iter()
+            .filter(|(_, stats)| stats.games>=self.min_games&&stats.score>0)
+            .map(|((key, raw_move), stats)| BookEntry{
+                key: *key,
+                raw_move: *raw_move,
+                weight: ((f64::from(stats.score)*scale) as u16).max(1),
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)).then(a.raw_move.cmp(&b.raw_move)));
+        Book{entries}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES:&str="[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 1-0
+
+[Result \"0-1\"]
+
+1. e4 c5 0-1
+
+[Result \"1/2-1/2\"]
+
+1. d4 d5 1/2-1/2
+
+[Result \"*\"]
+
+1. c4 *
+
+[Result \"1-0\"]
+
+1. e4 e4 1-0
+";
+
+    #[test]
+    fn test_statistics(){
+        let mut builder=BookBuilder::new();
+        let errors=builder.add_pgn(GAMES);
+        assert_eq!(errors.len(), 1);
+        let board=new_board();
+        let e4=board.parse_san("e4").unwrap();
+        assert_eq!(builder.stats(board, e4), Some(MoveStats{games: 2, score: 2}));
+        assert_eq!(builder.stats(board, board.parse_san("d4").unwrap()), Some(MoveStats{games: 1, score: 1}));
+        assert_eq!(builder.stats(board, board.parse_san("c4").unwrap()), None);
+    }
+    #[test]
+    fn test_build_filters_and_sorts(){
+        let mut builder=BookBuilder{max_ply: 2, min_games: 1, ..Default::default()};
+        builder.add_pgn(GAMES);
+        let book=Book::from_bytes(&builder.build().to_bytes());
+        assert!(book.entries.windows(2).all(|pair| pair[0].key<=pair[1].key));
+        let board=new_board();
+        let moves:Vec<(String, u16)>=book.moves(board).into_iter().map(|(mv, weight)| (mv.to_uci(), weight)).collect();
+        assert_eq!(moves, [("e2e4".to_string(), 2), ("d2d4".to_string(), 1)]);
+        let mut after=board;
+        after.make_move(after.parse_san("e4").unwrap());
+        let replies:Vec<String>=book.moves(after).into_iter().map(|(mv, _)| mv.to_uci()).collect();
+        // 1... e5 lost without scoring; 2. Nf3 lies beyond max_ply.
+        assert_eq!(replies, ["c7c5"]);
+        assert_eq!(book.entries.len(), 4);
+
+        builder.min_games=2;
+        assert_eq!(builder.build().entries.len(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+use super::*;
+
+pub const STANDARD_POSITION_INDEX:u16=518;
+
+/// Back rank of Chess960 starting position `index` (0-959, Scharnagl numbering),
+/// from the a-file to the h-file.
+pub fn get_960_back_rank(index:u16)->Option<[ChessPieceKind;8]>{
+    if index>=960{return None;}
+    let mut rank:[Option<ChessPieceKind>;8]=[None;8];
+    let mut n=index as usize;
+    rank[(n%4)*2+1]=Some(ChessPieceKind::Bishop);
+    n/=4;
+    rank[(n%4)*2]=Some(ChessPieceKind::Bishop);
+    n/=4;
+    place_on_empty(&mut rank, n%6, ChessPieceKind::Queen);
+    n/=6;
+    let (first, second)=[(0,1),(0,2),(0,3),(0,4),(1,2),(1,3),(1,4),(2,3),(2,4),(3,4)][n];
+    // Placing the second knight first keeps the first knight's index valid.
+    place_on_empty(&mut rank, second, ChessPieceKind::Knight);
+    place_on_empty(&mut rank, first, ChessPieceKind::Knight);
+    for kind in [ChessPieceKind::Rook, ChessPieceKind::King, ChessPieceKind::Rook]{
+        place_on_empty(&mut rank, 0, kind);
+    }
+    Some(rank.map(|kind| kind.unwrap()))
+}
+
+fn place_on_empty(rank:&mut [Option<ChessPieceKind>;8], nth:usize, kind:ChessPieceKind){
+    let file=(0..8).filter(|file| rank[*file].is_none()).nth(nth).unwrap();
+    rank[file]=Some(kind);
+}
+
+/// Chess960 starting position `index`, with castling handled the Chess960 way:
+/// the king castles by moving onto its own rook.
+pub fn new_board_960(index:u16)->Option<ChessBoard>{
+    let back_rank=get_960_back_rank(index)?;
+    let mut template:Vec<(ChessColour, ChessPieceKind, u8, u8)>=Vec::new();
+    for (idx, kind) in back_rank.iter().enumerate(){
+        let file=idx as u8+1;
+        template.push((ChessColour::White, *kind, 1, file));
+        template.push((ChessColour::White, ChessPieceKind::Pawn, 2, file));
+        template.push((ChessColour::Black, *kind, 8, file));
+        template.push((ChessColour::Black, ChessPieceKind::Pawn, 7, file));
+    }
+    let mut board=board_from_template(&template, ChessColour::White);
+    board.chess960=true;
+    Some(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChessPieceKind::*;
+
+    #[test]
+    fn test_scharnagl_numbering(){
+        assert_eq!(get_960_back_rank(STANDARD_POSITION_INDEX), Some([Rook,Knight,Bishop,Queen,King,Bishop,Knight,Rook]));
+        assert_eq!(get_960_back_rank(0), Some([Bishop,Bishop,Queen,Knight,Knight,Rook,King,Rook]));
+        assert_eq!(get_960_back_rank(959), Some([Rook,King,Rook,Knight,Knight,Queen,Bishop,Bishop]));
+        assert_eq!(get_960_back_rank(960), None);
+    }
+    #[test]
+    fn test_all_positions_distinct(){
+        let mut seen:Vec<String>=(0..960).map(|idx| {
+            get_960_back_rank(idx).unwrap().iter().map(|kind| fen::get_piece_char(*kind, ChessColour::White)).collect()
+        }).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 960);
+        assert_eq!(new_board_960(0).unwrap().to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(new_board_960(0).unwrap().to_shredder_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+    }
+    #[test]
+    fn test_castling_onto_rook(){
+        let mut board=ChessBoard::from_fen("4k3/8/8/8/8/8/8/1R2K1R1 w GB - 0 1").unwrap();
+        assert!(board.chess960);
+        assert!(board.make_move(new_move(get_u64_pos(1,5), get_u64_pos(1,2))));
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/2KR2R1 b - - 1 1");
+        let mut board=ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K1R1 w G - 0 1").unwrap();
+        assert!(board.make_move(new_move(get_u64_pos(1,5), get_u64_pos(1,7))));
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+}
@@ -0,0 +1,212 @@
+use super::*;
+use crate::status::{has_mating_material, GameStatus};
+use std::time::Duration;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DelayMode {
+    /// Sudden death: the clock only ever runs down.
+    None,
+    /// Fischer: the increment is added after every move.
+    Increment(Duration),
+    /// Bronstein: the time used is given back, up to the delay.
+    Bronstein(Duration),
+    /// Simple (US) delay: the clock only starts once the delay has passed.
+    SimpleDelay(Duration),
+}
+
+/// One period of a time control. `moves` is the number of moves to be made in the
+/// period, `None` for the rest of the game.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeControlStage {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub delay: DelayMode,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clock {
+    pub stages: Vec<TimeControlStage>,
+    remaining: [Duration;2],
+    moves_made: [u32;2],
+    stage: [usize;2],
+    flagged: Option<ChessColour>,
+}
+
+fn get_colour_idx(col:ChessColour)->usize{
+    get_colour_hash(col) as usize
+}
+
+impl Clock{
+    /// A clock for the given stages; the first stage's time is on both clocks.
+    /// Panics if `stages` is empty.
+    pub fn new(stages:Vec<TimeControlStage>)->Clock{
+        let time=stages[0].time;
+        Clock{stages, remaining: [time;2], moves_made: [0;2], stage: [0;2], flagged: None}
+    }
+
+    pub fn sudden_death(time:Duration)->Clock{
+        Clock::new(vec![TimeControlStage{moves: None, time, delay: DelayMode::None}])
+    }
+
+    pub fn fischer(time:Duration, increment:Duration)->Clock{
+        Clock::new(vec![TimeControlStage{moves: None, time, delay: DelayMode::Increment(increment)}])
+    }
+
+    pub fn bronstein(time:Duration, delay:Duration)->Clock{
+        Clock::new(vec![TimeControlStage{moves: None, time, delay: DelayMode::Bronstein(delay)}])
+    }
+
+    pub fn simple_delay(time:Duration, delay:Duration)->Clock{
+        Clock::new(vec![TimeControlStage{moves: None, time, delay: DelayMode::SimpleDelay(delay)}])
+    }
+
+    /// Parses a PGN `TimeControl` value such as `300+2` or `40/5400+30:1800+30`.
+    /// A `d` or `b` in place of the `+` gives a simple or Bronstein delay, e.g. `600d5`.
+    pub fn from_pgn_time_control(value:&str)->Option<Clock>{
+        let mut stages=Vec::new();
+        for period in value.split(':'){
+            let (moves, rest)=match period.split_once('/'){
+                Some((moves, rest))=>(Some(moves.parse().ok()?), rest),
+                None=>(None, period),
+            };
+            let (seconds, delay)=match rest.find(['+','d','b']){
+                Some(idx)=>{
+                    let extra=Duration::from_secs(rest[idx+1..].parse().ok()?);
+                    let delay=match &rest[idx..=idx]{
+                        "+"=>DelayMode::Increment(extra),
+                        "d"=>DelayMode::SimpleDelay(extra),
+                        _=>DelayMode::Bronstein(extra),
+                    };
+                    (&rest[..idx], delay)
+                }
+                None=>(rest, DelayMode::None),
+            };
+            stages.push(TimeControlStage{moves, time: Duration::from_secs(seconds.parse().ok()?), delay});
+        }
+        Some(Clock::new(stages))
+    }
+
+    pub fn remaining(&self, col:ChessColour)->Duration{
+        self.remaining[get_colour_idx(col)]
+    }
+
+    /// What `col`'s clock would show `elapsed` into its current move.
+    pub fn remaining_after(&self, col:ChessColour, elapsed:Duration)->Duration{
+        let charged=match self.stages[self.stage[get_colour_idx(col)]].delay{
+            DelayMode::SimpleDelay(delay)=>elapsed.saturating_sub(delay),
+            _=>elapsed,
+        };
+        self.remaining(col).saturating_sub(charged)
+    }
+
+    /// Whether `col`'s flag falls if its current move has taken `elapsed` so far.
+    /// Once a flag has fallen it stays fallen.
+    pub fn check_flag(&mut self, col:ChessColour, elapsed:Duration)->bool{
+        if self.flagged.is_none()&&self.remaining_after(col, elapsed).is_zero()&&!elapsed.is_zero(){
+            self.flagged=Some(col);
+            self.remaining[get_colour_idx(col)]=Duration::ZERO;
+        }
+        self.flagged==Some(col)
+    }
+
+    pub fn flagged(&self)->Option<ChessColour>{
+        self.flagged
+    }
+
+    /// Stops `col`'s clock after a move that took `elapsed`, applying the delay or
+    /// increment and any time added for reaching the next stage. Returns false and
+    /// leaves the clock unchanged except for the flag if the time had already run out.
+    pub fn press(&mut self, col:ChessColour, elapsed:Duration)->bool{
+        if self.check_flag(col, elapsed){
+            return false;
+        }
+        let idx=get_colour_idx(col);
+        let stage=self.stages[self.stage[idx]];
+        let mut remaining=self.remaining_after(col, elapsed);
+        remaining+=match stage.delay{
+            DelayMode::None|DelayMode::SimpleDelay(_)=>Duration::ZERO,
+            DelayMode::Increment(increment)=>increment,
+            DelayMode::Bronstein(delay)=>elapsed.min(delay),
+        };
+        self.moves_made[idx]+=1;
+        let stage_end:u32=self.stages[..=self.stage[idx]].iter().map(|stage| stage.moves.unwrap_or(0)).sum();
+        if stage.moves.is_some()&&self.moves_made[idx]==stage_end&&self.stage[idx]+1<self.stages.len(){
+            self.stage[idx]+=1;
+            remaining+=self.stages[self.stage[idx]].time;
+        }
+        self.remaining[idx]=remaining;
+        true
+    }
+
+    /// The game status once the clock is taken into account: a fallen flag loses,
+    /// unless the opponent could not have mated in which case it is a draw.
+    pub fn status(&self, board:&ChessBoard)->GameStatus{
+        let status=board.status();
+        let Some(col)=self.flagged else {
+            return status;
+        };
+        if status.is_over(){
+            return status;
+        }
+        if has_mating_material(get_op_col(col), *board){
+            GameStatus::Timeout(get_op_col(col))
+        }
+        else{
+            GameStatus::TimeoutVsInsufficientMaterial
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s:u64)->Duration{
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_delay_modes(){
+        let mut clock=Clock::fischer(secs(60), secs(2));
+        assert!(clock.press(ChessColour::White, secs(5)));
+        assert_eq!(clock.remaining(ChessColour::White), secs(57));
+
+        let mut clock=Clock::bronstein(secs(60), secs(3));
+        assert!(clock.press(ChessColour::White, secs(2)));
+        assert_eq!(clock.remaining(ChessColour::White), secs(60));
+        assert!(clock.press(ChessColour::White, secs(10)));
+        assert_eq!(clock.remaining(ChessColour::White), secs(53));
+
+        let mut clock=Clock::simple_delay(secs(60), secs(5));
+        assert_eq!(clock.remaining_after(ChessColour::Black, secs(4)), secs(60));
+        assert!(clock.press(ChessColour::Black, secs(8)));
+        assert_eq!(clock.remaining(ChessColour::Black), secs(57));
+    }
+    #[test]
+    fn test_multi_stage(){
+        let mut clock=Clock::from_pgn_time_control("40/5400+30:1800+30").unwrap();
+        assert_eq!(clock.stages.len(), 2);
+        for _ in 0..39{
+            assert!(clock.press(ChessColour::White, secs(100)));
+        }
+        assert_eq!(clock.remaining(ChessColour::White), secs(5400-39*70));
+        assert!(clock.press(ChessColour::White, secs(100)));
+        assert_eq!(clock.remaining(ChessColour::White), secs(5400-40*70+1800));
+        assert_eq!(Clock::from_pgn_time_control("600d5").unwrap().stages[0].delay, DelayMode::SimpleDelay(secs(5)));
+        assert_eq!(Clock::from_pgn_time_control("5+x"), None);
+    }
+    #[test]
+    fn test_flag_fall_status(){
+        let mut clock=Clock::sudden_death(secs(10));
+        assert!(!clock.press(ChessColour::White, secs(11)));
+        assert_eq!(clock.flagged(), Some(ChessColour::White));
+        assert_eq!(clock.status(&new_board()), GameStatus::Timeout(ChessColour::Black));
+        let lone_king=ChessBoard::from_fen("8/8/4k3/8/8/8/4K3/7R w - - 0 1").unwrap();
+        let mut clock=Clock::sudden_death(secs(10));
+        clock.check_flag(ChessColour::White, secs(10));
+        assert_eq!(clock.status(&lone_king), GameStatus::TimeoutVsInsufficientMaterial);
+    }
+}
@@ -0,0 +1,153 @@
+use super::*;
+use crate::fen::get_piece_char;
+use std::fmt;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BoardStyle {
+    /// FEN letters, `.` for empty squares.
+    Ascii,
+    /// Chess glyphs, `·` for empty squares.
+    Unicode,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DisplayOptions {
+    pub style: BoardStyle,
+    pub coordinates: bool,
+    /// Draw the board from Black's side.
+    pub flipped: bool,
+    /// Squares to mark, e.g. the mask returned by `get_moves`. Marked pieces are
+    /// bracketed and marked empty squares drawn as `*`.
+    pub highlight: u64,
+}
+
+impl Default for DisplayOptions{
+    fn default()->DisplayOptions{
+        DisplayOptions{style: BoardStyle::Ascii, coordinates: true, flipped: false, highlight: 0}
+    }
+}
+
+/// A board paired with its display options, see `ChessBoard::display`.
+#[derive(Copy, Clone)]
+pub struct BoardDisplay {
+    board: ChessBoard,
+    options: DisplayOptions,
+}
+
+fn get_piece_glyph(kind:ChessPieceKind, col:ChessColour)->char{
+    match (col, kind){
+        (ChessColour::White, ChessPieceKind::King)=>'♔',
+        (ChessColour::White, ChessPieceKind::Queen)=>'♕',
+        (ChessColour::White, ChessPieceKind::Rook)=>'♖',
+        (ChessColour::White, ChessPieceKind::Bishop)=>'♗',
+        (ChessColour::White, ChessPieceKind::Knight)=>'♘',
+        (ChessColour::White, ChessPieceKind::Pawn)=>'♙',
+        (ChessColour::Black, ChessPieceKind::King)=>'♚',
+        (ChessColour::Black, ChessPieceKind::Queen)=>'♛',
+        (ChessColour::Black, ChessPieceKind::Rook)=>'♜',
+        (ChessColour::Black, ChessPieceKind::Bishop)=>'♝',
+        (ChessColour::Black, ChessPieceKind::Knight)=>'♞',
+        (ChessColour::Black, ChessPieceKind::Pawn)=>'♟',
+    }
+}
+
+impl ChessBoard{
+    pub fn display(&self, options:DisplayOptions)->BoardDisplay{
+        BoardDisplay{board: *self, options}
+    }
+}
+
+impl fmt::Display for BoardDisplay{
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        let options=self.options;
+        let files:Vec<u8>=if options.flipped {(1..=8).rev().collect()} else {(1..=8).collect()};
+        let ranks:Vec<u8>=if options.flipped {(1..=8).collect()} else {(1..=8).rev().collect()};
+        for rank in ranks{
+            let mut line=String::new();
+            if options.coordinates{
+                line.push(char::from(b'0'+rank));
+            }
+            let mut prev_marked=false;
+            for (idx, file) in files.iter().enumerate(){
+                let pos=get_u64_pos(rank, *file);
+                let piece=get_piece_bit_mask(pos, self.board);
+                let marked=(pos&options.highlight)>0&&piece.is_some();
+                // Brackets take the place of the separating spaces so columns stay aligned.
+                let separator=match (prev_marked, marked){
+                    (true, true)=>'|',
+                    (true, false)=>']',
+                    (false, true)=>'[',
+                    (false, false)=>' ',
+                };
+                if idx>0||options.coordinates||marked{
+                    line.push(separator);
+                }
+                line.push(match (piece, options.style){
+                    (Some(p), BoardStyle::Ascii)=>get_piece_char(p.kind, p.colour),
+                    (Some(p), BoardStyle::Unicode)=>get_piece_glyph(p.kind, p.colour),
+                    (None, _) if (pos&options.highlight)>0=>'*',
+                    (None, BoardStyle::Ascii)=>'.',
+                    (None, BoardStyle::Unicode)=>'·',
+                });
+                prev_marked=marked;
+            }
+            if prev_marked{
+                line.push(']');
+            }
+            writeln!(f, "{}", line)?;
+        }
+        if options.coordinates{
+            let names:Vec<String>=files.iter().map(|file| char::from(b'a'+file-1).to_string()).collect();
+            writeln!(f, "  {}", names.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ChessBoard{
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        self.display(DisplayOptions::default()).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_start(){
+        let expected="\
+8 r n b q k b n r
+7 p p p p p p p p
+6 . . . . . . . .
+5 . . . . . . . .
+4 . . . . . . . .
+3 . . . . . . . .
+2 P P P P P P P P
+1 R N B Q K B N R
+  a b c d e f g h
+";
+        assert_eq!(new_board().to_string(), expected);
+    }
+    #[test]
+    fn test_unicode_flipped(){
+        let board=ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let options=DisplayOptions{style: BoardStyle::Unicode, coordinates: false, flipped: true, ..Default::default()};
+        let text=board.display(options).to_string();
+        let lines:Vec<&str>=text.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], "· · · ♔ · · · ♖");
+        assert_eq!(lines[7], "· · · ♚ · · · ·");
+    }
+    #[test]
+    fn test_highlight(){
+        let board=ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let rook=get_piece_bit_mask(get_square_from_name("a1").unwrap(), board).unwrap();
+        let options=DisplayOptions{highlight: get_moves(rook, board), ..Default::default()};
+        let text=board.display(options).to_string();
+        assert!(text.contains("8 * . . . k . . .\n"));
+        assert!(text.contains("1 R * * * K . . .\n"));
+        let options=DisplayOptions{coordinates: false, highlight: get_u64_pos(1, 1)|get_u64_pos(1, 2)|get_u64_pos(1, 5), ..Default::default()};
+        assert!(board.display(options).to_string().ends_with("[R]* . .[K]. . .\n"));
+    }
+}
@@ -0,0 +1,816 @@
+//! Distance-to-mate tables for endings of up to four pieces, generated by
+//! retrograde analysis over every placement of the pieces.
+//!
+//! En passant captures are left out of the analysis, so tables with pawns on both
+//! sides can be off where one would decide the game; positions with an en passant
+//! capture available are not probed.
+
+use super::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const MAX_TABLE_PIECES:usize=4;
+const PROMOTIONS:[ChessPieceKind;4]=[ChessPieceKind::Queen, ChessPieceKind::Rook, ChessPieceKind::Bishop, ChessPieceKind::Knight];
+const MAGIC:&[u8;4]=b"SKTB";
+const VERSION:u8=1;
+
+// Stored values: 0 is a draw, odd values are wins in that many plies, other even
+// values are losses in two plies fewer.
+const DRAW:u8=0;
+const ILLEGAL:u8=255;
+const MAX_DISTANCE:u32=252;
+
+const KING_STEPS:[(i8, i8);8]=[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const KNIGHT_STEPS:[(i8, i8);8]=[(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const ROOK_STEPS:[(i8, i8);4]=[(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_STEPS:[(i8, i8);4]=[(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// Distance to mate in plies from the side to move's point of view. `Loss(0)` is a
+/// position where the side to move is mated.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Dtm {
+    Win(u32),
+    Draw,
+    Loss(u32),
+}
+
+impl Dtm{
+    fn from_value(value:u8)->Option<Dtm>{
+        match value{
+            ILLEGAL=>None,
+            DRAW=>Some(Dtm::Draw),
+            v if v%2==1=>Some(Dtm::Win(u32::from(v))),
+            v=>Some(Dtm::Loss(u32::from(v)-2)),
+        }
+    }
+
+    fn value(&self)->u8{
+        match self{
+            Dtm::Win(plies)=>*plies as u8,
+            Dtm::Draw=>DRAW,
+            Dtm::Loss(plies)=>(*plies+2) as u8,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DtmError {
+    Io(io::Error),
+    /// Not a material name like `KRvK`, or more than four pieces.
+    InvalidMaterial(String),
+    CorruptedTable,
+}
+
+impl fmt::Display for DtmError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            DtmError::Io(err)=>write!(f, "could not read table: {}", err),
+            DtmError::InvalidMaterial(name)=>write!(f, "cannot build a table for {}", name),
+            DtmError::CorruptedTable=>write!(f, "table file is corrupted"),
+        }
+    }
+}
+
+impl std::error::Error for DtmError {}
+
+impl From<io::Error> for DtmError{
+    fn from(err:io::Error)->DtmError{
+        DtmError::Io(err)
+    }
+}
+
+// A piece on a square numbered from a1 = 0 to h8 = 63.
+pub(crate) type Placed=(ChessPieceKind, ChessColour, usize);
+
+fn get_order(kind:ChessPieceKind)->usize{
+    PIECE_ORDER.iter().position(|other| *other==kind).unwrap_or(0)
+}
+
+fn get_steps(sq:usize, steps:&[(i8, i8)])->u64{
+    let (file, rank)=((sq%8) as i8, (sq/8) as i8);
+    let mut out=0;
+    for (df, dr) in steps{
+        let (f, r)=(file+df, rank+dr);
+        if (0..8).contains(&f)&&(0..8).contains(&r){
+            out|=1<<(r*8+f);
+        }
+    }
+    out
+}
+
+fn get_slides(sq:usize, occupied:u64, steps:&[(i8, i8)])->u64{
+    let mut out=0;
+    for (df, dr) in steps{
+        let (mut f, mut r)=((sq%8) as i8+df, (sq/8) as i8+dr);
+        while (0..8).contains(&f)&&(0..8).contains(&r){
+            let bit=1u64<<(r*8+f);
+            out|=bit;
+            if (occupied&bit)>0{break;}
+            f+=df;
+            r+=dr;
+        }
+    }
+    out
+}
+
+// King and knight moves from each square.
+fn get_step_tables()->&'static [[u64;64];2]{
+    static TABLES:OnceLock<[[u64;64];2]>=OnceLock::new();
+    TABLES.get_or_init(|| {
+        [std::array::from_fn(|sq| get_steps(sq, &KING_STEPS)), std::array::from_fn(|sq| get_steps(sq, &KNIGHT_STEPS))]
+    })
+}
+
+pub(crate) fn get_attacks(kind:ChessPieceKind, col:ChessColour, sq:usize, occupied:u64)->u64{
+    match kind{
+        ChessPieceKind::King=>get_step_tables()[0][sq],
+        ChessPieceKind::Knight=>get_step_tables()[1][sq],
+        ChessPieceKind::Rook=>get_slides(sq, occupied, &ROOK_STEPS),
+        ChessPieceKind::Bishop=>get_slides(sq, occupied, &BISHOP_STEPS),
+        ChessPieceKind::Queen=>get_slides(sq, occupied, &ROOK_STEPS)|get_slides(sq, occupied, &BISHOP_STEPS),
+        ChessPieceKind::Pawn=>{
+            let dr=if col==ChessColour::White {1} else {-1};
+            get_steps(sq, &[(-1, dr), (1, dr)])
+        }
+    }
+}
+
+fn get_occupied(pieces:&[Placed])->u64{
+    pieces.iter().fold(0, |out, piece| out|(1<<piece.2))
+}
+
+pub(crate) fn is_king_attacked(col:ChessColour, pieces:&[Placed])->bool{
+    let occupied=get_occupied(pieces);
+    let Some(king)=pieces.iter().find(|p| p.0==ChessPieceKind::King&&p.1==col) else {return false;};
+    pieces.iter().filter(|p| p.1!=col).any(|p| (get_attacks(p.0, p.1, p.2, occupied)&(1<<king.2))>0)
+}
+
+// Material name with white's pieces first, e.g. `KQvKR`.
+fn get_material_name(pieces:&[Placed])->String{
+    let side=|col:ChessColour|{
+        let mut kinds:Vec<ChessPieceKind>=pieces.iter().filter(|p| p.1==col).map(|p| p.0).collect();
+        kinds.sort_by_key(|kind| get_order(*kind));
+        kinds.into_iter().map(|kind| fen::get_piece_char(kind, ChessColour::White)).collect::<String>()
+    };
+    format!("{}v{}", side(ChessColour::White), side(ChessColour::Black))
+}
+
+fn get_flipped_name(name:&str)->String{
+    match name.split_once('v'){
+        Some((white, black))=>format!("{}v{}", black, white),
+        None=>name.to_string(),
+    }
+}
+
+// The name a material is stored under: the side with more material first.
+fn get_canonical_name(name:&str)->String{
+    let weight=|side:&str| side.chars().map(|c| match c{
+        'Q'=>9,
+        'R'=>5,
+        'B'|'N'=>3,
+        'P'=>1,
+        _=>0,
+    }).sum::<u32>();
+    let flipped=get_flipped_name(name);
+    let (white, black)=name.split_once('v').unwrap_or((name, ""));
+    if (weight(black), black)>(weight(white), white) {flipped} else {name.to_string()}
+}
+
+// The same position with colours swapped and the board mirrored top to bottom.
+fn get_flipped(pieces:&[Placed])->Vec<Placed>{
+    pieces.iter().map(|(kind, col, sq)| (*kind, get_op_col(*col), sq^56)).collect()
+}
+
+#[derive(Clone, Debug)]
+struct Layout {
+    // White's pieces then black's, each in K Q R B N P order.
+    pieces: Vec<(ChessPieceKind, ChessColour)>,
+    has_pawns: bool,
+    // Squares the white king is folded into by symmetry: the a-d files, and ranks
+    // 1-4 as well without pawns.
+    king_squares: usize,
+    size: usize,
+}
+
+impl Layout{
+    fn from_name(name:&str)->Option<Layout>{
+        let (white, black)=name.split_once('v')?;
+        if !white.starts_with('K')||!black.starts_with('K')||white.len()+black.len()>MAX_TABLE_PIECES{return None;}
+        let mut pieces=Vec::new();
+        for (side, col) in [(white, ChessColour::White), (black, ChessColour::Black)]{
+            let mut kinds=Vec::new();
+            for c in side.chars(){
+                let kind=PIECE_ORDER.iter().copied().find(|kind| fen::get_piece_char(*kind, ChessColour::White)==c)?;
+                if kind==ChessPieceKind::King&&!kinds.is_empty(){return None;}
+                kinds.push(kind);
+            }
+            kinds.sort_by_key(|kind| get_order(*kind));
+            pieces.extend(kinds.into_iter().map(|kind| (kind, col)));
+        }
+        let has_pawns=pieces.iter().any(|p| p.0==ChessPieceKind::Pawn);
+        let king_squares=if has_pawns {32} else {16};
+        let size=2*king_squares*64usize.pow(pieces.len() as u32-1);
+        Some(Layout{pieces, has_pawns, king_squares, size})
+    }
+
+    // Index of a position whose pieces are in layout order.
+    fn index(&self, pieces:&[Placed], stm:ChessColour)->usize{
+        let king=pieces[0].2;
+        let mut mirror=if king%8>3 {7} else {0};
+        if !self.has_pawns&&king/8>3{
+            mirror|=56;
+        }
+        let king=king^mirror;
+        let mut idx=usize::from(stm==ChessColour::Black)*self.king_squares+(king/8)*4+king%8;
+        for piece in pieces[1..].iter(){
+            idx=idx*64+(piece.2^mirror);
+        }
+        idx
+    }
+
+    fn decode(&self, mut idx:usize)->([Placed;MAX_TABLE_PIECES], ChessColour){
+        let mut out=[(ChessPieceKind::King, ChessColour::White, 0);MAX_TABLE_PIECES];
+        for i in (1..self.pieces.len()).rev(){
+            out[i]=(self.pieces[i].0, self.pieces[i].1, idx%64);
+            idx/=64;
+        }
+        let king=idx%self.king_squares;
+        out[0]=(ChessPieceKind::King, ChessColour::White, (king/4)*8+king%4);
+        let stm=if idx/self.king_squares==0 {ChessColour::White} else {ChessColour::Black};
+        (out, stm)
+    }
+}
+
+fn is_valid(pieces:&[Placed], stm:ChessColour)->bool{
+    let occupied=get_occupied(pieces);
+    if occupied.count_ones() as usize!=pieces.len(){return false;}
+    if pieces.iter().any(|p| p.0==ChessPieceKind::Pawn&&(p.2<8||p.2>=56)){return false;}
+    !is_king_attacked(get_op_col(stm), pieces)
+}
+
+/// A generated table for one material balance, e.g. `KQvK`.
+#[derive(Clone, Debug)]
+pub struct DtmTable {
+    name: String,
+    layout: Layout,
+    values: Vec<u8>,
+}
+
+impl DtmTable{
+    pub fn name(&self)->&str{
+        &self.name
+    }
+
+    fn probe(&self, pieces:&[Placed], stm:ChessColour)->Option<Dtm>{
+        Dtm::from_value(*self.values.get(self.layout.index(pieces, stm))?)
+    }
+
+    /// The file form of the table: a short header, then the values of the legal
+    /// placements Huffman coded. Illegal placements are worked out again on loading.
+    pub fn to_bytes(&self)->Vec<u8>{
+        let mut counts=[0u64;256];
+        for value in self.values.iter().filter(|v| **v!=ILLEGAL){
+            counts[usize::from(*value)]+=1;
+        }
+        let lengths=get_code_lengths(&counts);
+        let codes=get_codes(&lengths);
+        let mut out=MAGIC.to_vec();
+        out.push(VERSION);
+        out.push(self.name.len() as u8);
+        out.extend_from_slice(self.name.as_bytes());
+        out.extend_from_slice(&lengths);
+        let mut buffer=0u64;
+        let mut bits=0;
+        for value in self.values.iter().filter(|v| **v!=ILLEGAL){
+            let len=u32::from(lengths[usize::from(*value)]);
+            let code=codes[usize::from(*value)];
+            for i in (0..len).rev(){
+                buffer=(buffer<<1)|((code>>i)&1);
+                bits+=1;
+                if bits==8{
+                    out.push(buffer as u8);
+                    buffer=0;
+                    bits=0;
+                }
+            }
+        }
+        if bits>0{
+            out.push((buffer<<(8-bits)) as u8);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes:&[u8])->Result<DtmTable, DtmError>{
+        if bytes.len()<6||&bytes[..4]!=MAGIC||bytes[4]!=VERSION{
+            return Err(DtmError::CorruptedTable);
+        }
+        let name_end=6+usize::from(bytes[5]);
+        let name=bytes.get(6..name_end).and_then(|name| std::str::from_utf8(name).ok()).ok_or(DtmError::CorruptedTable)?;
+        let layout=Layout::from_name(name).ok_or_else(|| DtmError::InvalidMaterial(name.to_string()))?;
+        let lengths=bytes.get(name_end..name_end+256).ok_or(DtmError::CorruptedTable)?;
+        let mut decoder=HuffmanDecoder::new(lengths, &bytes[name_end+256..]).ok_or(DtmError::CorruptedTable)?;
+        let n=layout.pieces.len();
+        let mut values=Vec::with_capacity(layout.size);
+        for idx in 0..layout.size{
+            let (pieces, stm)=layout.decode(idx);
+            if !is_valid(&pieces[..n], stm){
+                values.push(ILLEGAL);
+                continue;
+            }
+            match decoder.next(){
+                Some(ILLEGAL)|None=>return Err(DtmError::CorruptedTable),
+                Some(value)=>values.push(value),
+            }
+        }
+        Ok(DtmTable{name: name.to_string(), layout, values})
+    }
+
+    pub fn open<P:AsRef<Path>>(path:P)->Result<DtmTable, DtmError>{
+        DtmTable::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save<P:AsRef<Path>>(&self, path:P)->io::Result<()>{
+        std::fs::write(path, self.to_bytes())
+    }
+}
+
+// Huffman code lengths for the given symbol counts; a lone symbol gets one bit.
+fn get_code_lengths(counts:&[u64;256])->[u8;256]{
+    let mut lengths=[0u8;256];
+    // Each node holds its weight and the symbols below it.
+    let mut nodes:Vec<(u64, Vec<usize>)>=counts.iter().enumerate()
+        .filter(|(_, count)| **count>0)
+        .map(|(symbol, count)| (*count, vec![symbol]))
+        .collect();
+    if nodes.len()==1{
+        lengths[nodes[0].1[0]]=1;
+    }
+    while nodes.len()>1{
+        nodes.sort_by_key(|node| std::cmp::Reverse(node.0));
+        let (weight_a, symbols_a)=nodes.pop().unwrap_or_default();
+        let (weight_b, mut symbols_b)=nodes.pop().unwrap_or_default();
+        symbols_b.extend(symbols_a);
+        for symbol in symbols_b.iter(){
+            lengths[*symbol]+=1;
+        }
+        nodes.push((weight_a+weight_b, symbols_b));
+    }
+    lengths
+}
+
+// Canonical codes: shorter codes first, ties broken by symbol.
+fn get_codes(lengths:&[u8;256])->[u64;256]{
+    let mut codes=[0u64;256];
+    let mut code=0u64;
+    for len in 1..=64u8{
+        for symbol in 0..256{
+            if lengths[symbol]==len{
+                codes[symbol]=code;
+                code+=1;
+            }
+        }
+        code<<=1;
+    }
+    codes
+}
+
+struct HuffmanDecoder<'a> {
+    // Number of codes of each length, and the symbols in canonical order.
+    counts: [u64;65],
+    symbols: Vec<u8>,
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl<'a> HuffmanDecoder<'a>{
+    fn new(lengths:&[u8], data:&'a [u8])->Option<HuffmanDecoder<'a>>{
+        let mut counts=[0u64;65];
+        let mut symbols=Vec::new();
+        for len in 1..=64u8{
+            for (symbol, other) in lengths.iter().enumerate(){
+                if *other==len{
+                    counts[usize::from(len)]+=1;
+                    symbols.push(symbol as u8);
+                }
+            }
+        }
+        if lengths.iter().any(|len| *len>64){return None;}
+        Some(HuffmanDecoder{counts, symbols, data, bit: 0})
+    }
+
+    fn next(&mut self)->Option<u8>{
+        let mut code=0u64;
+        let mut first=0u64;
+        let mut index=0u64;
+        for len in 1..=64{
+            let byte=self.data.get(self.bit/8)?;
+            code|=u64::from((byte>>(7-self.bit%8))&1);
+            self.bit+=1;
+            let count=self.counts[len];
+            if code<first+count{
+                return self.symbols.get((index+code-first) as usize).copied();
+            }
+            index+=count;
+            first=(first+count)<<1;
+            code<<=1;
+        }
+        None
+    }
+}
+
+// A position after a move, either inside the table being built or in another one.
+enum Successor {
+    Inner(usize),
+    Exit(Dtm),
+}
+
+/// A set of distance-to-mate tables, generated or loaded from files.
+#[derive(Clone, Debug, Default)]
+pub struct DtmTablebase {
+    tables: HashMap<String, DtmTable>,
+}
+
+impl DtmTablebase{
+    pub fn new()->DtmTablebase{
+        DtmTablebase::default()
+    }
+
+    pub fn insert(&mut self, table:DtmTable){
+        self.tables.insert(table.name.clone(), table);
+    }
+
+    pub fn get(&self, name:&str)->Option<&DtmTable>{
+        self.tables.get(&get_canonical_name(name))
+    }
+
+    /// Loads every `.dtm` file in `dir`.
+    pub fn open_dir<P:AsRef<Path>>(dir:P)->Result<DtmTablebase, DtmError>{
+        let mut out=DtmTablebase::new();
+        for entry in std::fs::read_dir(dir)?{
+            let path=entry?.path();
+            if path.extension().is_some_and(|ext| ext=="dtm"){
+                out.insert(DtmTable::open(&path)?);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Writes each table to `dir` as `<material>.dtm`.
+    pub fn save_dir<P:AsRef<Path>>(&self, dir:P)->io::Result<()>{
+        for table in self.tables.values(){
+            table.save(dir.as_ref().join(format!("{}.dtm", table.name)))?;
+        }
+        Ok(())
+    }
+
+    /// Builds the table for `material`, e.g. `KRvK` or `KBNvK`, along with the
+    /// tables its captures and promotions lead into. Tables already present are
+    /// reused.
+    pub fn generate(&mut self, material:&str)->Result<&DtmTable, DtmError>{
+        let name=get_canonical_name(material);
+        let layout=Layout::from_name(&name).ok_or_else(|| DtmError::InvalidMaterial(material.to_string()))?;
+        if !self.tables.contains_key(&name){
+            for dependency in get_dependencies(&layout){
+                self.generate(&dependency)?;
+            }
+            let values=self.get_values(&layout).ok_or_else(|| DtmError::InvalidMaterial(material.to_string()))?;
+            self.insert(DtmTable{name: name.clone(), layout, values});
+        }
+        self.tables.get(&name).ok_or(DtmError::InvalidMaterial(name))
+    }
+
+    /// Distance to mate of a position, or None when no table covers it. Positions
+    /// with castling rights or an en passant capture available are not covered.
+    pub fn dtm(&self, board:&ChessBoard)->Option<Dtm>{
+        for col in [ChessColour::White, ChessColour::Black]{
+            let king=get_piece_bit_mask(get_king_pos(col, *board), *board)?;
+            if get_castling_rook(king, true, *board).is_some()||get_castling_rook(king, false, *board).is_some(){
+                return None;
+            }
+        }
+        let occupied=get_all_piece_map(*board);
+        let en_passant=get_legal_moves(*board).into_iter().any(|mv| {
+            get_piece_bit_mask(mv.from, *board).is_some_and(|p| p.kind==ChessPieceKind::Pawn)
+                &&get_file_u64(mv.from)!=get_file_u64(mv.to)
+                &&(mv.to&occupied)==0
+        });
+        if en_passant{
+            return None;
+        }
+        let pieces:Vec<Placed>=board.pieces.iter()
+            .filter(|p| !p.is_captured)
+            .map(|p| (p.kind, p.colour, get_square_index(p.pos)))
+            .collect();
+        self.probe(&pieces, board.current_move)
+    }
+
+    pub(crate) fn probe(&self, pieces:&[Placed], stm:ChessColour)->Option<Dtm>{
+        if pieces.iter().all(|p| p.0==ChessPieceKind::King){
+            return Some(Dtm::Draw);
+        }
+        let name=get_material_name(pieces);
+        let (table, mut pieces, stm)=match self.tables.get(&name){
+            Some(table)=>(table, pieces.to_vec(), stm),
+            None=>(self.tables.get(&get_flipped_name(&name))?, get_flipped(pieces), get_op_col(stm)),
+        };
+        pieces.sort_by_key(|p| (p.1==ChessColour::Black, get_order(p.0)));
+        table.probe(&pieces, stm)
+    }
+
+    // Every legal move from a position, with its target in the table or its value
+    // for the opponent in a smaller one.
+    fn get_successors(&self, layout:&Layout, pieces:&[Placed], stm:ChessColour, out:&mut Vec<Successor>){
+        out.clear();
+        let occupied=get_occupied(pieces);
+        let own=pieces.iter().filter(|p| p.1==stm).fold(0u64, |out, p| out|(1<<p.2));
+        let exit=|after:&[Placed]| self.probe(after, get_op_col(stm)).unwrap_or(Dtm::Draw);
+        for (i, &(kind, col, from)) in pieces.iter().enumerate(){
+            if col!=stm{continue;}
+            let mut targets=get_attacks(kind, col, from, occupied)&!own;
+            if kind==ChessPieceKind::Pawn{
+                targets&=occupied;
+                let forward=if col==ChessColour::White {from+8} else {from-8};
+                if (occupied&(1<<forward))==0{
+                    targets|=1<<forward;
+                    let start=if col==ChessColour::White {from/8==1} else {from/8==6};
+                    let double=if col==ChessColour::White {from+16} else {from.wrapping_sub(16)};
+                    if start&&(occupied&(1<<double))==0{
+                        targets|=1<<double;
+                    }
+                }
+            }
+            while targets>0{
+                let to=targets.trailing_zeros() as usize;
+                targets&=targets-1;
+                let mut next=[(ChessPieceKind::King, ChessColour::White, 0);MAX_TABLE_PIECES];
+                next[..pieces.len()].copy_from_slice(pieces);
+                next[i].2=to;
+                let captured=pieces.iter().position(|p| p.2==to);
+                let mut len=0;
+                let mut after=next;
+                for (j, &piece) in next[..pieces.len()].iter().enumerate(){
+                    if Some(j)!=captured{
+                        after[len]=piece;
+                        len+=1;
+                    }
+                }
+                let after=&mut after[..len];
+                if is_king_attacked(stm, after){continue;}
+                if kind==ChessPieceKind::Pawn&&!(8..56).contains(&to){
+                    let moved=after.iter().position(|p| p.2==to).unwrap_or(0);
+                    for promotion in PROMOTIONS{
+                        after[moved].0=promotion;
+                        out.push(Successor::Exit(exit(after)));
+                    }
+                }
+                else if captured.is_some(){
+                    out.push(Successor::Exit(exit(after)));
+                }
+                else{
+                    out.push(Successor::Inner(layout.index(after, get_op_col(stm))));
+                }
+            }
+        }
+    }
+
+    // Indices of the positions with the other side to move that reach this one by a
+    // quiet move.
+    fn get_predecessors(&self, layout:&Layout, values:&[u8], pieces:&[Placed], stm:ChessColour, out:&mut Vec<usize>){
+        out.clear();
+        let mover=get_op_col(stm);
+        let occupied=get_occupied(pieces);
+        for (i, &(kind, col, to)) in pieces.iter().enumerate(){
+            if col!=mover{continue;}
+            let mut sources=0u64;
+            if kind==ChessPieceKind::Pawn{
+                let (back, start_rank, double_rank)=if col==ChessColour::White {(to.wrapping_sub(8), 1, 3)} else {(to+8, 6, 4)};
+                if back<64&&(occupied&(1<<back))==0&&(8..56).contains(&back){
+                    sources|=1<<back;
+                    let double=if col==ChessColour::White {to.wrapping_sub(16)} else {to+16};
+                    if to/8==double_rank&&double/8==start_rank&&(occupied&(1<<double))==0{
+                        sources|=1<<double;
+                    }
+                }
+            }
+            else{
+                sources=get_attacks(kind, col, to, occupied)&!occupied;
+            }
+            while sources>0{
+                let from=sources.trailing_zeros() as usize;
+                sources&=sources-1;
+                let mut before=[(ChessPieceKind::King, ChessColour::White, 0);MAX_TABLE_PIECES];
+                before[..pieces.len()].copy_from_slice(pieces);
+                before[i].2=from;
+                let idx=layout.index(&before[..pieces.len()], mover);
+                if values[idx]!=ILLEGAL{
+                    out.push(idx);
+                }
+            }
+        }
+    }
+
+    // Retrograde analysis: mates first, then positions that can reach a loss in d-1
+    // plies are wins in d, and positions whose every move reaches a win of at most
+    // d-1 plies are losses in d.
+    fn get_values(&self, layout:&Layout)->Option<Vec<u8>>{
+        let n=layout.pieces.len();
+        let mut values=vec![DRAW;layout.size];
+        // Positions a move into another table decides, by the distance it decides them at.
+        let mut pending:Vec<Vec<usize>>=vec![Vec::new();MAX_DISTANCE as usize+2];
+        let mut layer=Vec::new();
+        let mut successors=Vec::new();
+        for (idx, value) in values.iter_mut().enumerate(){
+            let (pieces, stm)=layout.decode(idx);
+            if !is_valid(&pieces[..n], stm){
+                *value=ILLEGAL;
+            }
+        }
+        for (idx, value) in values.iter().enumerate(){
+            if *value==ILLEGAL{continue;}
+            let (pieces, stm)=layout.decode(idx);
+            self.get_successors(layout, &pieces[..n], stm, &mut successors);
+            if successors.is_empty(){
+                if is_king_attacked(stm, &pieces[..n]){
+                    layer.push(idx);
+                }
+                continue;
+            }
+            let mut quickest_win:Option<u32>=None;
+            let mut slowest_loss:Option<u32>=Some(0);
+            for successor in successors.iter(){
+                match successor{
+                    Successor::Exit(Dtm::Loss(plies))=>quickest_win=Some(quickest_win.map_or(plies+1, |best| best.min(plies+1))),
+                    Successor::Exit(Dtm::Win(plies))=>slowest_loss=slowest_loss.map(|worst| worst.max(plies+1)),
+                    Successor::Exit(Dtm::Draw)=>slowest_loss=None,
+                    Successor::Inner(_)=>{}
+                }
+            }
+            if let Some(plies)=quickest_win{
+                pending[plies as usize].push(idx);
+            }
+            else if let Some(plies)=slowest_loss.filter(|plies| *plies>0){
+                pending[plies as usize].push(idx);
+            }
+        }
+
+        for idx in layer.iter(){
+            values[*idx]=Dtm::Loss(0).value();
+        }
+        let mut predecessors=Vec::new();
+        for distance in 1..=MAX_DISTANCE{
+            let mut next=Vec::new();
+            let mut candidates=Vec::new();
+            for idx in layer.iter(){
+                let (pieces, stm)=layout.decode(*idx);
+                self.get_predecessors(layout, &values, &pieces[..n], stm, &mut predecessors);
+                candidates.extend_from_slice(&predecessors);
+            }
+            candidates.append(&mut pending[distance as usize]);
+            candidates.sort_unstable();
+            candidates.dedup();
+            for idx in candidates{
+                if values[idx]!=DRAW{continue;}
+                let decided=distance%2==1||self.is_lost(layout, &values, idx, distance, &mut successors);
+                if decided{
+                    values[idx]=if distance%2==1 {Dtm::Win(distance)} else {Dtm::Loss(distance)}.value();
+                    next.push(idx);
+                }
+            }
+            layer=next;
+            if layer.is_empty()&&pending[distance as usize..].iter().all(|p| p.is_empty()){
+                return Some(values);
+            }
+        }
+        None
+    }
+
+    fn is_lost(&self, layout:&Layout, values:&[u8], idx:usize, distance:u32, successors:&mut Vec<Successor>)->bool{
+        let (pieces, stm)=layout.decode(idx);
+        self.get_successors(layout, &pieces[..layout.pieces.len()], stm, successors);
+        successors.iter().all(|successor| match successor{
+            Successor::Inner(next)=>values[*next]!=ILLEGAL&&values[*next]%2==1,
+            Successor::Exit(Dtm::Win(plies))=>*plies<distance,
+            Successor::Exit(_)=>false,
+        })
+    }
+}
+
+// Materials that captures and promotions lead into, kings-only endings aside.
+fn get_dependencies(layout:&Layout)->Vec<String>{
+    let pieces:Vec<Placed>=layout.pieces.iter().map(|p| (p.0, p.1, 0)).collect();
+    let mut out:Vec<String>=Vec::new();
+    let mut add=|pieces:&[Placed]|{
+        if pieces.iter().any(|p| p.0!=ChessPieceKind::King){
+            let name=get_canonical_name(&get_material_name(pieces));
+            if !out.contains(&name){
+                out.push(name);
+            }
+        }
+    };
+    for (i, piece) in pieces.iter().enumerate(){
+        if piece.0==ChessPieceKind::King{continue;}
+        let mut captured=pieces.clone();
+        captured.remove(i);
+        add(&captured);
+        if piece.0!=ChessPieceKind::Pawn{continue;}
+        for promotion in PROMOTIONS{
+            let mut promoted=pieces.clone();
+            promoted[i].0=promotion;
+            add(&promoted);
+            // Promoting with a capture.
+            for (j, other) in pieces.iter().enumerate(){
+                if other.1!=piece.1&&other.0!=ChessPieceKind::King{
+                    let mut both=promoted.clone();
+                    both.remove(j);
+                    add(&both);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dtm(tb:&DtmTablebase, fen:&str)->Option<Dtm>{
+        tb.dtm(&ChessBoard::from_fen(fen).unwrap())
+    }
+
+    fn longest_win(table:&DtmTable)->u32{
+        table.values.iter().filter(|v| **v!=ILLEGAL&&**v%2==1).map(|v| u32::from(*v)).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_heavy_pieces(){
+        let mut tb=DtmTablebase::new();
+        // Known maxima: mate in 10 with the queen and in 16 with the rook.
+        assert_eq!(longest_win(tb.generate("KQvK").unwrap()), 19);
+        assert_eq!(longest_win(tb.generate("KvKR").unwrap()), 31);
+        assert_eq!(tb.get("KvKR").unwrap().name(), "KRvK");
+        assert_eq!(dtm(&tb, "k7/8/1K6/8/8/8/7Q/8 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(dtm(&tb, "k6Q/8/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        assert!(matches!(dtm(&tb, "k7/8/1K6/8/8/8/7q/8 w - - 0 1"), Some(Dtm::Loss(_))));
+        assert_eq!(dtm(&tb, "K7/8/1k6/8/8/8/8/7r b - - 0 1"), Some(Dtm::Win(1)));
+        // The rook can be taken, or the king is stalemated.
+        assert_eq!(dtm(&tb, "8/8/8/8/8/3k4/1r6/K7 w - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(dtm(&tb, "8/8/8/8/8/2k5/1r6/K7 w - - 0 1"), Some(Dtm::Draw));
+        assert!(matches!(dtm(&tb, "8/8/8/8/8/8/1r6/K1k5 b - - 0 1"), Some(Dtm::Win(_))));
+        assert_eq!(dtm(&tb, "8/8/8/8/8/4k3/8/4K2R w K - 0 1"), None);
+        assert_eq!(dtm(&tb, "8/8/8/8/8/3k4/8/3KB3 w - - 0 1"), None);
+    }
+    #[test]
+    fn test_four_piece_layout(){
+        let layout=Layout::from_name("KBNvK").unwrap();
+        assert_eq!(layout.size, 2*16*64*64*64);
+        for idx in (0..layout.size).step_by(9973){
+            let (pieces, stm)=layout.decode(idx);
+            assert_eq!(layout.index(&pieces, stm), idx);
+        }
+        let pieces=[(ChessPieceKind::King, ChessColour::White, 62), (ChessPieceKind::Bishop, ChessColour::White, 5), (ChessPieceKind::Knight, ChessColour::White, 52), (ChessPieceKind::King, ChessColour::Black, 63)];
+        let (decoded, stm)=layout.decode(layout.index(&pieces, ChessColour::Black));
+        // Folded onto the a-d files and ranks 1-4 by mirroring both ways.
+        assert_eq!((decoded, stm), ([(ChessPieceKind::King, ChessColour::White, 1), (ChessPieceKind::Bishop, ChessColour::White, 58), (ChessPieceKind::Knight, ChessColour::White, 11), (ChessPieceKind::King, ChessColour::Black, 0)], ChessColour::Black));
+    }
+    // Takes minutes unoptimised, so run it with
+    //
+    //     cargo test --release --lib dtm -- --ignored
+    #[test]
+    #[ignore="slow without optimisation"]
+    fn test_four_pieces(){
+        let mut tb=DtmTablebase::new();
+        // The longest bishop and knight mate is mate in 33.
+        assert_eq!(longest_win(tb.generate("KBNvK").unwrap()), 65);
+        assert_eq!(dtm(&tb, "7k/4N3/6K1/8/5B2/8/8/8 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(dtm(&tb, "7k/4N3/6K1/4B3/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        // Black takes the loose knight.
+        assert_eq!(dtm(&tb, "8/8/3B4/8/8/8/8/K5kN b - - 0 1"), Some(Dtm::Draw));
+    }
+    #[test]
+    fn test_pawn_ending(){
+        let mut tb=DtmTablebase::new();
+        tb.generate("KPvK").unwrap();
+        assert!(tb.get("KQvK").is_some()&&tb.get("KNvK").is_some());
+        assert_eq!(dtm(&tb, "8/8/8/8/8/4k3/4P3/4K3 w - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(dtm(&tb, "k7/P7/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(dtm(&tb, "k7/2P5/1K6/8/8/8/8/8 w - - 0 1"), Some(Dtm::Win(1)));
+        assert!(matches!(dtm(&tb, "8/8/8/8/8/8/4P3/4K2k w - - 0 1"), Some(Dtm::Win(_))));
+        assert!(matches!(dtm(&tb, "8/8/8/8/8/8/4p3/4k2K b - - 0 1"), Some(Dtm::Win(_))));
+    }
+    #[test]
+    fn test_serialisation(){
+        let mut tb=DtmTablebase::new();
+        let table=tb.generate("KRvK").unwrap().clone();
+        let bytes=table.to_bytes();
+        assert!(bytes.len()<table.values.len()/2);
+        let read=DtmTable::from_bytes(&bytes).unwrap();
+        assert_eq!((read.name(), &read.values), (table.name(), &table.values));
+        assert!(matches!(DtmTable::from_bytes(&bytes[..bytes.len()-1]), Err(DtmError::CorruptedTable)));
+        assert!(matches!(tb.generate("KQRvKQR"), Err(DtmError::InvalidMaterial(_))));
+        assert!(matches!(tb.generate("KK"), Err(DtmError::InvalidMaterial(_))));
+    }
+}
@@ -0,0 +1,316 @@
+//! Compact binary encodings: a fixed 32 bytes for a position, and one byte a move
+//! for games, each move stored as its index among the sorted legal moves.
+//!
+//! A position is laid out as:
+//! - bytes 0-7: the occupied squares, as a little-endian bitboard;
+//! - bytes 8-23: a four-bit code for each occupied square from bit 0 up, low nibble first;
+//! - byte 24: bit 0 set when Black is to move, bit 1 for Chess960;
+//! - byte 25: the halfmove clock; bytes 26-27: the move number, little-endian;
+//! - bytes 28-31: zero.
+//!
+//! Piece codes 0-5 are the white pawn, knight, bishop, rook, queen and king, 6-11 the
+//! black ones. 12 is a pawn that may be taken en passant, of the side not to move,
+//! and 13 and 14 are white and black rooks that can still castle.
+
+use super::*;
+use crate::fen::set_ep_square;
+use crate::game::{Game, GameResult, Termination};
+use std::fmt;
+
+pub const POSITION_SIZE:usize=32;
+/// Pieces that fit in a packed position.
+pub const MAX_PACKED_PIECES:usize=32;
+
+const KINDS:[ChessPieceKind;6]=[
+    ChessPieceKind::Pawn,
+    ChessPieceKind::Knight,
+    ChessPieceKind::Bishop,
+    ChessPieceKind::Rook,
+    ChessPieceKind::Queen,
+    ChessPieceKind::King,
+];
+const EP_PAWN:u8=12;
+const WHITE_CASTLING_ROOK:u8=13;
+const BLACK_CASTLING_ROOK:u8=14;
+
+const RESULTS:[GameResult;4]=[GameResult::Ongoing, GameResult::WhiteWins, GameResult::BlackWins, GameResult::Draw];
+const TERMINATIONS:[Termination;10]=[
+    Termination::Checkmate,
+    Termination::Stalemate,
+    Termination::InsufficientMaterial,
+    Termination::FiftyMoveRule,
+    Termination::ThreefoldRepetition,
+    Termination::TimeForfeit,
+    Termination::TimeoutVsInsufficientMaterial,
+    Termination::Resignation,
+    Termination::Agreement,
+    Termination::Adjudication,
+];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EncodingError {
+    /// The position has more pieces than a packed position holds.
+    TooManyPieces,
+    Truncated,
+    InvalidPosition,
+    /// The move at this ply is not a legal move index.
+    InvalidMove(usize),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            EncodingError::TooManyPieces=>write!(f, "position has more than {} pieces", MAX_PACKED_PIECES),
+            EncodingError::Truncated=>write!(f, "encoded data is truncated"),
+            EncodingError::InvalidPosition=>write!(f, "invalid packed position"),
+            EncodingError::InvalidMove(ply)=>write!(f, "invalid move at ply {}", ply),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+fn get_colour_offset(col:ChessColour)->u8{
+    if col==ChessColour::White {0} else {KINDS.len() as u8}
+}
+
+/// Packs the position into 32 bytes. The repetition history is not kept.
+pub fn encode_position(board:&ChessBoard)->Result<[u8;POSITION_SIZE], EncodingError>{
+    let board=*board;
+    let occupied=get_all_piece_map(board);
+    if occupied.count_ones() as usize>MAX_PACKED_PIECES{
+        return Err(EncodingError::TooManyPieces);
+    }
+    let mut castling_rooks:u64=0;
+    for col in [ChessColour::White, ChessColour::Black]{
+        let Some(king)=get_piece_bit_mask(get_king_pos(col, board), board) else {continue;};
+        for king_side in [true, false]{
+            if let Some(rook)=get_castling_rook(king, king_side, board){
+                castling_rooks|=rook.pos;
+            }
+        }
+    }
+    let ep=get_ep_capture_spots(get_op_col(board.current_move), board);
+    let ep_pawn=if board.current_move==ChessColour::White {ep>>8} else {ep<<8};
+
+    let mut pieces:Vec<ChessPiece>=board.pieces.iter().filter(|p| !p.is_captured).copied().collect();
+    pieces.sort_by_key(|p| p.pos.trailing_zeros());
+    let mut out=[0u8;POSITION_SIZE];
+    out[..8].copy_from_slice(&occupied.to_le_bytes());
+    for (idx, piece) in pieces.iter().enumerate(){
+        let code=if (piece.pos&ep_pawn)>0{
+            EP_PAWN
+        }
+        else if (piece.pos&castling_rooks)>0{
+            if piece.colour==ChessColour::White {WHITE_CASTLING_ROOK} else {BLACK_CASTLING_ROOK}
+        }
+        else{
+            let kind=KINDS.iter().position(|kind| *kind==piece.kind).unwrap_or(0) as u8;
+            get_colour_offset(piece.colour)+kind
+        };
+        out[8+idx/2]|=code<<(4*(idx%2));
+    }
+    out[24]=u8::from(board.current_move==ChessColour::Black)|(u8::from(board.chess960)<<1);
+    out[25]=board.rule_50_moves;
+    out[26..28].copy_from_slice(&board.move_number.to_le_bytes());
+    Ok(out)
+}
+
+/// Unpacks a position written by `encode_position` from the first 32 bytes.
+pub fn decode_position(bytes:&[u8])->Result<ChessBoard, EncodingError>{
+    if bytes.len()<POSITION_SIZE{
+        return Err(EncodingError::Truncated);
+    }
+    let mut occupied_bytes=[0u8;8];
+    occupied_bytes.copy_from_slice(&bytes[..8]);
+    let occupied=u64::from_le_bytes(occupied_bytes);
+    if occupied.count_ones() as usize>MAX_PACKED_PIECES||bytes[24]>>2!=0{
+        return Err(EncodingError::InvalidPosition);
+    }
+    let current_move=if (bytes[24]&1)>0 {ChessColour::Black} else {ChessColour::White};
+
+    let mut template:Vec<(ChessColour, ChessPieceKind, u8, u8)>=Vec::new();
+    let mut castling_rooks:u64=0;
+    let mut ep_pawn:u64=0;
+    let mut rest=occupied;
+    let mut idx=0;
+    while rest>0{
+        let pos=rest&rest.wrapping_neg();
+        rest&=rest-1;
+        let code=(bytes[8+idx/2]>>(4*(idx%2)))&0x0f;
+        idx+=1;
+        let (col, kind)=match code{
+            0..=11=>{
+                let col=if code<6 {ChessColour::White} else {ChessColour::Black};
+                (col, KINDS[code as usize%6])
+            }
+            EP_PAWN=>{
+                ep_pawn|=pos;
+                (get_op_col(current_move), ChessPieceKind::Pawn)
+            }
+            WHITE_CASTLING_ROOK|BLACK_CASTLING_ROOK=>{
+                castling_rooks|=pos;
+                (if code==WHITE_CASTLING_ROOK {ChessColour::White} else {ChessColour::Black}, ChessPieceKind::Rook)
+            }
+            _=>return Err(EncodingError::InvalidPosition),
+        };
+        template.push((col, kind, pos.trailing_zeros() as u8/8+1, get_file_u64(pos)));
+    }
+
+    let mut board=board_from_template(&template, current_move);
+    let castling_colours:Vec<ChessColour>=board.pieces.iter().filter(|p| (p.pos&castling_rooks)>0).map(|p| p.colour).collect();
+    for piece in board.pieces.iter_mut(){
+        if matches!(piece.kind, ChessPieceKind::King|ChessPieceKind::Rook){
+            piece.has_moved=(piece.pos&castling_rooks)==0&&!(piece.kind==ChessPieceKind::King&&castling_colours.contains(&piece.colour));
+        }
+    }
+    board.chess960=(bytes[24]&2)>0;
+    if ep_pawn.count_ones()>1{
+        return Err(EncodingError::InvalidPosition);
+    }
+    if ep_pawn>0{
+        let square=if current_move==ChessColour::White {ep_pawn<<8} else {ep_pawn>>8};
+        if !set_ep_square(&mut board, square){
+            return Err(EncodingError::InvalidPosition);
+        }
+    }
+    board.rule_50_moves=bytes[25];
+    board.move_number=u16::from_le_bytes([bytes[26], bytes[27]]);
+    if board.move_number==0||board.validate().is_err(){
+        return Err(EncodingError::InvalidPosition);
+    }
+    board.rule_repetition[0]=[0;64];
+    if (board.rule_50_moves as usize)<board.rule_repetition.len(){
+        board.rule_repetition[board.rule_50_moves as usize]=hash_board_state(board);
+    }
+    Ok(board)
+}
+
+// Legal moves in a fixed order, so that indices do not depend on move generation.
+fn get_sorted_moves(board:ChessBoard)->Vec<Move>{
+    let mut moves=get_legal_moves(board);
+    moves.sort_by_key(|mv| (mv.from.trailing_zeros(), mv.to.trailing_zeros(), mv.promotion.map_or(0, |kind| get_piece_hash(kind)+1)));
+    moves
+}
+
+/// The index of `mv` among the legal moves, sorted by from square, to square and
+/// promotion. No position has more than 256 legal moves, so this fits a byte.
+pub fn encode_move_index(board:&ChessBoard, mv:Move)->Option<u8>{
+    get_sorted_moves(*board).iter().position(|legal| *legal==mv).map(|idx| idx as u8)
+}
+
+pub fn decode_move_index(board:&ChessBoard, index:u8)->Option<Move>{
+    get_sorted_moves(*board).get(index as usize).copied()
+}
+
+/// The start position, a byte each for the result and termination, then a byte a
+/// move. Clocks, draw offers and tags are left out.
+pub fn encode_game(game:&Game)->Result<Vec<u8>, EncodingError>{
+    let mut out=encode_position(&game.start)?.to_vec();
+    out.push(RESULTS.iter().position(|result| *result==game.result()).unwrap_or(0) as u8);
+    out.push(game.termination().and_then(|termination| TERMINATIONS.iter().position(|t| *t==termination)).map_or(0, |idx| idx as u8+1));
+    let mut board=game.start;
+    for (ply, mv) in game.moves.iter().enumerate(){
+        out.push(encode_move_index(&board, *mv).ok_or(EncodingError::InvalidMove(ply))?);
+        board.make_move(*mv);
+    }
+    Ok(out)
+}
+
+pub fn decode_game(bytes:&[u8])->Result<Game, EncodingError>{
+    let start=decode_position(bytes)?;
+    if bytes.len()<POSITION_SIZE+2{
+        return Err(EncodingError::Truncated);
+    }
+    let result=*RESULTS.get(bytes[POSITION_SIZE] as usize).ok_or(EncodingError::InvalidPosition)?;
+    let termination=match bytes[POSITION_SIZE+1]{
+        0=>None,
+        code=>Some(*TERMINATIONS.get(code as usize-1).ok_or(EncodingError::InvalidPosition)?),
+    };
+    let mut game=Game::from_board(start);
+    for (ply, index) in bytes[POSITION_SIZE+2..].iter().enumerate(){
+        let Some(mv)=decode_move_index(&game.board, *index) else {
+            return Err(EncodingError::InvalidMove(ply));
+        };
+        if !game.make_move(mv){
+            return Err(EncodingError::InvalidMove(ply));
+        }
+    }
+    game.result=result;
+    game.termination=termination;
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(board:&ChessBoard)->ChessBoard{
+        decode_position(&encode_position(board).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_position_round_trip(){
+        for fen in [
+            fen::START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/8/8/3pP3/8/8/k6K b - e3 7 60",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 99 300",
+        ]{
+            let board=ChessBoard::from_fen(fen).unwrap();
+            let decoded=round_trip(&board);
+            assert_eq!(decoded.to_fen(), board.to_fen());
+            assert_eq!(decoded.chess960, board.chess960);
+        }
+        let crowded=ChessBoard::from_fen("rnbqkbnr/pppppppp/n7/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(encode_position(&crowded), Err(EncodingError::TooManyPieces));
+    }
+    #[test]
+    fn test_random_games_round_trip(){
+        let mut seed:u64=0x9E3779B97F4A7C15;
+        let mut next=|| {
+            seed^=seed<<13;
+            seed^=seed>>7;
+            seed^=seed<<17;
+            seed
+        };
+        for _ in 0..8{
+            let mut game=Game::new();
+            while !game.is_over()&&game.moves.len()<120{
+                let board=game.board;
+                assert_eq!(round_trip(&board).to_fen(), board.to_fen());
+                let moves=get_legal_moves(board);
+                let mv=moves[(next()%moves.len() as u64) as usize];
+                let index=encode_move_index(&board, mv).unwrap();
+                assert_eq!(decode_move_index(&board, index), Some(mv));
+                game.make_move(mv);
+            }
+            let bytes=encode_game(&game).unwrap();
+            assert_eq!(bytes.len(), POSITION_SIZE+2+game.moves.len());
+            let decoded=decode_game(&bytes).unwrap();
+            assert_eq!(decoded.moves, game.moves);
+            assert_eq!((decoded.result(), decoded.termination()), (game.result(), game.termination()));
+        }
+    }
+    #[test]
+    fn test_invalid_data(){
+        let bytes=encode_game(&Game::new()).unwrap();
+        assert_eq!(decode_position(&bytes[..20]).err(), Some(EncodingError::Truncated));
+        let mut bad=bytes.clone();
+        bad.push(20);
+        assert_eq!(decode_game(&bad).err(), Some(EncodingError::InvalidMove(0)));
+        let mut bad=bytes;
+        bad[8]|=0x0f;
+        assert_eq!(decode_position(&bad).err(), Some(EncodingError::InvalidPosition));
+        let mut bad=encode_position(&new_board()).unwrap();
+        bad[26..28].copy_from_slice(&[0, 0]);
+        assert_eq!(decode_position(&bad).err(), Some(EncodingError::InvalidPosition));
+        // No kings, and a pawn on the back rank.
+        let mut bad=[0u8;32];
+        bad[..8].copy_from_slice(&(get_u64_pos(1, 8)|get_u64_pos(2, 4)).to_le_bytes());
+        bad[26]=1;
+        assert_eq!(decode_position(&bad).err(), Some(EncodingError::InvalidPosition));
+    }
+}
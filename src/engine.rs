@@ -0,0 +1,655 @@
+//! The engine side of UCI and CECP (XBoard), as spoken by skye-engine. Which one
+//! is used is decided by the first command: `uci` or `xboard`.
+
+use super::*;
+use crate::game::Game;
+use crate::search::{run_search, SearchLimits, SearchOptions, SearchResult};
+use crate::transposition::TranspositionTable;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub const ENGINE_NAME:&str="Skye";
+pub const ENGINE_AUTHOR:&str="the Skye developers";
+
+// Bounds of the UCI options.
+const MAX_MULTIPV:usize=64;
+const MAX_THREADS:usize=256;
+const DEFAULT_HASH_MEGABYTES:usize=16;
+const MAX_HASH_MEGABYTES:usize=4096;
+// Moves the rest of the game is assumed to last when the time control does not say.
+const DEFAULT_MOVES_TO_GO:u32=30;
+// Kept back from every move for communication overhead.
+const MOVE_OVERHEAD:Duration=Duration::from_millis(20);
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Protocol {
+    Uci,
+    Cecp,
+}
+
+/// The time to spend on a move with `remaining` on the clock.
+pub fn get_move_time(remaining:Duration, increment:Duration, moves_to_go:Option<u32>)->Duration{
+    let moves=moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let time=remaining/moves+increment*3/4;
+    time.min(remaining/2).saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1))
+}
+
+// A CECP time control: `level MPS BASE INC`, where a session of `moves` resets the clock.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+struct Level {
+    moves: u32,
+    base: Duration,
+    increment: Duration,
+}
+
+// Seconds, possibly fractional, as given to `st` and `level`.
+impl Level{
+    // The clock at the start of a game, if a time control was given.
+    fn get_start_time(&self)->Option<Duration>{
+        (self.base>Duration::ZERO).then_some(self.base)
+    }
+}
+
+fn parse_seconds(value:&str)->Option<Duration>{
+    let seconds:f64=value.parse().ok()?;
+    if !seconds.is_finite()||seconds<0.0{return None;}
+    Some(Duration::from_secs_f64(seconds))
+}
+
+// `level` gives the base time as minutes or minutes:seconds.
+fn parse_level(args:&[&str])->Option<Level>{
+    let [moves, base, increment]=args else {return None;};
+    let base=match base.split_once(':'){
+        Some((minutes, seconds))=>Duration::from_secs(minutes.parse::<u64>().ok()?*60+seconds.parse::<u64>().ok()?),
+        None=>parse_seconds(base)?*60,
+    };
+    Some(Level{moves: moves.parse().ok()?, base, increment: parse_seconds(increment)?})
+}
+
+type Output=Arc<Mutex<Box<dyn Write+Send>>>;
+
+fn send(output:&Output, line:&str)->io::Result<()>{
+    let mut output=output.lock().unwrap_or_else(|err| err.into_inner());
+    writeln!(output, "{}", line)?;
+    output.flush()
+}
+
+// A search running on its own thread.
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    // Set to also drop the result instead of sending it.
+    cancel: Arc<AtomicBool>,
+    thread: JoinHandle<Option<SearchResult>>,
+}
+
+/// Protocol state of a running engine. Searches run on a background thread so
+/// commands such as `stop` and `isready` are answered while it thinks.
+pub struct Engine {
+    protocol: Option<Protocol>,
+    output: Output,
+    game: Game,
+    search: Option<RunningSearch>,
+    options: SearchOptions,
+    // Kept between searches, cleared for a new game.
+    table: Arc<TranspositionTable>,
+    // CECP: no moves of its own in force mode, otherwise it plays `side`.
+    force: bool,
+    side: ChessColour,
+    level: Level,
+    // Moves the engine played since `new` or `level`, counting off `level` sessions.
+    moves_played: u32,
+    move_time: Option<Duration>,
+    max_depth: Option<u8>,
+    time: Option<Duration>,
+}
+
+impl Engine{
+    /// An engine writing its replies to `output`.
+    pub fn new(output:impl Write+Send+'static)->Engine{
+        Engine{
+            protocol: None,
+            output: Arc::new(Mutex::new(Box::new(output))),
+            game: Game::new(),
+            search: None,
+            options: SearchOptions::default(),
+            table: Arc::new(TranspositionTable::new(DEFAULT_HASH_MEGABYTES)),
+            force: false,
+            side: ChessColour::Black,
+            level: Level::default(),
+            moves_played: 0,
+            move_time: None,
+            max_depth: None,
+            time: None,
+        }
+    }
+
+    /// The protocol chosen by the first command, if any yet.
+    pub fn protocol(&self)->Option<Protocol>{
+        self.protocol
+    }
+
+    /// Handles one line of input. Returns false once the engine should exit.
+    pub fn handle(&mut self, line:&str)->io::Result<bool>{
+        let words:Vec<&str>=line.split_whitespace().collect();
+        let Some(&command)=words.first() else {return Ok(true);};
+        if self.protocol.is_none(){
+            self.protocol=match command{
+                "uci"=>Some(Protocol::Uci),
+                "xboard"|"protover"=>Some(Protocol::Cecp),
+                _=>None,
+            };
+        }
+        match self.protocol{
+            Some(Protocol::Uci)=>self.handle_uci(&words),
+            Some(Protocol::Cecp)=>self.handle_cecp(&words),
+            None=>Ok(command!="quit"),
+        }
+    }
+
+    fn send(&self, line:&str)->io::Result<()>{
+        send(&self.output, line)
+    }
+
+    // Starts a search of the current position. With `infinite` the result is held
+    // back until `stop`, as UCI requires.
+    fn start_search(&mut self, limits:SearchLimits, infinite:bool){
+        self.stop_search();
+        let stop=Arc::new(AtomicBool::new(false));
+        let cancel=Arc::new(AtomicBool::new(false));
+        let output=self.output.clone();
+        let board=self.game.board;
+        let protocol=self.protocol;
+        let (thread_stop, thread_cancel)=(stop.clone(), cancel.clone());
+        let options=self.options;
+        let table=self.table.clone();
+        let thread=std::thread::spawn(move || {
+            let (lines, nodes)=run_search(board, limits, &options, &table, &thread_stop);
+            while infinite&&!thread_stop.load(Ordering::Relaxed){
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let best_move=lines[0].best_move().or_else(|| get_legal_moves(board).first().copied());
+            let best=best_move.map(|mv| mv.to_uci());
+            let mut replies=Vec::new();
+            match (protocol, best){
+                (Some(Protocol::Uci), best)=>{
+                    for (idx, line) in lines.iter().enumerate(){
+                        let mut info=format!("info depth {} multipv {} score {} nodes {}", line.depth, idx+1, get_uci_score(line.score), nodes);
+                        if !line.pv.is_empty(){
+                            let pv:Vec<String>=line.pv.iter().map(|mv| mv.to_uci()).collect();
+                            info.push_str(&format!(" pv {}", pv.join(" ")));
+                        }
+                        replies.push(info);
+                    }
+                    replies.push(format!("bestmove {}", best.as_deref().unwrap_or("0000")));
+                }
+                (_, Some(best))=>replies.push(format!("move {}", best)),
+                (_, None)=>{}
+            }
+            // Cancelling and sending are decided under the lock, so a search whose
+            // result was sent always reports it.
+            let mut output=output.lock().unwrap_or_else(|err| err.into_inner());
+            if thread_cancel.load(Ordering::Relaxed){return None;}
+            for reply in replies{
+                let _=writeln!(output, "{}", reply);
+            }
+            let _=output.flush();
+            let line=&lines[0];
+            Some(SearchResult{best_move, score: line.score, depth: line.depth, nodes, pv: line.pv.clone()})
+        });
+        self.search=Some(RunningSearch{stop, cancel, thread});
+    }
+
+    // Waits for the running search to end by itself. Returns its result if it was
+    // sent.
+    fn wait_search(&mut self)->Option<SearchResult>{
+        let search=self.search.take()?;
+        search.thread.join().ok().flatten()
+    }
+
+    // Ends the running search early; its result is still sent and returned.
+    fn stop_search(&mut self)->Option<SearchResult>{
+        let search=self.search.as_ref()?;
+        search.stop.store(true, Ordering::Relaxed);
+        self.wait_search()
+    }
+
+    // Ends the running search without sending its result. Returns the result only
+    // if it had already been sent.
+    fn cancel_search(&mut self)->Option<SearchResult>{
+        let search=self.search.as_ref()?;
+        search.cancel.store(true, Ordering::Relaxed);
+        search.stop.store(true, Ordering::Relaxed);
+        self.wait_search()
+    }
+
+    fn handle_uci(&mut self, words:&[&str])->io::Result<bool>{
+        match words[0]{
+            "uci"=>{
+                self.send(&format!("id name {}", ENGINE_NAME))?;
+                self.send(&format!("id author {}", ENGINE_AUTHOR))?;
+                self.send(&format!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MEGABYTES, MAX_HASH_MEGABYTES))?;
+                self.send(&format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS))?;
+                self.send(&format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTIPV))?;
+                self.send("uciok")?;
+            }
+            "isready"=>self.send("readyok")?,
+            "setoption"=>self.set_uci_option(&words[1..]),
+            "ucinewgame"=>{
+                self.stop_search();
+                self.table.clear();
+                self.game=Game::new();
+            }
+            "position"=>{
+                self.stop_search();
+                self.set_uci_position(&words[1..]);
+            }
+            "go"=>{
+                let (limits, infinite)=self.get_uci_limits(&words[1..]);
+                self.start_search(limits, infinite);
+            }
+            "stop"=>{
+                self.stop_search();
+            }
+            "quit"=>{
+                self.stop_search();
+                return Ok(false);
+            }
+            _=>{}
+        }
+        Ok(true)
+    }
+
+    // `setoption name NAME [value VALUE]`; unknown options are ignored.
+    fn set_uci_option(&mut self, args:&[&str]){
+        let value_at=args.iter().position(|word| *word=="value").unwrap_or(args.len());
+        if args.first()!=Some(&"name"){return;}
+        let name=args[1..value_at].join(" ");
+        let value=args.get(value_at+1..).map(|value| value.join(" ")).unwrap_or_default();
+        let Ok(value)=value.parse::<usize>() else {return;};
+        if name.eq_ignore_ascii_case("MultiPV"){
+            self.options.multipv=value.clamp(1, MAX_MULTIPV);
+        }
+        else if name.eq_ignore_ascii_case("Threads"){
+            self.options.threads=value.clamp(1, MAX_THREADS);
+        }
+        else if name.eq_ignore_ascii_case("Hash"){
+            self.stop_search();
+            self.table=Arc::new(TranspositionTable::new(value.clamp(1, MAX_HASH_MEGABYTES)));
+        }
+    }
+
+    // `position [startpos | fen FEN] [moves MOVE...]`. A bad FEN or move leaves the
+    // position at the last good point.
+    fn set_uci_position(&mut self, args:&[&str]){
+        let moves_at=args.iter().position(|word| *word=="moves").unwrap_or(args.len());
+        let board=match args.first(){
+            Some(&"startpos")=>Some(new_board()),
+            Some(&"fen")=>ChessBoard::from_fen(&args[1..moves_at].join(" ")).ok(),
+            _=>None,
+        };
+        let Some(board)=board else {return;};
+        self.game=Game::from_board(board);
+        for uci in args.iter().skip(moves_at+1){
+            let Some(mv)=self.game.board.parse_uci(uci) else {break;};
+            if !self.game.make_move(mv){break;}
+        }
+    }
+
+    fn get_uci_limits(&self, args:&[&str])->(SearchLimits, bool){
+        let mut limits=SearchLimits::default();
+        let mut infinite=false;
+        let (mut time, mut increment, mut moves_to_go)=(None, Duration::ZERO, None);
+        let white=self.game.board.current_move==ChessColour::White;
+        for (idx, word) in args.iter().enumerate(){
+            let value=args.get(idx+1).and_then(|value| value.parse::<u64>().ok());
+            match *word{
+                "depth"=>limits.depth=value.map(|depth| depth.min(u64::from(u8::MAX)) as u8),
+                "nodes"=>limits.nodes=value,
+                "movetime"=>limits.movetime=value.map(Duration::from_millis),
+                "movestogo"=>moves_to_go=value.map(|moves| moves as u32),
+                "wtime" if white=>time=value.map(Duration::from_millis),
+                "btime" if !white=>time=value.map(Duration::from_millis),
+                "winc" if white=>increment=value.map_or(Duration::ZERO, Duration::from_millis),
+                "binc" if !white=>increment=value.map_or(Duration::ZERO, Duration::from_millis),
+                "infinite"=>infinite=true,
+                _=>{}
+            }
+        }
+        if let (Some(time), None)=(time, limits.movetime){
+            limits.movetime=Some(get_move_time(time, increment, moves_to_go));
+        }
+        if infinite{
+            limits=SearchLimits{depth: Some(u8::MAX), ..Default::default()};
+        }
+        (limits, infinite)
+    }
+
+    fn handle_cecp(&mut self, words:&[&str])->io::Result<bool>{
+        match words[0]{
+            "protover"=>{
+                self.send(&format!("feature myname=\"{}\" setboard=1 usermove=1 ping=1 smp=1 san=0 sigint=0 sigterm=0 colors=0 done=1", ENGINE_NAME))?;
+            }
+            "ping"=>{
+                self.send(&format!("pong {}", words.get(1).unwrap_or(&"")))?;
+            }
+            "new"=>{
+                self.cancel_search();
+                self.table.clear();
+                self.game=Game::new();
+                self.force=false;
+                self.side=ChessColour::Black;
+                self.max_depth=None;
+                self.moves_played=0;
+                self.time=self.level.get_start_time();
+            }
+            "force"=>{
+                self.cancel_cecp_search();
+                self.force=true;
+            }
+            "go"=>{
+                self.cancel_cecp_search();
+                self.force=false;
+                self.side=self.game.board.current_move;
+                self.think();
+            }
+            "?"=>{
+                if let Some(result)=self.stop_search(){
+                    self.play_result(result);
+                }
+            }
+            "usermove"=>{
+                if let Some(mv)=words.get(1){
+                    self.cecp_user_move(mv)?;
+                }
+            }
+            "level"=>{
+                if let Some(level)=parse_level(&words[1..]){
+                    self.level=level;
+                    self.moves_played=0;
+                    self.move_time=None;
+                    self.time=level.get_start_time();
+                }
+            }
+            "st"=>{
+                self.move_time=words.get(1).and_then(|value| parse_seconds(value));
+            }
+            "cores"=>{
+                if let Some(threads)=words.get(1).and_then(|value| value.parse::<usize>().ok()){
+                    self.options.threads=threads.clamp(1, MAX_THREADS);
+                }
+            }
+            "sd"=>{
+                self.max_depth=words.get(1).and_then(|value| value.parse().ok());
+            }
+            // Clocks are given in centiseconds.
+            "time"=>{
+                self.time=words.get(1).and_then(|value| value.parse::<u64>().ok()).map(|cs| Duration::from_millis(cs*10));
+            }
+            "undo"=>{
+                self.cancel_cecp_search();
+                self.game.undo();
+            }
+            "remove"=>{
+                self.cancel_cecp_search();
+                self.game.undo();
+                self.game.undo();
+            }
+            "result"=>{
+                self.cancel_cecp_search();
+                self.force=true;
+            }
+            "setboard"=>{
+                self.cancel_cecp_search();
+                match ChessBoard::from_fen(&words[1..].join(" ")){
+                    Ok(board)=>self.game=Game::from_board(board),
+                    Err(err)=>self.send(&format!("tellusererror Illegal position: {}", err))?,
+                }
+            }
+            "quit"=>{
+                self.cancel_search();
+                return Ok(false);
+            }
+            "xboard"|"accepted"|"rejected"|"otim"|"random"|"post"|"nopost"|"hard"|"easy"|"computer"|"name"=>{}
+            // Without usermove=1 moves come bare.
+            mv=>{
+                if self.game.board.parse_uci(mv).is_some(){
+                    self.cecp_user_move(mv)?;
+                }
+                else {
+                    self.send(&format!("Error (unknown command): {}", mv))?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    // Stops the engine thinking without it moving. A move it already sent is
+    // played, since the GUI has seen it.
+    fn cancel_cecp_search(&mut self){
+        if let Some(result)=self.cancel_search(){
+            self.play_result(result);
+        }
+    }
+
+    fn play_result(&mut self, result:SearchResult){
+        if let Some(mv)=result.best_move{
+            if self.game.make_move(mv){
+                self.moves_played+=1;
+            }
+        }
+    }
+
+    fn cecp_user_move(&mut self, uci:&str)->io::Result<()>{
+        self.cancel_cecp_search();
+        let legal=self.game.board.parse_uci(uci).or_else(|| self.game.board.parse_san(uci));
+        match legal{
+            Some(mv) if self.game.make_move(mv)=>{
+                if !self.force&&self.game.board.current_move==self.side{
+                    self.think();
+                }
+                Ok(())
+            }
+            _=>self.send(&format!("Illegal move: {}", uci)),
+        }
+    }
+
+    fn think(&mut self){
+        if self.game.is_over(){return;}
+        let limits=self.get_cecp_limits();
+        self.start_search(limits, false);
+    }
+
+    fn get_cecp_limits(&self)->SearchLimits{
+        let mut limits=SearchLimits{depth: self.max_depth, ..Default::default()};
+        limits.movetime=self.move_time.or_else(|| {
+            let time=self.time?;
+            let moves_to_go=(self.level.moves>0).then(|| self.level.moves-self.moves_played%self.level.moves);
+            Some(get_move_time(time, self.level.increment, moves_to_go))
+        });
+        limits
+    }
+}
+
+// The score as UCI writes it: `cp N` or `mate N`.
+fn get_uci_score(score:i32)->String{
+    match crate::search::get_mate_in(score){
+        Some(moves)=>format!("mate {}", moves),
+        None=>format!("cp {}", score),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer{
+        fn write(&mut self, buf:&[u8])->io::Result<usize>{
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self)->io::Result<()>{
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer{
+        fn take(&self)->String{
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_uci_session(){
+        let output=SharedBuffer::default();
+        let mut engine=Engine::new(output.clone());
+        assert!(engine.handle("uci").unwrap());
+        assert_eq!(engine.protocol(), Some(Protocol::Uci));
+        assert!(output.take().ends_with("uciok\n"));
+        engine.handle("position fen 7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        engine.handle("go depth 3").unwrap();
+        engine.wait_search();
+        let reply=output.take();
+        assert!(reply.contains("score mate 1"));
+        assert!(reply.ends_with("bestmove a1a8\n"));
+
+        engine.handle("position startpos moves e2e4 e7e5").unwrap();
+        assert_eq!(engine.game.moves.len(), 2);
+        engine.handle("go infinite").unwrap();
+        engine.handle("isready").unwrap();
+        assert_eq!(output.take(), "readyok\n");
+        engine.handle("stop").unwrap();
+        assert!(output.take().contains("bestmove "));
+        assert!(!engine.handle("quit").unwrap());
+    }
+    #[test]
+    fn test_uci_multipv(){
+        let output=SharedBuffer::default();
+        let mut engine=Engine::new(output.clone());
+        engine.handle("uci").unwrap();
+        assert!(output.take().contains("option name MultiPV type spin"));
+        engine.handle("setoption name MultiPV value 3").unwrap();
+        engine.handle("position fen 7k/8/6K1/8/8/8/8/RR6 w - - 0 1").unwrap();
+        engine.handle("go depth 2").unwrap();
+        engine.wait_search();
+        let reply=output.take();
+        let infos:Vec<&str>=reply.lines().filter(|line| line.starts_with("info")).collect();
+        assert_eq!(infos.len(), 3);
+        for (idx, info) in infos.iter().enumerate(){
+            assert!(info.contains(&format!(" multipv {} ", idx+1)));
+        }
+        assert!(infos[1].contains("score mate 1"));
+        assert!(!infos[2].contains("score mate"));
+
+        engine.handle("setoption name Threads value 4").unwrap();
+        engine.handle("setoption name Hash value 1").unwrap();
+        assert_eq!((engine.options.threads, engine.table.len()), (4, 65536));
+        engine.handle("setoption name MultiPV value 1").unwrap();
+        engine.handle("position fen 7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        engine.handle("go depth 3").unwrap();
+        engine.wait_search();
+        assert!(output.take().ends_with("bestmove a1a8\n"));
+    }
+    // Lets the engine finish thinking and plays its move, as the next command would.
+    fn wait_move(engine:&mut Engine){
+        if let Some(result)=engine.wait_search(){
+            engine.play_result(result);
+        }
+    }
+
+    #[test]
+    fn test_cecp_session(){
+        let output=SharedBuffer::default();
+        let mut engine=Engine::new(output.clone());
+        engine.handle("xboard").unwrap();
+        engine.handle("protover 2").unwrap();
+        assert_eq!(engine.protocol(), Some(Protocol::Cecp));
+        assert!(output.take().contains("usermove=1"));
+        engine.handle("new").unwrap();
+        engine.handle("sd 2").unwrap();
+        engine.handle("usermove e2e4").unwrap();
+        engine.handle("ping 7").unwrap();
+        wait_move(&mut engine);
+        let reply=output.take();
+        assert!(reply.starts_with("move ")||reply.contains("\nmove "));
+        assert_eq!(engine.game.moves.len(), 2);
+        engine.handle("usermove e2e4").unwrap();
+        assert_eq!(output.take(), "Illegal move: e2e4\n");
+
+        engine.handle("force").unwrap();
+        engine.handle("undo").unwrap();
+        engine.handle("undo").unwrap();
+        assert!(engine.game.moves.is_empty());
+        engine.handle("setboard 7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        engine.handle("go").unwrap();
+        wait_move(&mut engine);
+        assert_eq!(output.take(), "move a1a8\n");
+        assert!(engine.game.is_over());
+        engine.handle("setboard not a fen").unwrap();
+        assert!(output.take().starts_with("tellusererror"));
+    }
+    #[test]
+    fn test_cecp_interrupts(){
+        let output=SharedBuffer::default();
+        let mut engine=Engine::new(output.clone());
+        engine.handle("xboard").unwrap();
+        engine.handle("new").unwrap();
+        engine.handle("st 30").unwrap();
+        for interrupt in ["force", "undo", "new", "setboard 7k/8/6K1/8/8/8/8/R7 b - - 0 1", "result 1-0 {adjudication}"]{
+            engine.handle("new").unwrap();
+            engine.handle("usermove e2e4").unwrap();
+            let start=std::time::Instant::now();
+            engine.handle(interrupt).unwrap();
+            assert!(start.elapsed()<Duration::from_secs(5));
+            assert!(engine.search.is_none());
+            assert_eq!(output.take(), "", "{} let the engine move", interrupt);
+        }
+        engine.handle("new").unwrap();
+        engine.handle("force").unwrap();
+        engine.handle("go").unwrap();
+        engine.handle("?").unwrap();
+        assert!(output.take().starts_with("move "));
+        assert_eq!(engine.game.moves.len(), 1);
+    }
+    #[test]
+    fn test_cecp_clock(){
+        let mut engine=Engine::new(io::sink());
+        engine.handle("xboard").unwrap();
+        engine.handle("level 40 5 0").unwrap();
+        assert_eq!(engine.get_cecp_limits().movetime, Some(Duration::from_millis(7480)));
+        engine.handle("time 6000").unwrap();
+        assert_eq!(engine.get_cecp_limits().movetime, Some(Duration::from_millis(1480)));
+        engine.handle("new").unwrap();
+        assert_eq!(engine.get_cecp_limits().movetime, Some(Duration::from_millis(7480)));
+        engine.handle("st 2").unwrap();
+        assert_eq!(engine.get_cecp_limits().movetime, Some(Duration::from_secs(2)));
+
+        // Sessions count the engine's own moves, not the move number of a set up position.
+        engine.handle("level 40 5 0").unwrap();
+        engine.handle("setboard 7k/8/6K1/8/8/8/8/R7 b - - 0 30").unwrap();
+        assert_eq!(engine.get_cecp_limits().movetime, Some(Duration::from_millis(7480)));
+        engine.handle("sd 1").unwrap();
+        engine.handle("go").unwrap();
+        wait_move(&mut engine);
+        let expected=get_move_time(Duration::from_secs(300), Duration::ZERO, Some(39));
+        assert_eq!(engine.get_cecp_limits().movetime, Some(expected));
+    }
+    #[test]
+    fn test_time_controls(){
+        assert_eq!(parse_level(&["40", "5", "0"]), Some(Level{moves: 40, base: Duration::from_secs(300), increment: Duration::ZERO}));
+        assert_eq!(parse_level(&["0", "2:30", "1.5"]), Some(Level{moves: 0, base: Duration::from_secs(150), increment: Duration::from_millis(1500)}));
+        assert_eq!(get_move_time(Duration::from_secs(60), Duration::ZERO, Some(10)), Duration::from_millis(5980));
+        assert_eq!(get_move_time(Duration::from_millis(10), Duration::from_secs(1), None), Duration::from_millis(1));
+
+        let mut engine=Engine::new(io::sink());
+        engine.handle("uci").unwrap();
+        engine.handle("position startpos moves e2e4").unwrap();
+        let (limits, infinite)=engine.get_uci_limits(&["wtime", "1000", "btime", "30000", "binc", "1000", "movestogo", "10"]);
+        assert_eq!((limits.movetime, infinite), (Some(Duration::from_millis(3730)), false));
+    }
+}
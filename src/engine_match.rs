@@ -0,0 +1,498 @@
+//! Engine-vs-engine matches: games from a set of openings with colours swapped,
+//! adjudication by score, and Elo and SPRT statistics for the result.
+
+use super::*;
+use crate::epd::read_epd;
+use crate::game::{Game, GameResult, Termination};
+use crate::pgn::read_pgn;
+use crate::search::{search, SearchLimits, SearchResult};
+use crate::uci::{GoParams, UciEngine, UciError};
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum PlayerError {
+    Io(io::Error),
+    /// An external engine failed or broke the protocol.
+    Engine(UciError),
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            PlayerError::Io(err)=>write!(f, "engine i/o failed: {}", err),
+            PlayerError::Engine(err)=>write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+impl From<io::Error> for PlayerError{
+    fn from(err:io::Error)->PlayerError{
+        PlayerError::Io(err)
+    }
+}
+
+impl PlayerError{
+    /// Whether the engine itself is at fault, e.g. crashed, hung or played an
+    /// illegal move. Such an error loses the game rather than ending the match.
+    pub fn is_forfeit(&self)->bool{
+        matches!(self, PlayerError::Engine(UciError::IllegalMove(_)|UciError::Exited|UciError::Timeout(_)))
+    }
+}
+
+impl From<UciError> for PlayerError{
+    fn from(err:UciError)->PlayerError{
+        PlayerError::Engine(err)
+    }
+}
+
+/// One side of a match.
+pub trait Player {
+    fn name(&self)->String;
+
+    /// Called before every game.
+    fn new_game(&mut self)->Result<(), PlayerError>{
+        Ok(())
+    }
+
+    /// Searches the game's current position. A result without a best move forfeits
+    /// the game.
+    fn play(&mut self, game:&Game, limits:SearchLimits)->Result<SearchResult, PlayerError>;
+}
+
+/// The built-in search, with its own limits if they should differ from the match's.
+#[derive(Clone, Debug)]
+pub struct SearchPlayer {
+    pub name: String,
+    pub limits: Option<SearchLimits>,
+}
+
+impl SearchPlayer{
+    pub fn new(name:&str)->SearchPlayer{
+        SearchPlayer{name: name.to_string(), limits: None}
+    }
+}
+
+impl Player for SearchPlayer{
+    fn name(&self)->String{
+        self.name.clone()
+    }
+
+    fn play(&mut self, game:&Game, limits:SearchLimits)->Result<SearchResult, PlayerError>{
+        Ok(search(game.board, self.limits.unwrap_or(limits)))
+    }
+}
+
+/// A UCI engine run as a child process.
+pub struct UciPlayer {
+    engine: UciEngine,
+}
+
+impl UciPlayer{
+    /// Starts the engine, sets the options and waits until it is ready.
+    pub fn spawn(path:&str, args:&[String], options:&[(String, String)])->Result<UciPlayer, PlayerError>{
+        let mut engine=UciEngine::spawn(path, args)?;
+        for (name, value) in options{
+            engine.set_option(name, Some(value))?;
+        }
+        engine.is_ready()?;
+        Ok(UciPlayer{engine})
+    }
+}
+
+impl Player for UciPlayer{
+    fn name(&self)->String{
+        self.engine.name().to_string()
+    }
+
+    fn new_game(&mut self)->Result<(), PlayerError>{
+        Ok(self.engine.new_game()?)
+    }
+
+    fn play(&mut self, game:&Game, limits:SearchLimits)->Result<SearchResult, PlayerError>{
+        // An engine given no limit at all would search forever.
+        let limits=if limits==SearchLimits::default() {SearchLimits{depth: Some(4), ..limits}} else {limits};
+        let found=self.engine.search(game, &GoParams{limits, ..Default::default()})?;
+        let mut result=SearchResult{best_move: found.best_move, score: 0, depth: 0, nodes: 0, pv: Vec::new()};
+        if let Some(info)=found.last_info(1){
+            result.score=info.score.map_or(0, |score| score.to_score());
+            result.depth=info.depth.unwrap_or(0).min(u32::from(u8::MAX)) as u8;
+            result.nodes=info.nodes.unwrap_or(0);
+            result.pv=info.pv.clone();
+        }
+        Ok(result)
+    }
+}
+
+/// Score thresholds for ending games early. Scores are each engine's own, from its
+/// side's point of view.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Adjudication {
+    /// A side that scores itself at or below minus this, for `resign_moves` moves in a
+    /// row while its opponent scores at least this, loses.
+    pub resign_score: Option<i32>,
+    pub resign_moves: usize,
+    /// The game is drawn once both sides score within this of zero for
+    /// `draw_moves` moves each, from ply `draw_after` on.
+    pub draw_score: Option<i32>,
+    pub draw_moves: usize,
+    pub draw_after: usize,
+    /// Games still going after this many plies are drawn.
+    pub max_plies: Option<usize>,
+}
+
+impl Default for Adjudication{
+    fn default()->Adjudication{
+        Adjudication{resign_score: Some(1000), resign_moves: 3, draw_score: Some(10), draw_moves: 8, draw_after: 80, max_plies: Some(400)}
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchOptions {
+    /// Games to play, in pairs with colours swapped; an odd number is rounded up.
+    pub games: usize,
+    pub limits: SearchLimits,
+    /// Plies of each opening to play before the engines take over, all if `None`.
+    pub opening_plies: Option<usize>,
+    pub adjudication: Adjudication,
+    /// Stops the match as soon as the test has decided.
+    pub sprt: Option<Sprt>,
+    pub event: String,
+}
+
+impl Default for MatchOptions{
+    fn default()->MatchOptions{
+        MatchOptions{
+            games: 2,
+            limits: SearchLimits{movetime: Some(Duration::from_millis(100)), ..Default::default()},
+            opening_plies: None,
+            adjudication: Adjudication::default(),
+            sprt: None,
+            event: "Engine match".to_string(),
+        }
+    }
+}
+
+/// Wins, losses and draws from the first player's point of view.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+fn get_elo(score:f64)->f64{
+    400.0*(score/(1.0-score)).log10()
+}
+
+fn get_expected_score(elo:f64)->f64{
+    1.0/(1.0+10f64.powf(-elo/400.0))
+}
+
+impl MatchScore{
+    pub fn games(&self)->u32{
+        self.wins+self.losses+self.draws
+    }
+
+    /// Points per game, draws counting half.
+    pub fn score(&self)->f64{
+        (f64::from(self.wins)+f64::from(self.draws)/2.0)/f64::from(self.games().max(1))
+    }
+
+    // Variance of a single game's points.
+    fn variance(&self)->f64{
+        let score=self.score();
+        let games=f64::from(self.games().max(1));
+        (f64::from(self.wins)*(1.0-score).powi(2)+f64::from(self.losses)*score.powi(2)+f64::from(self.draws)*(0.5-score).powi(2))/games
+    }
+
+    /// Elo difference of the first player over the second; `None` without games or
+    /// when one side scored everything.
+    pub fn elo(&self)->Option<f64>{
+        let score=self.score();
+        if self.games()==0||score<=0.0||score>=1.0{
+            return None;
+        }
+        Some(get_elo(score))
+    }
+
+    /// Half the width of the 95% confidence interval of `elo`.
+    pub fn elo_error(&self)->Option<f64>{
+        self.elo()?;
+        let margin=1.96*(self.variance()/f64::from(self.games())).sqrt();
+        let low=(self.score()-margin).max(1e-6);
+        let high=(self.score()+margin).min(1.0-1e-6);
+        Some((get_elo(high)-get_elo(low))/2.0)
+    }
+
+    fn add(&mut self, result:GameResult, first_is_white:bool){
+        match (result, first_is_white){
+            (GameResult::WhiteWins, true)|(GameResult::BlackWins, false)=>self.wins+=1,
+            (GameResult::WhiteWins, false)|(GameResult::BlackWins, true)=>self.losses+=1,
+            _=>self.draws+=1,
+        }
+    }
+}
+
+impl fmt::Display for MatchScore {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        write!(f, "+{} -{} ={}", self.wins, self.losses, self.draws)?;
+        match (self.elo(), self.elo_error()){
+            (Some(elo), Some(error))=>write!(f, " elo {:.1} +/- {:.1}", elo, error),
+            _=>Ok(()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SprtStatus {
+    Continue,
+    /// The first player is no better than `elo0`.
+    AcceptH0,
+    /// The first player is at least `elo1` better.
+    AcceptH1,
+}
+
+/// A sequential probability ratio test of `elo0` against `elo1`, with error rates
+/// `alpha` and `beta`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt{
+    pub fn new(elo0:f64, elo1:f64)->Sprt{
+        Sprt{elo0, elo1, alpha: 0.05, beta: 0.05}
+    }
+
+    /// The log-likelihood ratio of the score, with game points taken as normally
+    /// distributed.
+    pub fn llr(&self, score:&MatchScore)->f64{
+        let variance=score.variance();
+        if score.games()==0||variance<=0.0{
+            return 0.0;
+        }
+        let s0=get_expected_score(self.elo0);
+        let s1=get_expected_score(self.elo1);
+        f64::from(score.games())*(s1-s0)*(2.0*score.score()-s0-s1)/(2.0*variance)
+    }
+
+    /// The lower and upper bounds of the log-likelihood ratio.
+    pub fn bounds(&self)->(f64, f64){
+        ((self.beta/(1.0-self.alpha)).ln(), ((1.0-self.beta)/self.alpha).ln())
+    }
+
+    pub fn status(&self, score:&MatchScore)->SprtStatus{
+        let llr=self.llr(score);
+        let (lower, upper)=self.bounds();
+        if llr>=upper {SprtStatus::AcceptH1} else if llr<=lower {SprtStatus::AcceptH0} else {SprtStatus::Continue}
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MatchResult {
+    pub games: Vec<Game>,
+    pub score: MatchScore,
+}
+
+impl MatchResult{
+    /// Every game, as one PGN database.
+    pub fn pgn(&self)->String{
+        self.games.iter().map(|game| game.to_pgn()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Openings from an EPD file, one per record.
+pub fn openings_from_epd(text:&str)->Vec<Game>{
+    read_epd(text).into_iter().filter_map(|record| record.ok()).map(|record| Game::from_board(record.board)).collect()
+}
+
+/// Openings from a PGN database: each game's start position and moves.
+pub fn openings_from_pgn(text:&str)->Vec<Game>{
+    read_pgn(text).into_iter().filter_map(|game| game.ok()).collect()
+}
+
+// Ends the game as a loss for `col` if `err` is its engine's fault, otherwise passes
+// the error on.
+fn forfeit(game:&mut Game, col:ChessColour, err:PlayerError)->Result<(), PlayerError>{
+    if !err.is_forfeit(){return Err(err);}
+    let termination=if matches!(err, PlayerError::Engine(UciError::Timeout(_))) {Termination::TimeForfeit} else {Termination::Adjudication};
+    game.finish(GameResult::from_winner(Some(get_op_col(col))), termination);
+    Ok(())
+}
+
+fn play_game(white:&mut dyn Player, black:&mut dyn Player, opening:&Game, options:&MatchOptions)->Result<Game, PlayerError>{
+    let mut game=Game::from_board(opening.start);
+    for mv in opening.moves.iter().take(options.opening_plies.unwrap_or(usize::MAX)){
+        if !game.make_move(*mv){break;}
+    }
+    game.set_tag("Event", &options.event);
+    game.set_tag("White", &white.name());
+    game.set_tag("Black", &black.name());
+    if let Err(err)=white.new_game(){
+        forfeit(&mut game, ChessColour::White, err)?;
+        return Ok(game);
+    }
+    if let Err(err)=black.new_game(){
+        forfeit(&mut game, ChessColour::Black, err)?;
+        return Ok(game);
+    }
+    let rules=options.adjudication;
+    // Indexed by colour, White first.
+    let mut losing=[0usize;2];
+    let mut last_score=[0i32;2];
+    let mut quiet=0;
+    while !game.is_over(){
+        if rules.max_plies.is_some_and(|plies| game.moves.len()>=plies){
+            game.adjudicate(GameResult::Draw);
+            break;
+        }
+        let mover=game.board.current_move;
+        let player:&mut dyn Player=if mover==ChessColour::White {&mut *white} else {&mut *black};
+        let result=match player.play(&game, options.limits){
+            Ok(result)=>result,
+            Err(err)=>{
+                forfeit(&mut game, mover, err)?;
+                break;
+            }
+        };
+        if !result.best_move.is_some_and(|mv| game.make_move(mv)){
+            game.adjudicate(GameResult::from_winner(Some(get_op_col(mover))));
+            break;
+        }
+        let side=if mover==ChessColour::White {0} else {1};
+        last_score[side]=result.score;
+        if let Some(threshold)=rules.resign_score{
+            losing[side]=if result.score<= -threshold {losing[side]+1} else {0};
+            if losing[side]>=rules.resign_moves.max(1)&&last_score[1-side]>=threshold{
+                game.adjudicate(GameResult::from_winner(Some(get_op_col(mover))));
+            }
+        }
+        if let Some(threshold)=rules.draw_score{
+            quiet=if result.score.abs()<=threshold {quiet+1} else {0};
+            if quiet>=2*rules.draw_moves.max(1)&&game.moves.len()>=rules.draw_after{
+                game.adjudicate(GameResult::Draw);
+            }
+        }
+    }
+    Ok(game)
+}
+
+/// Plays `first` against `second`, each opening twice with colours swapped, cycling
+/// through the openings (the standard start position if there are none).
+/// `on_game` sees every finished game with the score so far.
+pub fn run_match(first:&mut dyn Player, second:&mut dyn Player, openings:&[Game], options:&MatchOptions, mut on_game:impl FnMut(&Game, &MatchScore))->Result<MatchResult, PlayerError>{
+    let standard=[Game::new()];
+    let openings=if openings.is_empty() {&standard[..]} else {openings};
+    let mut result=MatchResult::default();
+    for round in 0..options.games.div_ceil(2)*2{
+        let opening=&openings[(round/2)%openings.len()];
+        let first_is_white=round%2==0;
+        let mut game=if first_is_white{
+            play_game(first, second, opening, options)?
+        }
+        else{
+            play_game(second, first, opening, options)?
+        };
+        game.set_tag("Round", &(round+1).to_string());
+        result.score.add(game.result(), first_is_white);
+        on_game(&game, &result.score);
+        result.games.push(game);
+        if options.sprt.is_some_and(|sprt| sprt.status(&result.score)!=SprtStatus::Continue){
+            break;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(name:&str, depth:u8)->SearchPlayer{
+        SearchPlayer{name: name.to_string(), limits: Some(SearchLimits{depth: Some(depth), ..Default::default()})}
+    }
+
+    #[test]
+    fn test_statistics(){
+        let score=MatchScore{wins: 60, losses: 20, draws: 20};
+        assert!((score.elo().unwrap()-147.2).abs()<0.1);
+        let error=score.elo_error().unwrap();
+        assert!(error>40.0&&error<80.0);
+        assert_eq!(MatchScore{wins: 3, losses: 0, draws: 0}.elo(), None);
+        let sprt=Sprt::new(0.0, 10.0);
+        let (lower, upper)=sprt.bounds();
+        assert!((lower+2.944).abs()<0.001&&(upper-2.944).abs()<0.001);
+        assert_eq!(sprt.status(&MatchScore{wins: 10, losses: 10, draws: 10}), SprtStatus::Continue);
+        assert_eq!(sprt.status(&MatchScore{wins: 600, losses: 400, draws: 1000}), SprtStatus::AcceptH1);
+        assert_eq!(sprt.status(&MatchScore{wins: 400, losses: 600, draws: 1000}), SprtStatus::AcceptH0);
+        assert!(sprt.llr(&MatchScore{wins: 600, losses: 400, draws: 1000})>0.0);
+    }
+    #[test]
+    fn test_match_swaps_colours_and_adjudicates(){
+        // The side to move is a queen up in every opening, so the engines win one
+        // game each, and the test soon decides they are not 1000 Elo apart.
+        let openings=openings_from_epd("4k3/8/8/8/8/8/8/3QK3 w - -\n3qk3/8/8/8/8/8/8/4K3 b - -");
+        let options=MatchOptions{
+            games: 20,
+            adjudication: Adjudication{resign_score: Some(500), resign_moves: 2, ..Default::default()},
+            sprt: Some(Sprt::new(0.0, 1000.0)),
+            ..Default::default()
+        };
+        let mut seen=0;
+        let result=run_match(&mut depth("one", 1), &mut depth("two", 1), &openings, &options, |_, _| seen+=1).unwrap();
+        assert_eq!((seen, result.games.len()), (6, 6));
+        assert_eq!(result.score, MatchScore{wins: 3, losses: 3, draws: 0});
+        assert_eq!(options.sprt.unwrap().status(&result.score), SprtStatus::AcceptH0);
+        let whites:Vec<&str>=result.games.iter().map(|game| game.get_tag("White").unwrap()).collect();
+        assert_eq!(whites, ["one", "two", "one", "two", "one", "two"]);
+        assert!(result.games.iter().all(|game| game.termination()==Some(Termination::Adjudication)));
+        assert_eq!(result.pgn().matches("[Event \"Engine match\"]").count(), 6);
+    }
+    #[test]
+    fn test_draw_adjudication(){
+        let options=MatchOptions{
+            games: 3,
+            adjudication: Adjudication{max_plies: Some(6), ..Default::default()},
+            ..Default::default()
+        };
+        let openings=openings_from_pgn("1. e4 e5 2. Nf3 Nc6 *");
+        let result=run_match(&mut depth("one", 1), &mut depth("two", 1), &openings, &options, |_, _| {}).unwrap();
+        assert_eq!(result.games.len(), 4);
+        assert!(result.games.iter().all(|game| game.result()==GameResult::Draw&&game.moves.len()==6));
+        assert!(result.games[0].to_pgn().contains("1. e4 e5 2. Nf3 Nc6"));
+    }
+    fn stub(fault:&str)->UciPlayer{
+        let script=concat!(env!("CARGO_MANIFEST_DIR"), "/tests/uci_stub.sh").to_string();
+        UciPlayer::spawn("sh", &[script], &[("Fault".to_string(), fault.to_string())]).unwrap()
+    }
+
+    #[test]
+    fn test_uci_players(){
+        let options=MatchOptions{games: 2, adjudication: Adjudication{max_plies: Some(2), ..Default::default()}, ..Default::default()};
+        let mut search=depth("search", 1);
+        let result=run_match(&mut stub("none"), &mut search, &[], &options, |_, _| {}).unwrap();
+        assert_eq!(result.score, MatchScore{wins: 0, losses: 0, draws: 2});
+        assert_eq!(result.games[0].get_tag("White"), Some("Stub Engine"));
+        assert_eq!(result.games[0].moves[0].to_uci(), "e2e4");
+        assert_eq!(result.games[1].moves[1].to_uci(), "e7e5");
+
+        // Illegal moves, crashes and hangs lose the game but the match goes on.
+        let result=run_match(&mut stub("illegal"), &mut search, &[], &options, |_, _| {}).unwrap();
+        assert_eq!(result.score, MatchScore{wins: 0, losses: 2, draws: 0});
+        assert!(result.games.iter().all(|game| game.termination()==Some(Termination::Adjudication)));
+        let result=run_match(&mut stub("crash"), &mut search, &[], &options, |_, _| {}).unwrap();
+        assert_eq!(result.score, MatchScore{wins: 0, losses: 2, draws: 0});
+        let mut hung=stub("hang");
+        hung.engine.timeout=Duration::from_millis(200);
+        let result=run_match(&mut search, &mut hung, &[], &MatchOptions{games: 1, ..options}, |_, _| {}).unwrap();
+        assert_eq!(result.score.wins, 2);
+        assert_eq!(result.games[0].termination(), Some(Termination::TimeForfeit));
+    }
+}
@@ -0,0 +1,333 @@
+//! Extended Position Description records and a runner for test suites such as WAC
+//! and STS.
+
+use super::*;
+use crate::fen::FenError;
+use crate::search::{search, SearchLimits};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq)]
+pub enum EpdError {
+    /// Fewer than the four position fields.
+    MissingField(&'static str),
+    InvalidPosition(FenError),
+    /// An operation that is not an opcode followed by operands, or has an unclosed string.
+    InvalidOperation(String),
+    /// A `bm` or `am` operand that is not a legal move in the position.
+    IllegalMove(String),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            EpdError::MissingField(field)=>write!(f, "EPD is missing the {} field", field),
+            EpdError::InvalidPosition(err)=>write!(f, "invalid EPD position: {}", err),
+            EpdError::InvalidOperation(op)=>write!(f, "invalid EPD operation '{}'", op),
+            EpdError::IllegalMove(mv)=>write!(f, "illegal move '{}'", mv),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+/// One EPD line: a position and its operations.
+#[derive(Clone)]
+pub struct EpdRecord {
+    pub board: ChessBoard,
+    /// Opcodes with their operands in the order given, string operands unquoted.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+// Splits the text after the position into operations, each ended by a semicolon
+// outside of a quoted string.
+fn get_operations(text:&str)->Result<Vec<(String, Vec<String>)>, EpdError>{
+    let mut out=Vec::new();
+    let mut words:Vec<String>=Vec::new();
+    let mut word=String::new();
+    let mut chars=text.chars();
+    while let Some(c)=chars.next(){
+        match c{
+            '"'=>{
+                let mut quoted=String::new();
+                loop{
+                    match chars.next(){
+                        Some('"')=>break,
+                        Some(c)=>quoted.push(c),
+                        None=>return Err(EpdError::InvalidOperation(text.trim().to_string())),
+                    }
+                }
+                words.push(quoted);
+            }
+            ';'|' '|'\t'=>{
+                if !word.is_empty(){
+                    words.push(std::mem::take(&mut word));
+                }
+                if c==';'{
+                    if words.is_empty(){
+                        return Err(EpdError::InvalidOperation(text.trim().to_string()));
+                    }
+                    let opcode=words.remove(0);
+                    out.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            _=>word.push(c),
+        }
+    }
+    if !word.is_empty(){
+        words.push(word);
+    }
+    // The last semicolon is often left out.
+    if !words.is_empty(){
+        let opcode=words.remove(0);
+        out.push((opcode, words));
+    }
+    Ok(out)
+}
+
+impl EpdRecord{
+    /// Parses one EPD line. The `hmvc` and `fmvn` operations, if given, set the move
+    /// counters.
+    pub fn parse(line:&str)->Result<EpdRecord, EpdError>{
+        let line=line.trim();
+        let mut fields=Vec::new();
+        let mut rest=line;
+        for name in ["piece placement", "side to move", "castling", "en passant"]{
+            rest=rest.trim_start();
+            let end=rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end==0{
+                return Err(EpdError::MissingField(name));
+            }
+            fields.push(&rest[..end]);
+            rest=&rest[end..];
+        }
+        let operations=get_operations(rest)?;
+        let operand=|opcode:&str| operations.iter().find(|(op, _)| op==opcode).and_then(|(_, operands)| operands.first().cloned());
+        let fen=format!("{} {} {}", fields.join(" "), operand("hmvc").unwrap_or("0".to_string()), operand("fmvn").unwrap_or("1".to_string()));
+        let board=ChessBoard::from_fen(&fen).map_err(EpdError::InvalidPosition)?;
+        let record=EpdRecord{board, operations};
+        for opcode in ["bm", "am"]{
+            for mv in record.get(opcode).unwrap_or(&[]){
+                if get_move(board, mv).is_none(){
+                    return Err(EpdError::IllegalMove(mv.clone()));
+                }
+            }
+        }
+        Ok(record)
+    }
+
+    /// The operands of the first operation with this opcode.
+    pub fn get(&self, opcode:&str)->Option<&[String]>{
+        self.operations.iter().find(|(op, _)| op==opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    fn get_first(&self, opcode:&str)->Option<&str>{
+        self.get(opcode)?.first().map(|operand| operand.as_str())
+    }
+
+    pub fn id(&self)->Option<&str>{
+        self.get_first("id")
+    }
+
+    /// The primary comment, `c0`.
+    pub fn comment(&self)->Option<&str>{
+        self.get_first("c0")
+    }
+
+    /// Best moves, `bm`: the position is solved by finding any of them.
+    pub fn best_moves(&self)->Vec<Move>{
+        self.get_moves("bm")
+    }
+
+    /// Moves to avoid, `am`.
+    pub fn avoid_moves(&self)->Vec<Move>{
+        self.get_moves("am")
+    }
+
+    /// Direct mate, `dm`: the side to move mates in this many moves.
+    pub fn direct_mate(&self)->Option<i32>{
+        self.get_first("dm")?.parse().ok()
+    }
+
+    /// Centipawn evaluation, `ce`, from the side to move's point of view.
+    pub fn centipawn_eval(&self)->Option<i32>{
+        self.get_first("ce")?.parse().ok()
+    }
+
+    fn get_moves(&self, opcode:&str)->Vec<Move>{
+        self.get(opcode).unwrap_or(&[]).iter().filter_map(|mv| get_move(self.board, mv)).collect()
+    }
+
+    /// The record as an EPD line, quoting operands that contain spaces or semicolons.
+    /// EPD cannot escape a double quote, so any inside an operand are left out and
+    /// such an operand does not read back unchanged.
+    pub fn to_epd(&self)->String{
+        let fen=self.board.to_fen();
+        let mut out:Vec<&str>=fen.split(' ').take(4).collect();
+        let mut ops=String::new();
+        for (opcode, operands) in self.operations.iter(){
+            ops.push(' ');
+            ops.push_str(opcode);
+            for operand in operands{
+                if operand.is_empty()||operand.contains([' ', ';', '"']){
+                    ops.push_str(&format!(" \"{}\"", operand.replace('"', "")));
+                }
+                else{
+                    ops.push(' ');
+                    ops.push_str(operand);
+                }
+            }
+            ops.push(';');
+        }
+        out.push(ops.trim_start());
+        out.join(" ").trim_end().to_string()
+    }
+}
+
+// Suites give moves in SAN, though a few use UCI.
+fn get_move(board:ChessBoard, text:&str)->Option<Move>{
+    board.parse_san(text).or_else(|| board.parse_uci(text))
+}
+
+/// Reads every record of an EPD file, skipping blank lines and `#` comments. A bad
+/// line does not stop the lines after it.
+pub fn read_epd(text:&str)->Vec<Result<EpdRecord, EpdError>>{
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty()&&!line.starts_with('#'))
+        .map(EpdRecord::parse)
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EpdResult {
+    pub id: Option<String>,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub time: Duration,
+    /// Whether the search met the record's `bm`, `am` and `dm` operations; `None`
+    /// if it has none of them.
+    pub solved: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SuiteReport {
+    pub results: Vec<EpdResult>,
+}
+
+impl SuiteReport{
+    pub fn solved(&self)->usize{
+        self.results.iter().filter(|result| result.solved==Some(true)).count()
+    }
+
+    /// Positions that had something to solve.
+    pub fn tested(&self)->usize{
+        self.results.iter().filter(|result| result.solved.is_some()).count()
+    }
+
+    pub fn total_time(&self)->Duration{
+        self.results.iter().map(|result| result.time).sum()
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        for (idx, result) in self.results.iter().enumerate(){
+            let status=match result.solved{
+                Some(true)=>"solved",
+                Some(false)=>"failed",
+                None=>"-",
+            };
+            writeln!(f, "{:>4} {:<16} {:<6} {:<6} depth {:>2} score {:>6} {:>8.3}s",
+                idx+1,
+                result.id.as_deref().unwrap_or("?"),
+                result.best_move.map(|mv| mv.to_uci()).unwrap_or("-".to_string()),
+                status,
+                result.depth,
+                result.score,
+                result.time.as_secs_f64())?;
+        }
+        write!(f, "solved {}/{} in {:.3}s", self.solved(), self.tested(), self.total_time().as_secs_f64())
+    }
+}
+
+/// Searches one record and checks the result against its operations.
+pub fn run_record(record:&EpdRecord, limits:SearchLimits)->EpdResult{
+    let start=Instant::now();
+    let result=search(record.board, limits);
+    let time=start.elapsed();
+    let best=record.best_moves();
+    let avoid=record.avoid_moves();
+    let mate=record.direct_mate();
+    let solved=if best.is_empty()&&avoid.is_empty()&&mate.is_none(){
+        None
+    }
+    else{
+        let found=|mv:Option<Move>, moves:&[Move]| mv.is_some_and(|mv| moves.contains(&mv));
+        Some((best.is_empty()||found(result.best_move, &best))
+            &&!found(result.best_move, &avoid)
+            &&mate.is_none_or(|mate| result.mate_in().is_some_and(|found| found>0&&found<=mate)))
+    };
+    EpdResult{
+        id: record.id().map(|id| id.to_string()),
+        best_move: result.best_move,
+        score: result.score,
+        depth: result.depth,
+        time,
+        solved,
+    }
+}
+
+/// Runs every record under the same search limits, such as a fixed depth or a
+/// movetime per position.
+pub fn run_suite(records:&[EpdRecord], limits:SearchLimits)->SuiteReport{
+    SuiteReport{results: records.iter().map(|record| run_record(record, limits)).collect()}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_operations(){
+        let record=EpdRecord::parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; am Qe2; id \"test; one\"; c0 \"Open game\"; ce 25; hmvc 2; fmvn 3;").unwrap();
+        assert_eq!(record.board.to_fen(), "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        assert_eq!(record.best_moves().iter().map(|mv| mv.to_uci()).collect::<Vec<_>>(), ["f1b5", "f1c4"]);
+        assert_eq!(record.avoid_moves().iter().map(|mv| mv.to_uci()).collect::<Vec<_>>(), ["d1e2"]);
+        assert_eq!(record.id(), Some("test; one"));
+        assert_eq!(record.comment(), Some("Open game"));
+        assert_eq!(record.centipawn_eval(), Some(25));
+        assert_eq!(record.direct_mate(), None);
+        let again=EpdRecord::parse(&record.to_epd()).unwrap();
+        assert_eq!(again.operations, record.operations);
+        assert_eq!(again.board.to_fen(), record.board.to_fen());
+        let mut quoted=record;
+        quoted.operations=vec![("c0".to_string(), vec!["say \"hi\"".to_string()])];
+        assert!(quoted.to_epd().ends_with(" c0 \"say hi\";"));
+    }
+    #[test]
+    fn test_parse_errors(){
+        assert_eq!(EpdRecord::parse("8/8/8/8/8/8/8/K6k w").err(), Some(EpdError::MissingField("castling")));
+        assert!(matches!(EpdRecord::parse("8/8/8 w - -"), Err(EpdError::InvalidPosition(_))));
+        assert_eq!(EpdRecord::parse("k7/8/8/8/8/8/8/K7 w - - bm Qh8;").err(), Some(EpdError::IllegalMove("Qh8".to_string())));
+        assert!(matches!(EpdRecord::parse("k7/8/8/8/8/8/8/K7 w - - id \"open;"), Err(EpdError::InvalidOperation(_))));
+        let records=read_epd("# suite\n\nk7/8/8/8/8/8/8/K7 w - - id \"a\";\nnonsense\n");
+        assert_eq!(records.len(), 2);
+        assert!(records[0].is_ok()&&records[1].is_err());
+    }
+    #[test]
+    fn test_run_suite(){
+        let records:Vec<EpdRecord>=read_epd("\
+            7k/8/6K1/8/8/8/8/R7 w - - bm Ra8#; id \"mate\";\n\
+            7k/8/6K1/8/8/8/8/R7 w - - dm 1; id \"direct\";\n\
+            4k3/8/8/3q4/8/8/3R4/4K3 w - - am Kf2; bm Rxd5; id \"queen\";\n\
+            4k3/8/8/3q4/8/8/3R4/4K3 w - - bm Kf2; id \"wrong\";\n\
+            4k3/8/8/8/8/8/8/4K3 w - - id \"none\";").into_iter().map(|record| record.unwrap()).collect();
+        let report=run_suite(&records, SearchLimits{depth: Some(2), ..Default::default()});
+        let solved:Vec<Option<bool>>=report.results.iter().map(|result| result.solved).collect();
+        assert_eq!(solved, [Some(true), Some(true), Some(true), Some(false), None]);
+        assert_eq!((report.solved(), report.tested()), (3, 4));
+        assert!(report.to_string().ends_with(&format!("solved 3/4 in {:.3}s", report.total_time().as_secs_f64())));
+    }
+}
@@ -0,0 +1,310 @@
+use super::*;
+use std::fmt;
+
+pub const START_FEN:&str="rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPlacement(String),
+    TooManyPieces,
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidCounter(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            FenError::MissingField(field)=>write!(f, "FEN is missing the {} field", field),
+            FenError::InvalidPlacement(rank)=>write!(f, "invalid piece placement '{}'", rank),
+            FenError::TooManyPieces=>write!(f, "FEN has more pieces than the board can hold"),
+            FenError::InvalidSideToMove(side)=>write!(f, "invalid side to move '{}'", side),
+            FenError::InvalidCastling(castling)=>write!(f, "invalid castling field '{}'", castling),
+            FenError::InvalidEnPassant(square)=>write!(f, "invalid en passant square '{}'", square),
+            FenError::InvalidCounter(counter)=>write!(f, "invalid move counter '{}'", counter),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+pub fn get_piece_char(kind:ChessPieceKind, col:ChessColour)->char{
+    let c=match kind{
+        ChessPieceKind::Pawn=>'p',
+        ChessPieceKind::Knight=>'n',
+        ChessPieceKind::Bishop=>'b',
+        ChessPieceKind::Rook=>'r',
+        ChessPieceKind::Queen=>'q',
+        ChessPieceKind::King=>'k',
+    };
+    if col==ChessColour::White {c.to_ascii_uppercase()} else {c}
+}
+
+pub fn get_piece_from_char(c:char)->Option<(ChessColour, ChessPieceKind)>{
+    let kind=match c.to_ascii_lowercase(){
+        'p'=>ChessPieceKind::Pawn,
+        'n'=>ChessPieceKind::Knight,
+        'b'=>ChessPieceKind::Bishop,
+        'r'=>ChessPieceKind::Rook,
+        'q'=>ChessPieceKind::Queen,
+        'k'=>ChessPieceKind::King,
+        _=>return None,
+    };
+    let col=if c.is_ascii_uppercase() {ChessColour::White} else {ChessColour::Black};
+    Some((col, kind))
+}
+
+// Marks the rook named by one castling character as the castling rook. `K`/`Q` pick
+// the outermost rook on that side (X-FEN), file letters name the rook directly (Shredder-FEN).
+pub(crate) fn set_castling_right(board:&mut ChessBoard, c:char)->bool{
+    let col=if c.is_ascii_uppercase() {ChessColour::White} else {ChessColour::Black};
+    let king_pos=get_king_pos(col, *board);
+    if (king_pos&get_back_rank(col))==0{return false;}
+    let back_rank=get_back_rank(col);
+    let rooks=get_kind_map(ChessPieceKind::Rook, col, *board)&back_rank;
+    let target=match c.to_ascii_lowercase(){
+        // Lower bits lie towards the h-file, so the outermost king side rook is the lowest bit.
+        'k'=>{
+            let side=rooks&(king_pos-1);
+            side&side.wrapping_neg()
+        }
+        'q'=>{
+            let side=rooks&!(king_pos|(king_pos-1));
+            if side==0 {0} else {1<<(63-side.leading_zeros())}
+        }
+        file @ 'a'..='h'=>rooks&back_rank&0x0101010101010101u64.wrapping_shl(7-(file as u32-'a' as u32)),
+        _=>0,
+    };
+    if target==0{return false;}
+    for piece in board.pieces.iter_mut(){
+        if !piece.is_captured&&(piece.pos==target||piece.pos==king_pos){
+            piece.has_moved=false;
+        }
+    }
+    true
+}
+
+// Switches on Chess960 when an unmoved king or rook, one that can still castle, is
+// off its standard file.
+pub(crate) fn set_chess960(board:&mut ChessBoard){
+    for piece in board.pieces{
+        if piece.is_captured||piece.has_moved{continue;}
+        let standard_file=match piece.kind{
+            ChessPieceKind::King=>get_file(piece)==5,
+            ChessPieceKind::Rook=>get_file(piece)==1||get_file(piece)==8,
+            _=>true,
+        };
+        if !standard_file{
+            board.chess960=true;
+        }
+    }
+}
+
+// Marks the pawn in front of an en passant square as having just double stepped.
+// Fails if no pawn of the side not to move stands there.
+pub(crate) fn set_ep_square(board:&mut ChessBoard, square:u64)->bool{
+    let (pawn_pos, prev_pos, rank)=match board.current_move{
+        ChessColour::White=>(square>>8, square<<8, 0x0000FF0000000000),
+        ChessColour::Black=>(square<<8, square>>8, 0x0000000000FF0000),
+    };
+    if (square&rank)==0{
+        return false;
+    }
+    let pawn=get_kind_map(ChessPieceKind::Pawn, get_op_col(board.current_move), *board);
+    if (pawn&pawn_pos)==0{
+        return false;
+    }
+    for piece in board.pieces.iter_mut(){
+        if !piece.is_captured&&piece.pos==pawn_pos{
+            piece.prev_pos=prev_pos;
+        }
+    }
+    true
+}
+
+impl ChessBoard{
+    /// Parses a FEN string. The castling field may use standard `KQkq`, X-FEN or
+    /// Shredder-FEN file letters; positions that need Chess960 castling switch it on.
+    pub fn from_fen(fen:&str)->Result<ChessBoard, FenError>{
+        let mut fields=fen.split_whitespace();
+        let placement=fields.next().ok_or(FenError::MissingField("piece placement"))?;
+        let side=fields.next().ok_or(FenError::MissingField("side to move"))?;
+        let castling=fields.next().unwrap_or("-");
+        let en_passant=fields.next().unwrap_or("-");
+        let halfmove=fields.next().unwrap_or("0");
+        let fullmove=fields.next().unwrap_or("1");
+
+        let ranks:Vec<&str>=placement.split('/').collect();
+        if ranks.len()!=8{
+            return Err(FenError::InvalidPlacement(placement.to_string()));
+        }
+        let mut template:Vec<(ChessColour, ChessPieceKind, u8, u8)>=Vec::new();
+        for (idx, rank_str) in ranks.iter().enumerate(){
+            let rank=8-idx as u8;
+            let mut file:u8=1;
+            let mut after_digit=false;
+            for c in rank_str.chars(){
+                if let Some(skip)=c.to_digit(10){
+                    // A run of empty squares is a single digit from 1 to 8.
+                    file+=skip as u8;
+                    if skip==0||after_digit||file>9{
+                        return Err(FenError::InvalidPlacement(rank_str.to_string()));
+                    }
+                    after_digit=true;
+                }
+                else if let Some((col, kind))=get_piece_from_char(c){
+                    if file>8{
+                        return Err(FenError::InvalidPlacement(rank_str.to_string()));
+                    }
+                    template.push((col, kind, rank, file));
+                    file+=1;
+                    after_digit=false;
+                }
+                else{
+                    return Err(FenError::InvalidPlacement(rank_str.to_string()));
+                }
+            }
+            if file!=9{
+                return Err(FenError::InvalidPlacement(rank_str.to_string()));
+            }
+        }
+        if template.len()>MAX_PIECES{
+            return Err(FenError::TooManyPieces);
+        }
+        let current_move=match side{
+            "w"=>ChessColour::White,
+            "b"=>ChessColour::Black,
+            _=>return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+        let mut board=board_from_template(&template, current_move);
+
+        for piece in board.pieces.iter_mut(){
+            if matches!(piece.kind, ChessPieceKind::King|ChessPieceKind::Rook){
+                piece.has_moved=true;
+            }
+        }
+        if castling!="-"{
+            for c in castling.chars(){
+                if !set_castling_right(&mut board, c){
+                    return Err(FenError::InvalidCastling(castling.to_string()));
+                }
+            }
+        }
+        set_chess960(&mut board);
+
+        if en_passant!="-"{
+            let square=get_square_from_name(en_passant).ok_or(FenError::InvalidEnPassant(en_passant.to_string()))?;
+            if !set_ep_square(&mut board, square){
+                return Err(FenError::InvalidEnPassant(en_passant.to_string()));
+            }
+        }
+
+        board.rule_50_moves=halfmove.parse().map_err(|_| FenError::InvalidCounter(halfmove.to_string()))?;
+        board.move_number=fullmove.parse().map_err(|_| FenError::InvalidCounter(fullmove.to_string()))?;
+        board.rule_repetition[0]=[0;64];
+        if (board.rule_50_moves as usize)<board.rule_repetition.len(){
+            board.rule_repetition[board.rule_50_moves as usize]=hash_board_state(board);
+        }
+        Ok(board)
+    }
+
+    /// FEN for the position, with X-FEN castling letters for Chess960 positions.
+    pub fn to_fen(&self)->String{
+        get_fen(*self, false)
+    }
+
+    /// FEN for the position with Shredder-FEN castling letters, which name the rook files.
+    pub fn to_shredder_fen(&self)->String{
+        get_fen(*self, true)
+    }
+}
+
+fn get_castling_field(board:ChessBoard, shredder:bool)->String{
+    let mut out=String::new();
+    for col in [ChessColour::White, ChessColour::Black]{
+        let Some(king)=get_piece_bit_mask(get_king_pos(col, board), board) else {continue;};
+        for king_side in [true, false]{
+            let Some(rook)=get_castling_rook(king, king_side, board) else {continue;};
+            let rooks=get_kind_map(ChessPieceKind::Rook, col, board)&get_back_rank(col);
+            let outside=if king_side {rooks&(rook.pos-1)} else {rooks&!(rook.pos|(rook.pos-1))};
+            let c=if shredder||outside>0{
+                (b'a'+get_file(rook)-1) as char
+            }
+            else if king_side {'k'} else {'q'};
+            out.push(if col==ChessColour::White {c.to_ascii_uppercase()} else {c});
+        }
+    }
+    if out.is_empty(){
+        out.push('-');
+    }
+    out
+}
+
+fn get_fen(board:ChessBoard, shredder:bool)->String{
+    let mut out=String::new();
+    for rank in (1..=8).rev(){
+        let mut empty=0;
+        for file in 1..=8{
+            match get_piece_bit_mask(get_u64_pos(rank, file), board){
+                Some(piece)=>{
+                    if empty>0{
+                        out.push_str(&empty.to_string());
+                        empty=0;
+                    }
+                    out.push(get_piece_char(piece.kind, piece.colour));
+                }
+                None=>empty+=1,
+            }
+        }
+        if empty>0{
+            out.push_str(&empty.to_string());
+        }
+        if rank>1{
+            out.push('/');
+        }
+    }
+    let ep=get_ep_capture_spots(get_op_col(board.current_move), board);
+    let ep=if ep==0 {"-".to_string()} else {get_square_name(ep)};
+    format!("{} {} {} {} {} {}",
+        out,
+        if board.current_move==ChessColour::White {'w'} else {'b'},
+        get_castling_field(board, shredder),
+        ep,
+        board.rule_50_moves,
+        board.move_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_position_round_trip(){
+        let board=ChessBoard::from_fen(START_FEN).unwrap();
+        assert_eq!(board.to_fen(), START_FEN);
+        assert_eq!(new_board().to_fen(), START_FEN);
+        assert!(!board.chess960);
+    }
+    #[test]
+    fn test_en_passant_field(){
+        let mut board=new_board();
+        assert!(board.make_move(new_move(get_square_from_name("e2").unwrap(), get_square_from_name("e4").unwrap())));
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        let fen="rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(ChessBoard::from_fen(fen).unwrap().to_fen(), fen);
+    }
+    #[test]
+    fn test_invalid_fen(){
+        assert_eq!(ChessBoard::from_fen("8/8/8 w - - 0 1").err(), Some(FenError::InvalidPlacement("8/8/8".to_string())));
+        assert_eq!(ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").err(), Some(FenError::InvalidSideToMove("x".to_string())));
+        assert_eq!(ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").err(), Some(FenError::InvalidCastling("K".to_string())));
+        let digits="9".repeat(29);
+        assert_eq!(ChessBoard::from_fen(&format!("{}/8/8/8/8/8/8/8 w - - 0 1", digits)).err(), Some(FenError::InvalidPlacement(digits)));
+        assert_eq!(ChessBoard::from_fen("44k/8/8/8/8/8/8/4K3 w - - 0 1").err(), Some(FenError::InvalidPlacement("44k".to_string())));
+        for rank in ["k06K", "4k03", "44", "k16"]{
+            assert_eq!(ChessBoard::from_fen(&format!("{}/8/8/8/8/8/8/8 w - - 0 1", rank)).err(), Some(FenError::InvalidPlacement(rank.to_string())));
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use super::*;
+use crate::clock::Clock;
+use crate::status::GameStatus;
+use std::time::Duration;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Ongoing,
+}
+
+impl GameResult{
+    /// The result for a finished game, `None` meaning a draw.
+    pub fn from_winner(winner:Option<ChessColour>)->GameResult{
+        match winner{
+            Some(ChessColour::White)=>GameResult::WhiteWins,
+            Some(ChessColour::Black)=>GameResult::BlackWins,
+            None=>GameResult::Draw,
+        }
+    }
+
+    pub fn as_pgn(&self)->&'static str{
+        match self{
+            GameResult::WhiteWins=>"1-0",
+            GameResult::BlackWins=>"0-1",
+            GameResult::Draw=>"1/2-1/2",
+            GameResult::Ongoing=>"*",
+        }
+    }
+
+    pub fn from_pgn(value:&str)->Option<GameResult>{
+        match value{
+            "1-0"=>Some(GameResult::WhiteWins),
+            "0-1"=>Some(GameResult::BlackWins),
+            "1/2-1/2"=>Some(GameResult::Draw),
+            "*"=>Some(GameResult::Ongoing),
+            _=>None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    TimeForfeit,
+    TimeoutVsInsufficientMaterial,
+    Resignation,
+    Agreement,
+    Adjudication,
+}
+
+impl Termination{
+    /// The value for the PGN `Termination` tag.
+    pub fn as_pgn(&self)->&'static str{
+        match self{
+            Termination::TimeForfeit|Termination::TimeoutVsInsufficientMaterial=>"time forfeit",
+            Termination::Adjudication=>"adjudication",
+            _=>"normal",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DrawOfferState {
+    Pending,
+    Accepted,
+    Declined,
+    /// The opponent moved instead of answering.
+    Expired,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrawOffer {
+    pub by: ChessColour,
+    /// Number of moves played when the offer was made.
+    pub ply: usize,
+    pub state: DrawOfferState,
+}
+
+/// A game in progress: the board plus everything that can end a game away from it,
+/// such as clocks, draw offers, resignation and adjudication.
+#[derive(Clone)]
+pub struct Game {
+    pub start: ChessBoard,
+    pub board: ChessBoard,
+    pub moves: Vec<Move>,
+    pub clock: Option<Clock>,
+    pub draw_offers: Vec<DrawOffer>,
+    pub tags: Vec<(String, String)>,
+    pub(crate) result: GameResult,
+    pub(crate) termination: Option<Termination>,
+}
+
+impl Default for Game{
+    fn default()->Game{
+        Game::from_board(new_board())
+    }
+}
+
+impl Game{
+    pub fn new()->Game{
+        Game::default()
+    }
+
+    pub fn from_board(board:ChessBoard)->Game{
+        let mut game=Game{
+            start: board,
+            board,
+            moves: Vec::new(),
+            clock: None,
+            draw_offers: Vec::new(),
+            tags: Vec::new(),
+            result: GameResult::Ongoing,
+            termination: None,
+        };
+        game.update_status();
+        game
+    }
+
+    pub fn result(&self)->GameResult{
+        self.result
+    }
+
+    pub fn termination(&self)->Option<Termination>{
+        self.termination
+    }
+
+    pub fn is_over(&self)->bool{
+        self.result!=GameResult::Ongoing
+    }
+
+    /// Sets a PGN tag, replacing any earlier value.
+    pub fn set_tag(&mut self, name:&str, value:&str){
+        self.tags.retain(|(tag, _)| tag!=name);
+        self.tags.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn get_tag(&self, name:&str)->Option<&str>{
+        self.tags.iter().find(|(tag, _)| tag==name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn make_move(&mut self, mv:Move)->bool{
+        if self.is_over(){return false;}
+        let mover=self.board.current_move;
+        if !self.board.make_move(mv){return false;}
+        self.moves.push(mv);
+        for offer in self.draw_offers.iter_mut(){
+            if offer.state==DrawOfferState::Pending&&offer.by!=mover{
+                offer.state=DrawOfferState::Expired;
+            }
+        }
+        self.update_status();
+        true
+    }
+
+    /// Takes back the last move, reopening the game if it had ended. Clocks are not
+    /// rewound and draw offers made after that move are dropped.
+    pub fn undo(&mut self)->Option<Move>{
+        let mv=self.moves.pop()?;
+        let mut board=self.start;
+        for played in self.moves.iter(){
+            board.make_move(*played);
+        }
+        self.board=board;
+        let ply=self.moves.len();
+        self.draw_offers.retain(|offer| offer.ply<=ply);
+        self.result=GameResult::Ongoing;
+        self.termination=None;
+        self.update_status();
+        Some(mv)
+    }
+
+    /// Plays `mv` and stops the mover's clock after `elapsed`. If the flag had
+    /// already fallen the move is not played and the game ends on time.
+    pub fn make_timed_move(&mut self, mv:Move, elapsed:Duration)->bool{
+        if self.is_over(){return false;}
+        let mover=self.board.current_move;
+        if !get_legal_moves(self.board).contains(&mv){return false;}
+        if let Some(clock)=self.clock.as_mut(){
+            if !clock.press(mover, elapsed){
+                self.update_status();
+                return false;
+            }
+        }
+        self.make_move(mv)
+    }
+
+    /// Checks the flag of the side to move `elapsed` into its move, ending the game if it fell.
+    pub fn check_flag(&mut self, elapsed:Duration)->bool{
+        if self.is_over(){return false;}
+        let col=self.board.current_move;
+        let Some(clock)=self.clock.as_mut() else {
+            return false;
+        };
+        let fallen=clock.check_flag(col, elapsed);
+        self.update_status();
+        fallen
+    }
+
+    pub fn pending_draw_offer(&self)->Option<ChessColour>{
+        self.draw_offers.iter().find(|offer| offer.state==DrawOfferState::Pending).map(|offer| offer.by)
+    }
+
+    /// Offers a draw on behalf of `col`. An offer made while the opponent's own offer
+    /// is pending accepts it. The offer stays open until the opponent has moved.
+    pub fn offer_draw(&mut self, col:ChessColour)->bool{
+        if self.is_over(){return false;}
+        match self.pending_draw_offer(){
+            Some(by) if by==col=>false,
+            Some(_)=>self.accept_draw(col),
+            None=>{
+                self.draw_offers.push(DrawOffer{by: col, ply: self.moves.len(), state: DrawOfferState::Pending});
+                true
+            }
+        }
+    }
+
+    pub fn accept_draw(&mut self, col:ChessColour)->bool{
+        if !self.answer_draw(col, DrawOfferState::Accepted){return false;}
+        self.finish(GameResult::Draw, Termination::Agreement);
+        true
+    }
+
+    pub fn decline_draw(&mut self, col:ChessColour)->bool{
+        self.answer_draw(col, DrawOfferState::Declined)
+    }
+
+    fn answer_draw(&mut self, col:ChessColour, state:DrawOfferState)->bool{
+        if self.is_over(){return false;}
+        for offer in self.draw_offers.iter_mut(){
+            if offer.state==DrawOfferState::Pending&&offer.by!=col{
+                offer.state=state;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn resign(&mut self, col:ChessColour)->bool{
+        if self.is_over(){return false;}
+        self.finish(GameResult::from_winner(Some(get_op_col(col))), Termination::Resignation);
+        true
+    }
+
+    /// Ends the game with a result decided away from the board, e.g. by an arbiter
+    /// or a match runner's score threshold.
+    pub fn adjudicate(&mut self, result:GameResult)->bool{
+        if self.is_over()||result==GameResult::Ongoing{return false;}
+        self.finish(result, Termination::Adjudication);
+        true
+    }
+
+    pub(crate) fn finish(&mut self, result:GameResult, termination:Termination){
+        self.result=result;
+        self.termination=Some(termination);
+        for offer in self.draw_offers.iter_mut(){
+            if offer.state==DrawOfferState::Pending{
+                offer.state=if termination==Termination::Agreement {DrawOfferState::Accepted} else {DrawOfferState::Expired};
+            }
+        }
+    }
+
+    fn update_status(&mut self){
+        let status=match &self.clock{
+            Some(clock)=>clock.status(&self.board),
+            None=>self.board.status(),
+        };
+        let termination=match status{
+            GameStatus::Ongoing=>return,
+            GameStatus::Checkmate(_)=>Termination::Checkmate,
+            GameStatus::Stalemate=>Termination::Stalemate,
+            GameStatus::InsufficientMaterial=>Termination::InsufficientMaterial,
+            GameStatus::FiftyMoveRule=>Termination::FiftyMoveRule,
+            GameStatus::ThreefoldRepetition=>Termination::ThreefoldRepetition,
+            GameStatus::Timeout(_)=>Termination::TimeForfeit,
+            GameStatus::TimeoutVsInsufficientMaterial=>Termination::TimeoutVsInsufficientMaterial,
+        };
+        self.finish(GameResult::from_winner(status.winner()), termination);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from:&str, to:&str)->Move{
+        new_move(get_square_from_name(from).unwrap(), get_square_from_name(to).unwrap())
+    }
+
+    #[test]
+    fn test_draw_offer_expires(){
+        let mut game=Game::new();
+        assert!(game.offer_draw(ChessColour::White));
+        assert!(game.make_move(mv("e2","e4")));
+        assert_eq!(game.pending_draw_offer(), Some(ChessColour::White));
+        assert!(game.make_move(mv("e7","e5")));
+        assert_eq!(game.pending_draw_offer(), None);
+        assert_eq!(game.draw_offers[0].state, DrawOfferState::Expired);
+        assert!(!game.accept_draw(ChessColour::Black));
+
+        assert!(game.offer_draw(ChessColour::White));
+        assert!(game.decline_draw(ChessColour::Black));
+        assert!(game.offer_draw(ChessColour::Black));
+        assert!(game.offer_draw(ChessColour::White));
+        assert_eq!(game.result(), GameResult::Draw);
+        assert_eq!(game.termination(), Some(Termination::Agreement));
+    }
+    #[test]
+    fn test_resign_and_adjudicate(){
+        let mut game=Game::new();
+        assert!(game.resign(ChessColour::White));
+        assert_eq!(game.result().as_pgn(), "0-1");
+        assert!(!game.make_move(mv("e2","e4")));
+        let mut game=Game::new();
+        assert!(!game.adjudicate(GameResult::Ongoing));
+        assert!(game.adjudicate(GameResult::WhiteWins));
+        assert_eq!(game.termination().map(|t| t.as_pgn()), Some("adjudication"));
+    }
+    #[test]
+    fn test_undo_reopens_game(){
+        let mut game=Game::new();
+        for (from, to) in [("f2","f3"),("e7","e5"),("g2","g4"),("d8","h4")]{
+            assert!(game.make_move(mv(from, to)));
+        }
+        assert_eq!(game.termination(), Some(Termination::Checkmate));
+        assert_eq!(game.undo(), Some(mv("d8","h4")));
+        assert!(!game.is_over());
+        assert_eq!(game.board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2");
+    }
+    #[test]
+    fn test_time_forfeit(){
+        let mut game=Game::new();
+        game.clock=Some(Clock::fischer(Duration::from_secs(60), Duration::from_secs(1)));
+        assert!(game.make_timed_move(mv("e2","e4"), Duration::from_secs(30)));
+        assert!(!game.make_timed_move(mv("e7","e5"), Duration::from_secs(61)));
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::TimeForfeit));
+        assert_eq!(Termination::TimeoutVsInsufficientMaterial.as_pgn(), "time forfeit");
+    }
+}
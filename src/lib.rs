@@ -1,4 +1,33 @@
-#[derive(Copy, Clone, PartialEq)]
+pub mod attacks;
+pub mod board_builder;
+pub mod book_builder;
+pub mod chess960;
+pub mod clock;
+pub mod display;
+pub mod dtm;
+pub mod encoding;
+pub mod engine;
+pub mod engine_match;
+pub mod epd;
+pub mod fen;
+pub mod game;
+pub mod pgn;
+pub mod polyglot;
+pub mod problem;
+pub mod san;
+pub mod search;
+pub mod see;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod status;
+pub mod svg;
+pub mod syzygy;
+pub mod transposition;
+pub mod uci;
+pub mod validate;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChessPieceKind {
     Rook,
     Pawn,
@@ -8,13 +37,15 @@ pub enum ChessPieceKind {
     King,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChessColour {
     Black,
     White,
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChessPiece {
     pub pos:u64,
     pub prev_pos: u64,
@@ -25,6 +56,29 @@ pub struct ChessPiece {
     pub captured_now:bool,
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Move {
+    pub from: u64,
+    pub to: u64,
+    pub promotion: Option<ChessPieceKind>,
+}
+
+pub fn new_move(from:u64, to:u64)->Move{
+    Move{from, to, promotion: None}
+}
+
+impl Move{
+    /// Long algebraic (UCI) notation, e.g. `e2e4` or `a7a8q`. Chess960 castling
+    /// comes out as king-takes-rook since that is how the board stores it.
+    pub fn to_uci(&self)->String{
+        let mut out=get_square_name(self.from)+&get_square_name(self.to);
+        if let Some(kind)=self.promotion{
+            out.push(fen::get_piece_char(kind, ChessColour::Black));
+        }
+        out
+    }
+}
+
 pub fn get_rank(piece: ChessPiece)->u8{
     if piece.is_captured {
         return 0;
@@ -56,6 +110,27 @@ pub fn get_file(piece: ChessPiece)->u8{
 pub fn get_u64_pos(rank:u8, file:u8)->u64{
     return (0x1<<(8-file))<<(8*(rank-1));
 }
+/// Algebraic name of a single-square mask, e.g. "e4".
+pub fn get_square_name(pos:u64)->String{
+    let file=(b'a'+get_file_u64(pos)-1) as char;
+    let rank=(b'1'+pos.trailing_zeros() as u8/8) as char;
+    format!("{}{}", file, rank)
+}
+/// Index of a single-square mask counting along the ranks from a1 = 0 to h8 = 63,
+/// the numbering Polyglot and Syzygy use. The mask itself has a1 on bit 7.
+pub fn get_square_index(pos:u64)->usize{
+    let bit=pos.trailing_zeros() as usize;
+    (bit/8)*8+7-bit%8
+}
+pub fn get_square_from_name(name:&str)->Option<u64>{
+    let mut chars=name.chars();
+    let file=chars.next()?;
+    let rank=chars.next()?;
+    if chars.next().is_some()||!('a'..='h').contains(&file)||!('1'..='8').contains(&rank){
+        return None;
+    }
+    Some(get_u64_pos(rank as u8-b'0', file as u8-b'a'+1))
+}
 pub fn new_piece(rank: u8, file:u8, kind:ChessPieceKind, col:ChessColour)->ChessPiece{
         let mut out:ChessPiece=ChessPiece{
             pos: get_u64_pos(rank,file),
@@ -101,89 +176,244 @@ fn hash_piece(piece: ChessPiece)->u16{
     return out;
 }
 
+// Strongest first, the order endgame tables name their material in, e.g. `KRBvKP`.
+const PIECE_ORDER:[ChessPieceKind;6]=[ChessPieceKind::King, ChessPieceKind::Queen, ChessPieceKind::Rook, ChessPieceKind::Bishop, ChessPieceKind::Knight, ChessPieceKind::Pawn];
+
+/// Enough slots for a piece on every square.
+pub const MAX_PIECES:usize=64;
+
+/// The pieces on a board, kept packed: a captured piece is taken out and the ones
+/// after it close up, so iteration and `len` only see pieces still on the board.
+/// A table from square to slot makes finding the piece on a square a lookup.
+///
+/// Pieces are moved and captured with `move_pieces` and `remove`. `get_mut` and
+/// `iter_mut` are for changing anything but a piece's `pos`, which the table would
+/// not follow.
+#[derive(Copy, Clone)]
+pub struct PieceList {
+    slots: [ChessPiece;MAX_PIECES],
+    len: usize,
+    squares: [u8;64],
+}
+
+const NO_SLOT:u8=u8::MAX;
+
+impl PieceList{
+    pub fn new()->PieceList{
+        let mut empty:ChessPiece=new_piece(1,1,ChessPieceKind::Pawn,ChessColour::White);
+        empty.pos=0;
+        empty.prev_pos=0;
+        empty.is_captured=true;
+        PieceList{slots: [empty;MAX_PIECES], len: 0, squares: [NO_SLOT;64]}
+    }
+
+    fn get_slot(&self, pos:u64)->Option<usize>{
+        if pos.count_ones()!=1{return None;}
+        let slot=self.squares[pos.trailing_zeros() as usize];
+        (slot!=NO_SLOT).then_some(slot as usize)
+    }
+
+    /// Adds a piece. Fails if its square is already taken or it is not on one square.
+    pub fn push(&mut self, piece:ChessPiece)->bool{
+        if piece.pos.count_ones()!=1||self.get_slot(piece.pos).is_some(){return false;}
+        self.slots[self.len]=piece;
+        self.squares[piece.pos.trailing_zeros() as usize]=self.len as u8;
+        self.len+=1;
+        true
+    }
+
+    /// The piece on `pos`, a single square.
+    pub fn get(&self, pos:u64)->Option<&ChessPiece>{
+        self.get_slot(pos).map(|slot| &self.slots[slot])
+    }
+
+    pub fn get_mut(&mut self, pos:u64)->Option<&mut ChessPiece>{
+        self.get_slot(pos).map(|slot| &mut self.slots[slot])
+    }
+
+    /// Takes the piece off `pos`, returning it flagged `is_captured`. The pieces after
+    /// it keep their order.
+    pub fn remove(&mut self, pos:u64)->Option<ChessPiece>{
+        let slot=self.get_slot(pos)?;
+        let mut piece=self.slots[slot];
+        piece.is_captured=true;
+        self.slots.copy_within(slot+1..self.len, slot);
+        self.len-=1;
+        self.squares[pos.trailing_zeros() as usize]=NO_SLOT;
+        for (idx, moved) in self.slots[slot..self.len].iter().enumerate(){
+            self.squares[moved.pos.trailing_zeros() as usize]=(slot+idx) as u8;
+        }
+        Some(piece)
+    }
+
+    /// Moves the pieces on each `from` square to its `to` square all at once, so a
+    /// king and rook may trade squares when castling in Chess960. Fails, moving
+    /// nothing, if a `from` square is empty or a `to` square holds a piece that stays.
+    pub fn move_pieces(&mut self, moves:&[(u64, u64)])->bool{
+        let mut moved=[0usize;MAX_PIECES];
+        for (idx, &(from, to)) in moves.iter().enumerate(){
+            let Some(slot)=self.get_slot(from) else {return false;};
+            let blocked=self.get_slot(to).is_some()&&!moves.iter().any(|&(other, _)| other==to);
+            let repeated=moves[..idx].iter().any(|&(other_from, other_to)| other_from==from||other_to==to);
+            if to.count_ones()!=1||blocked||repeated{return false;}
+            moved[idx]=slot;
+        }
+        for &(from, _) in moves{
+            self.squares[from.trailing_zeros() as usize]=NO_SLOT;
+        }
+        for (&slot, &(_, to)) in moved.iter().zip(moves){
+            self.slots[slot].pos=to;
+            self.squares[to.trailing_zeros() as usize]=slot as u8;
+        }
+        true
+    }
+
+    /// The number of pieces on the board.
+    pub fn len(&self)->usize{
+        self.len
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.len==0
+    }
+
+    pub fn iter(&self)->std::slice::Iter<'_, ChessPiece>{
+        self.slots[..self.len].iter()
+    }
+
+    pub fn iter_mut(&mut self)->std::slice::IterMut<'_, ChessPiece>{
+        self.slots[..self.len].iter_mut()
+    }
+}
+
+impl Default for PieceList{
+    fn default()->PieceList{
+        PieceList::new()
+    }
+}
+
+impl IntoIterator for PieceList{
+    type Item=ChessPiece;
+    type IntoIter=std::iter::Take<std::array::IntoIter<ChessPiece, MAX_PIECES>>;
+    fn into_iter(self)->Self::IntoIter{
+        self.slots.into_iter().take(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a PieceList{
+    type Item=&'a ChessPiece;
+    type IntoIter=std::slice::Iter<'a, ChessPiece>;
+    fn into_iter(self)->Self::IntoIter{
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut PieceList{
+    type Item=&'a mut ChessPiece;
+    type IntoIter=std::slice::IterMut<'a, ChessPiece>;
+    fn into_iter(self)->Self::IntoIter{
+        self.iter_mut()
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct ChessBoard {
-    pub pieces: [ChessPiece;32],
+    pub pieces: PieceList,
     pub current_move: ChessColour,
     pub rule_50_moves: u8,
-    pub rule_repetition: [[u8;64];50]
+    pub rule_repetition: [[u8;64];100],
+    pub move_number: u16,
+    pub chess960: bool,
 }
 impl ChessBoard{
     fn move_piece(&mut self, from_c:u64, to_c:u64)->bool{
-        let mut other_o:Option<ChessPiece>=get_piece_bit_mask(from_c, *self);
-        if other_o.is_none(){return false;}
-        let piece:ChessPiece= other_o.take().unwrap();
+        let Some(piece)=get_piece_bit_mask(from_c, *self) else {
+            return false;
+        };
         if piece.colour!=self.current_move{return false;}
         if (to_c&filter_moves_check(piece, *self))==0{return false;}
-        for mut piece_n in self.pieces{
-            if piece_n.colour==self.current_move{
-                piece_n.prev_pos=piece_n.pos;
-            }
-            if piece_n.pos==to_c{
-                piece_n.is_captured=true;
-                self.rule_50_moves=0;
-            }
-            if piece_n.pos==from_c{
-                piece_n.pos=to_c;
-                if piece_n.kind==ChessPieceKind::Pawn{
-                    self.rule_50_moves=0;
-                }
-            }
-            
-        }
-        self.rule_repetition[self.rule_50_moves as usize]=hash_board_state(*self);
-        self.rule_50_moves+=1;
-        self.current_move=get_op_col(self.current_move);
-        return true;
+        self.apply_move(from_c, to_c);
+        true
     }
 
-    fn simulate_move_piece(&mut self, from_c:u64, to_c:u64)->bool{
-        let mut other_o:Option<ChessPiece>=get_piece_bit_mask(from_c, *self);
-        if other_o.is_none(){return false;}
-        let piece:ChessPiece= other_o.take().unwrap();
-        if (to_c&get_moves(piece, *self))==0{return false;}
-        if piece.colour!=self.current_move{return false;}
-        for mut piece_n in self.pieces{
-            if piece_n.colour==self.current_move{
-                piece_n.prev_pos=piece_n.pos;
+    // Plays a pseudo-legal move without checking it: handles captures, en passant and
+    // castling (king onto its own rook, or two files over outside of Chess960), then
+    // hands the move to the other side.
+    fn apply_move(&mut self, from_c:u64, to_c:u64){
+        let board=*self;
+        let Some(piece)=get_piece_bit_mask(from_c, board) else {
+            return;
+        };
+        let mut piece_to=to_c;
+        let mut rook_from:u64=0;
+        let mut rook_to:u64=0;
+        if piece.kind==ChessPieceKind::King{
+            if let Some((rook, king_side))=get_castling(piece, to_c, board){
+                (piece_to, rook_to)=get_castling_targets(piece.colour, king_side);
+                rook_from=rook.pos;
             }
-            if piece_n.pos==to_c{
-                piece_n.is_captured=true;
-                piece_n.captured_now=true;
+        }
+        let mut ep_victim:u64=0;
+        if piece.kind==ChessPieceKind::Pawn&&get_file_u64(from_c)!=get_file_u64(to_c)&&(to_c&get_all_piece_map(board))==0{
+            ep_victim=if piece.colour==ChessColour::White {to_c>>8} else {to_c<<8};
+        }
+        let mut irreversible=piece.kind==ChessPieceKind::Pawn;
+        for victim in [to_c, ep_victim]{
+            if self.pieces.get(victim).is_some_and(|p| p.colour!=piece.colour){
+                self.pieces.remove(victim);
+                irreversible=true;
             }
-            if piece_n.pos==from_c{
-                piece_n.pos=to_c;
+        }
+        for piece_n in self.pieces.iter_mut(){
+            if piece_n.colour==piece.colour{
+                piece_n.prev_pos=piece_n.pos;
+                if piece_n.pos==from_c||piece_n.pos==rook_from{
+                    piece_n.has_moved=true;
+                }
             }
-            
-            
         }
-                return true;
+        if rook_from==0{
+            self.pieces.move_pieces(&[(from_c, piece_to)]);
+        }
+        else{
+            self.pieces.move_pieces(&[(from_c, piece_to), (rook_from, rook_to)]);
+        }
+        self.rule_50_moves=if irreversible {0} else {self.rule_50_moves.saturating_add(1)};
+        if self.current_move==ChessColour::Black{
+            self.move_number+=1;
+        }
+        self.current_move=get_op_col(piece.colour);
+        if (self.rule_50_moves as usize)<self.rule_repetition.len(){
+            self.rule_repetition[self.rule_50_moves as usize]=hash_board_state(*self);
+        }
     }
-    
-    fn revert_simulate_move_piece(&mut self)->bool{
-        for mut piece_n in self.pieces{
-            if piece_n.colour==self.current_move{
-                piece_n.pos=piece_n.prev_pos;
-            }
-            if piece_n.captured_now{
-                piece_n.is_captured=false;
-                piece_n.captured_now=false;
+
+    /// Plays `mv` if it is legal, promoting when `mv.promotion` is set.
+    pub fn make_move(&mut self, mv:Move)->bool{
+        if !get_legal_moves(*self).contains(&mv){return false;}
+        let mover=self.current_move;
+        self.apply_move(mv.from, mv.to);
+        if let Some(kind)=mv.promotion{
+            if let Some(piece)=self.pieces.get_mut(mv.to).filter(|p| p.colour==mover){
+                piece.kind=kind;
             }
-                        
         }
-        return true;
+        true
     }
 
     fn promote_piece(&mut self, pos_c:u64, n_kind:ChessPieceKind)->bool{
-        for mut piece in self.pieces{
+        let board=*self;
+        for piece in self.pieces.iter_mut(){
+            if piece.is_captured{continue;}
             if piece.kind!=ChessPieceKind::Pawn{continue;}
-            if piece.colour!=self.current_move{continue;}
+            // The pawn reached the last rank on the previous move, so the turn has already passed.
+            if piece.colour==board.current_move{continue;}
             if piece.pos!=pos_c{continue;}
-            if !can_promote(piece.pos, *self){continue;}
+            if !can_promote(piece.pos, board){continue;}
             piece.kind=n_kind;
             return true;
         }
-        return false;
+        false
     }
 }
 pub fn new_board()->ChessBoard{
@@ -221,20 +451,25 @@ pub fn new_board()->ChessBoard{
         (ChessColour::Black,ChessPieceKind::Pawn,7,7),
         (ChessColour::Black,ChessPieceKind::Pawn,7,8)
     ];
-    let mut pieces:[ChessPiece;32]=[new_piece(1,1,ChessPieceKind::Pawn,ChessColour::White);32];
-    let mut idx=0;
-    for pie in pieces_template{
-        let (mut col,mut kind,mut c_file,mut c_rank)=pie;
-        pieces[idx]=new_piece(c_rank, c_file, kind, col);
-        idx+=1;
-    }
-    let mut out=ChessBoard{
-        pieces: pieces,
-        current_move: ChessColour::White,
+    board_from_template(&pieces_template, ChessColour::White)
+}
+
+fn board_from_template(template:&[(ChessColour, ChessPieceKind, u8, u8)], current_move:ChessColour)->ChessBoard{
+    let mut pieces=PieceList::new();
+    for pie in template.iter(){
+        let (col,kind,c_rank,c_file)=*pie;
+        pieces.push(new_piece(c_rank, c_file, kind, col));
+    }
+    let mut board=ChessBoard{
+        pieces,
+        current_move,
         rule_50_moves: 0,
-        rule_repetition: [[0;64];50]
+        rule_repetition: [[0;64];100],
+        move_number: 1,
+        chess960: false,
     };
-    return out;
+    board.rule_repetition[0]=hash_board_state(board);
+    board
 }
 
 
@@ -261,29 +496,54 @@ fn get_op_col(col:ChessColour)->ChessColour{
     };
 }
 fn get_piece_bit_mask(pos:u64, board:ChessBoard)->Option<ChessPiece>{
+    if pos.count_ones()==1{
+        return board.pieces.get(pos).copied();
+    }
     for piece in board.pieces{
+        if piece.is_captured{continue;}
         if (piece.pos&pos)>0{
             return Some(piece);
         }
     }
     return None;
 }
-fn hash_board_state(mut board: ChessBoard)->[u8;64]{
-    let mut scanner:u64=0x01;
-    let board_state:[u8;64]=[0;64];
-    for mut square in board_state {
-        let mut piece_opt=get_piece_bit_mask(scanner,board);
-        if piece_opt.is_none(){square=0b0;continue;}
-        let piece:ChessPiece= piece_opt.take().unwrap();
-        square=((get_piece_hash(piece.kind)<<3)|get_colour_hash(piece.colour)<<1)|get_colour_hash(board.current_move);
-        scanner=scanner<<1;
+// What makes two positions the same for repetitions: the pieces, the side to move,
+// the rooks that may still castle, and the en passant square when a capture there
+// is legal.
+fn hash_board_state(board: ChessBoard)->[u8;64]{
+    let mut board_state:[u8;64]=[0;64];
+    for piece in board.pieces.iter(){
+        if piece.is_captured{continue;}
+        // Kind codes start at zero, so shift them up to keep a black king distinct from an empty square.
+        board_state[piece.pos.trailing_zeros() as usize]=((get_piece_hash(piece.kind)+1)<<2)|(get_colour_hash(piece.colour)<<1)|get_colour_hash(board.current_move);
     }
-    return board_state;
+    for king in board.pieces.iter().filter(|p| !p.is_captured&&!p.has_moved&&p.kind==ChessPieceKind::King){
+        for king_side in [true, false]{
+            if let Some(rook)=find_castling_rook(*king, king_side, &board.pieces){
+                board_state[rook.pos.trailing_zeros() as usize]|=1<<5;
+            }
+        }
+    }
+    // Only a double step, which resets the 50-move count, opens an en passant capture.
+    let ep=if board.rule_50_moves==0 {get_ep_capture_spots(get_op_col(board.current_move), board)} else {0};
+    if ep>0{
+        let capturers=get_pawn_attacks(ep, get_op_col(board.current_move))&get_kind_map(ChessPieceKind::Pawn, board.current_move, board);
+        let legal=board.pieces.iter().any(|piece| {
+            if piece.is_captured||(piece.pos&capturers)==0{return false;}
+            let mut after=board;
+            after.apply_move(piece.pos, ep);
+            !is_checked(board.current_move, after)
+        });
+        if legal{
+            board_state[ep.trailing_zeros() as usize]|=1<<6;
+        }
+    }
+    board_state
 }
 fn get_ep_capture_spots(col:ChessColour, board:ChessBoard)->u64{
     let mut out:u64=0x00;
     for piece in board.pieces{
-        if (piece.colour==col)&&(piece.kind==ChessPieceKind::Pawn){
+        if !piece.is_captured&&(piece.colour==col)&&(piece.kind==ChessPieceKind::Pawn){
             if col==ChessColour::White{
                 if (piece.pos>>16)==(piece.prev_pos){
                     out=out|(piece.pos>>8);
@@ -300,18 +560,19 @@ fn get_ep_capture_spots(col:ChessColour, board:ChessBoard)->u64{
 
 }
 
+fn get_pawn_attacks(pos:u64, col:ChessColour)->u64{
+    let file=get_file_u64(pos);
+    let (toward_a, toward_h)=match col{
+        ChessColour::White=>(pos<<9, pos<<7),
+        ChessColour::Black=>(pos>>7, pos>>9),
+    };
+    let mut out:u64=0x00;
+    if file!=1{out|=toward_a;}
+    if file!=8{out|=toward_h;}
+    out
+}
 fn get_pawn_captures(piece: ChessPiece, _board:ChessBoard)->u64{
-    if piece.colour==ChessColour::White{
-        if get_file(piece)==1{return piece.pos<<7;}
-        if get_file(piece)==8{return piece.pos<<9;}
-        return (piece.pos<<7)|(piece.pos<<9);
-    } 
-    else if piece.colour==ChessColour::Black {
-        if get_file(piece)==1{return piece.pos>>9;}
-        if get_file(piece)==8{return piece.pos>>7;}
-        return (piece.pos>>7)|(piece.pos>>9);
-    }
-    return 0x0;
+    get_pawn_attacks(piece.pos, piece.colour)
 }
 
 fn get_pawn_moves(piece: ChessPiece,board:ChessBoard)->u64{
@@ -335,238 +596,204 @@ fn get_pawn_moves(piece: ChessPiece,board:ChessBoard)->u64{
 }
 
 
-fn get_knight_moves(piece: ChessPiece, board:ChessBoard)->u64{
+fn get_knight_attacks(pos:u64)->u64{
+    let file=get_file_u64(pos);
     let mut out:u64=0x00;
-    if get_file(piece)>=2{
-        out=out|(piece.pos<<17)|(piece.pos>>15);
-        if get_file(piece)>=3{
-            out=out|(piece.pos>>6)|(piece.pos<<10);
+    if file>=2{
+        out|=(pos<<17)|(pos>>15);
+        if file>=3{
+            out|=(pos>>6)|(pos<<10);
         }
     }
-    if get_file(piece)<=7{
-        out=out|(piece.pos>>17)|(piece.pos<<15);
-        if get_file(piece)<=6{
-            out=out|(piece.pos>>10)|(piece.pos<<6);
+    if file<=7{
+        out|=(pos>>17)|(pos<<15);
+        if file<=6{
+            out|=(pos>>10)|(pos<<6);
         }
     }
-    return out&(!get_piece_map(piece.colour,board));
+    out
+}
+fn get_knight_moves(piece: ChessPiece, board:ChessBoard)->u64{
+    get_knight_attacks(piece.pos)&(!get_piece_map(piece.colour,board))
 }
-fn get_rook_moves(piece: ChessPiece, board:ChessBoard)->u64{
-    let capturable:u64=get_piece_map(get_op_col(piece.colour), board);
-    let blockers:u64=get_piece_map(piece.colour, board);
-    let rank:u8=get_rank(piece);
-    let file:u8=get_file(piece);
-    let left_move_max=rank-1;
-    let right_move_max=8-rank;
-    let up_move_max=file-1;
-    let down_move_max=8-file;
 
+// Walks from `pos` in steps of `shift` (left when `up` is set, right otherwise)
+// for at most `max` squares, stopping on and including the first occupied square.
+fn get_ray_attacks(pos:u64, shift:u32, up:bool, max:u8, occupied:u64)->u64{
     let mut out:u64=0x00;
-    for offset in 1..=up_move_max{
-        if ((piece.pos<<(8*offset))&(!blockers))>0{
-            out=out|(piece.pos<<(8*offset));
-            if ((piece.pos<<(8*offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    for offset in 1..=right_move_max{
-        if ((piece.pos>>(offset))&(!blockers))>0{
-            out=out|(piece.pos>>(offset));
-            if ((piece.pos>>(offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    for offset in 1..=down_move_max{
-        if ((piece.pos>>(8*offset))&(!blockers))>0{
-            out=out|(piece.pos>>(8*offset));
-            if ((piece.pos>>(8*offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
+    let mut scanner=pos;
+    for _ in 0..max{
+        scanner=if up {scanner<<shift} else {scanner>>shift};
+        out|=scanner;
+        if (scanner&occupied)>0{
             break;
         }
     }
-    for offset in 1..=left_move_max{
-        if ((piece.pos<<(offset))&(!blockers))>0{
-            out=out|(piece.pos<<(offset));
-            if ((piece.pos<<(offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    return out;
+    out
+}
+fn get_rook_attacks(pos:u64, occupied:u64)->u64{
+    // get_rank_u64 counts ranks from the top of the mask, so rank-1 is the room left upwards.
+    let rank:u8=get_rank_u64(pos);
+    let file:u8=get_file_u64(pos);
+    get_ray_attacks(pos, 8, true, rank-1, occupied)
+        |get_ray_attacks(pos, 8, false, 8-rank, occupied)
+        |get_ray_attacks(pos, 1, true, file-1, occupied)
+        |get_ray_attacks(pos, 1, false, 8-file, occupied)
+}
+fn get_bishop_attacks(pos:u64, occupied:u64)->u64{
+    let rank:u8=get_rank_u64(pos);
+    let file:u8=get_file_u64(pos);
+    let up_max=rank-1;
+    let down_max=8-rank;
+    let left_max=file-1;
+    let right_max=8-file;
+    get_ray_attacks(pos, 9, true, up_max.min(left_max), occupied)
+        |get_ray_attacks(pos, 7, true, up_max.min(right_max), occupied)
+        |get_ray_attacks(pos, 7, false, down_max.min(left_max), occupied)
+        |get_ray_attacks(pos, 9, false, down_max.min(right_max), occupied)
+}
+fn get_rook_moves(piece: ChessPiece, board:ChessBoard)->u64{
+    get_rook_attacks(piece.pos, get_all_piece_map(board))&(!get_piece_map(piece.colour, board))
 }
 fn get_bishop_moves(piece:ChessPiece, board:ChessBoard)->u64{
-    
-    let capturable:u64=get_piece_map(get_op_col(piece.colour), board);
-    let blockers:u64=get_piece_map(piece.colour, board);
-    let rank:u8=get_rank(piece);
-    let file:u8=get_file(piece);
-    let left_move_max=rank-1;
-    let right_move_max=8-rank;
-    let up_move_max=file-1;
-    let down_move_max=8-file;
-    let ul_max=if up_move_max>left_move_max {left_move_max} else {up_move_max};
-    let dl_max=if down_move_max>left_move_max {left_move_max} else {down_move_max};
-    let ur_max=if up_move_max>right_move_max {right_move_max} else {up_move_max};
-    let dr_max=if down_move_max>right_move_max {right_move_max} else {down_move_max};
-    let mut out:u64=0x00;
-    for offset in 1..=ul_max{
-        if ((piece.pos<<(9*offset))&(!blockers))>0{
-            out=out|(piece.pos<<(9*offset));
-            if ((piece.pos<<(9*offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    for offset in 1..=ur_max{
-        if ((piece.pos<<(7*offset))&(!blockers))>0{
-            out=out|(piece.pos<<(7*offset));
-            if ((piece.pos<<(7*offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    for offset in 1..=dl_max{
-        if ((piece.pos>>(7*offset))&(!blockers))>0{
-            out=out|(piece.pos>>(7*offset));
-            if ((piece.pos>>(7*offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    for offset in 1..=dr_max{
-        if ((piece.pos>>(9*offset))&(!blockers))>0{
-            out=out|(piece.pos>>(9*offset));
-            if ((piece.pos>>(9*offset))&capturable)>0{
-                break;
-            }
-        }
-        else {
-            break;
-        }
-    }
-    return out;
+    get_bishop_attacks(piece.pos, get_all_piece_map(board))&(!get_piece_map(piece.colour, board))
 }
 fn get_queen_moves(piece:ChessPiece, board:ChessBoard)->u64{
-    return get_bishop_moves(piece,board)|get_rook_moves(piece, board);
-}
-fn get_king_moves_colour(col:ChessColour, board:ChessBoard)->u64{
+    get_bishop_moves(piece,board)|get_rook_moves(piece, board)
+}
+fn get_king_attacks(pos:u64)->u64{
+    let file=get_file_u64(pos);
+    let mut out:u64=(pos>>8)|(pos<<8);
+    if file!=1{out|=(pos>>7)|(pos<<1)|(pos<<9);}
+    if file!=8{out|=(pos>>1)|(pos>>9)|(pos<<7);}
+    out
+}
+// Every square `piece` attacks when only the pieces in `occupied` block sliders.
+fn get_piece_attacks(piece:ChessPiece, occupied:u64, board:ChessBoard)->u64{
+    match piece.kind{
+        ChessPieceKind::Pawn=>get_pawn_captures(piece, board),
+        ChessPieceKind::Knight=>get_knight_attacks(piece.pos),
+        ChessPieceKind::Bishop=>get_bishop_attacks(piece.pos, occupied),
+        ChessPieceKind::Rook=>get_rook_attacks(piece.pos, occupied),
+        ChessPieceKind::Queen=>get_bishop_attacks(piece.pos, occupied)|get_rook_attacks(piece.pos, occupied),
+        ChessPieceKind::King=>get_king_attacks(piece.pos),
+    }
+}
+fn get_kind_map(kind:ChessPieceKind, col:ChessColour, board:ChessBoard)->u64{
+    let mut out:u64=0x00;
     for piece in board.pieces{
-        if piece.colour!=col{continue;}
-        if piece.kind!=ChessPieceKind::King{continue;}
-        if get_file(piece)==8{
-            return ((piece.pos>>8)|(piece.pos>>7)|(piece.pos<<1)|(piece.pos<<9)|(piece.pos<<8))&(!(get_piece_map(col,board)));
-        }
-        if get_file(piece)==1{
-            return ((piece.pos>>1)|(piece.pos>>9)|(piece.pos>>8)|(piece.pos<<8)|(piece.pos<<7))&(!(get_piece_map(col,board)));
-        }
-        return ((piece.pos>>1)|(piece.pos>>9)|(piece.pos>>8)|(piece.pos>>7)|(piece.pos<<1)|(piece.pos<<9)|(piece.pos<<8)|(piece.pos<<7))&(!(get_piece_map(col,board)));
-
+        if piece.is_captured||piece.kind!=kind||piece.colour!=col{continue;}
+        out|=piece.pos;
     }
-    return 0x00;
+    out
 }
-
-fn get_capture_map_king_check(col:ChessColour,board:ChessBoard)->u64{
+// Squares of pieces of either colour standing in `occupied` that attack `square`,
+// found by looking outwards from `square` with each kind's attack pattern.
+fn get_attackers(square:u64, occupied:u64, board:ChessBoard)->u64{
+    let mut out:u64=0x00;
+    for col in [ChessColour::White, ChessColour::Black]{
+        let queens=get_kind_map(ChessPieceKind::Queen, col, board);
+        out|=get_pawn_attacks(square, get_op_col(col))&get_kind_map(ChessPieceKind::Pawn, col, board);
+        out|=get_knight_attacks(square)&get_kind_map(ChessPieceKind::Knight, col, board);
+        out|=get_king_attacks(square)&get_kind_map(ChessPieceKind::King, col, board);
+        out|=get_bishop_attacks(square, occupied)&(get_kind_map(ChessPieceKind::Bishop, col, board)|queens);
+        out|=get_rook_attacks(square, occupied)&(get_kind_map(ChessPieceKind::Rook, col, board)|queens);
+    }
+    out&occupied
+}
+// Squares attacked by `col` when only `occupied` blocks sliders, including squares
+// that hold `col`'s own pieces so a king cannot capture a defended piece.
+fn get_attack_map(col:ChessColour, occupied:u64, board:ChessBoard)->u64{
     let mut out:u64=0x00;
     for piece in board.pieces{
-        if piece.colour!=col{continue;}
-        out=out|match piece.kind{
-            ChessPieceKind::Rook=>get_rook_moves(piece, board),
-            ChessPieceKind::Pawn=>get_pawn_captures(piece, board),
-            ChessPieceKind::Knight=>get_knight_moves(piece, board),
-            ChessPieceKind::King=>get_king_moves_colour(col,board),
-            ChessPieceKind::Bishop=>get_bishop_moves(piece, board),
-            ChessPieceKind::Queen=>get_queen_moves(piece, board),
-        }
+        if piece.colour!=col||piece.is_captured{continue;}
+        out|=get_piece_attacks(piece, occupied, board);
     }
-    return out;
+    out
+}
+fn get_king_pos(col:ChessColour, board:ChessBoard)->u64{
+    get_kind_map(ChessPieceKind::King, col, board)
+}
+// The attacked king is left out of the blockers so it cannot step back along a checking ray.
+fn get_capture_map_king_check(col:ChessColour,board:ChessBoard)->u64{
+    get_attack_map(col, get_all_piece_map(board)&!get_king_pos(get_op_col(col), board), board)
 }
 
 fn get_king_moves(piece: ChessPiece, board: ChessBoard)->u64{
-    let mut out:u64=(piece.pos>>8)|(piece.pos<<8);
-    let right_side:u64=(piece.pos>>1)|(piece.pos>>9)|(piece.pos<<7);
-    let left_side:u64=(piece.pos>>7)|(piece.pos<<1)|(piece.pos<<9);
-    if get_file(piece)!=1{out=out|left_side;}
-    if get_file(piece)!=8{out=out|right_side;}
-    return out&(!(get_piece_map(piece.colour,board)|get_capture_map_king_check(get_op_col(piece.colour), board)));
+    get_king_attacks(piece.pos)&(!(get_piece_map(piece.colour,board)|get_capture_map_king_check(get_op_col(piece.colour), board)))
 }
 
 fn get_capture_map(col:ChessColour,board:ChessBoard)->u64{
-    let mut out:u64=0x00;
-    for piece in board.pieces{
-        if piece.colour!=col{continue;}
-        out=out|match piece.kind{
-            ChessPieceKind::Rook=>get_rook_moves(piece, board),
-            ChessPieceKind::Pawn=>get_pawn_captures(piece, board),
-            ChessPieceKind::Knight=>get_knight_moves(piece, board),
-            ChessPieceKind::King=>get_king_moves(piece,board),
-            ChessPieceKind::Bishop=>get_bishop_moves(piece, board),
-            ChessPieceKind::Queen=>get_queen_moves(piece, board),
-        }
-    }
-    return out;
+    get_attack_map(col, get_all_piece_map(board), board)
 }
 fn is_checked(col:ChessColour,board:ChessBoard)->bool{
-    let check_map=get_capture_map_king_check(get_op_col(col), board);
-    for piece in board.pieces{
-        if piece.colour!=col{continue;}
-        if piece.kind!=ChessPieceKind::King{continue;}
-        return (piece.pos&check_map)>0;
+    let king=get_king_pos(col, board);
+    if king==0{return true;}
+    get_attackers(king, get_all_piece_map(board), board)&get_piece_map(get_op_col(col), board)>0
+}
+// Every square from `a` to `b` inclusive, for two squares on the same rank.
+fn get_rank_span(a:u64, b:u64)->u64{
+    let (low, high)=if a<b {(a, b)} else {(b, a)};
+    (high-low)|high
+}
+fn get_back_rank(col:ChessColour)->u64{
+    match col{
+        ChessColour::White=>0xFF,
+        ChessColour::Black=>0xFF00000000000000,
+    }
+}
+// King and rook destinations for castling; the same files in Chess960 as in standard chess.
+fn get_castling_targets(col:ChessColour, king_side:bool)->(u64, u64){
+    let rank=if col==ChessColour::White {1} else {8};
+    if king_side {(get_u64_pos(rank,7), get_u64_pos(rank,6))} else {(get_u64_pos(rank,3), get_u64_pos(rank,4))}
+}
+// The unmoved rook `king` may castle with on the given side, the outermost one if
+// an odd setup left several.
+fn get_castling_rook(king:ChessPiece, king_side:bool, board:ChessBoard)->Option<ChessPiece>{
+    find_castling_rook(king, king_side, &board.pieces)
+}
+fn find_castling_rook(king:ChessPiece, king_side:bool, pieces:&PieceList)->Option<ChessPiece>{
+    if king.has_moved||(king.pos&get_back_rank(king.colour))==0{return None;}
+    let mut out:Option<ChessPiece>=None;
+    for &piece in pieces{
+        if piece.is_captured||piece.has_moved||piece.colour!=king.colour||piece.kind!=ChessPieceKind::Rook{continue;}
+        if (piece.pos&get_back_rank(king.colour))==0{continue;}
+        // Lower bits lie towards the h-file.
+        if king_side!=(piece.pos<king.pos){continue;}
+        let outer=match out{
+            None=>true,
+            Some(other)=>(piece.pos<other.pos)==king_side,
+        };
+        if outer{out=Some(piece);}
     }
-    return true;
+    out
 }
-fn get_long_castle_move(piece:ChessPiece, board:ChessBoard)->bool{
-    if piece.has_moved{return false;}
-    if is_checked(piece.colour,board){return false;}
-    if piece.kind!=ChessPieceKind::King{return false}
-    let mut other_o:Option<ChessPiece>=get_piece_bit_mask(piece.pos<<4, board);
-    if other_o.is_none(){return false;}
-    let other:ChessPiece= other_o.take().unwrap();
-    if other.has_moved{return false;}
-    if other.kind!=ChessPieceKind::Rook{return false;}
-    let block_check_map=!(get_capture_map(get_op_col(piece.colour), board)|get_piece_map(piece.colour,board));
-    return ((((((piece.pos<<1)&block_check_map)<<1)&block_check_map)<<1)&block_check_map)>0;        
+// Recognises `to_c` as a castling destination for `king`: its own castling rook, or
+// outside of Chess960 the square two files over.
+fn get_castling(king:ChessPiece, to_c:u64, board:ChessBoard)->Option<(ChessPiece, bool)>{
+    for king_side in [true, false]{
+        let Some(rook)=get_castling_rook(king, king_side, board) else {continue;};
+        let two_over=if king_side {king.pos>>2} else {king.pos<<2};
+        if rook.pos==to_c||(!board.chess960&&two_over==to_c){
+            return Some((rook, king_side));
+        }
+    }
+    None
 }
-
-
-fn get_short_castle_move(piece:ChessPiece, board:ChessBoard)->bool{
-    if piece.has_moved{return false;}
-    if is_checked(piece.colour,board){return false;}
-    if piece.kind!=ChessPieceKind::King{return false}
-    let mut other_o:Option<ChessPiece>=get_piece_bit_mask(piece.pos>>3, board);
-    if other_o.is_none(){return false;}
-    let other:ChessPiece= other_o.take().unwrap();
-    if other.has_moved{return false;}
-    if other.kind!=ChessPieceKind::Rook{return false;}
-    let block_check_map=!(get_capture_map(get_op_col(piece.colour), board)|get_piece_map(piece.colour,board));
-    return ((((piece.pos>>1)&block_check_map)>>1)&block_check_map)>0;
+fn get_castle_moves(king:ChessPiece, board:ChessBoard)->u64{
+    let mut out:u64=0x00;
+    for king_side in [true, false]{
+        let Some(rook)=get_castling_rook(king, king_side, board) else {continue;};
+        let (king_to, rook_to)=get_castling_targets(king.colour, king_side);
+        let king_path=get_rank_span(king.pos, king_to);
+        let blockers=get_all_piece_map(board)&!(king.pos|rook.pos);
+        if ((king_path|get_rank_span(rook.pos, rook_to))&blockers)>0{continue;}
+        if (king_path&get_capture_map(get_op_col(king.colour), board))>0{continue;}
+        out|=if board.chess960 {rook.pos} else {king_to};
+    }
+    out
 }
 
-
-
-
 fn get_rank_u64(pos:u64)->u8{
     
     let mut scanner:u64=0xFF;
@@ -594,7 +821,7 @@ fn get_file_u64(pos:u64)->u8{
 
 pub fn get_moves(piece:ChessPiece, board:ChessBoard)->u64{
     return match piece.kind{
-    ChessPieceKind::King=>get_king_moves(piece, board),
+    ChessPieceKind::King=>get_king_moves(piece, board)|get_castle_moves(piece, board),
     ChessPieceKind::Queen=>get_queen_moves(piece, board),
     ChessPieceKind::Rook=>get_rook_moves(piece, board),
     ChessPieceKind::Bishop=>get_bishop_moves(piece, board),
@@ -602,24 +829,43 @@ pub fn get_moves(piece:ChessPiece, board:ChessBoard)->u64{
     ChessPieceKind::Pawn=>get_pawn_moves(piece, board),
     }
 }
-pub fn filter_moves_check(piece:ChessPiece, mut board:ChessBoard)->u64{
+pub fn filter_moves_check(piece:ChessPiece, board:ChessBoard)->u64{
     let mut moves:u64=0x00;
     let mut scanner:u64=0x01;
-    let mut mvs=get_moves(piece, board); 
-    for a in 1..=64 {
-        let t_pos=mvs&scanner;
-        if t_pos>0{
-            board.simulate_move_piece(piece.pos, t_pos);
-            if !is_checked(piece.colour,board){
-                moves|=t_pos;
+    let mvs=get_moves(piece, board);
+    for _ in 0..64 {
+        if (mvs&scanner)>0{
+            let mut after=board;
+            after.apply_move(piece.pos, scanner);
+            if !is_checked(piece.colour, after){
+                moves|=scanner;
+            }
+        }
+        scanner<<=1;
+    }
+    moves
+}
+/// Every legal move for the side to move, with one entry per promotion piece.
+pub fn get_legal_moves(board:ChessBoard)->Vec<Move>{
+    let mut out=Vec::new();
+    let promotion_rank=get_back_rank(get_op_col(board.current_move));
+    for piece in board.pieces{
+        if piece.is_captured||piece.colour!=board.current_move{continue;}
+        let mut targets=filter_moves_check(piece, board);
+        while targets>0{
+            let to=targets&targets.wrapping_neg();
+            targets&=targets-1;
+            if piece.kind==ChessPieceKind::Pawn&&(to&promotion_rank)>0{
+                for kind in [ChessPieceKind::Queen, ChessPieceKind::Rook, ChessPieceKind::Bishop, ChessPieceKind::Knight]{
+                    out.push(Move{from: piece.pos, to, promotion: Some(kind)});
+                }
+            }
+            else{
+                out.push(new_move(piece.pos, to));
             }
-            board.revert_simulate_move_piece();
-
         }
-        scanner=scanner<<1;
-        
     }
-    return moves;
+    out
 }
 pub fn can_promote(pos:u64, board:ChessBoard)->bool{
     let mut other_o:Option<ChessPiece>=get_piece_bit_mask(pos, board);
@@ -630,19 +876,30 @@ pub fn can_promote(pos:u64, board:ChessBoard)->bool{
     return (piece.pos&0xFF00000000000000)>0;
 
 }
-pub fn promote_piece(pos:u64, kind:ChessPieceKind, mut board:ChessBoard)->bool{
-    return board.promote_piece(pos, kind);
+pub fn promote_piece(pos:u64, kind:ChessPieceKind, board:&mut ChessBoard)->bool{
+    board.promote_piece(pos, kind)
 }
 
 
-pub fn move_piece(mut board:ChessBoard, from_c:u64, to_c:u64)->bool{
-    return board.move_piece(from_c, to_c);
+pub fn move_piece(board:&mut ChessBoard, from_c:u64, to_c:u64)->bool{
+    board.move_piece(from_c, to_c)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn perft(board:ChessBoard, depth:u8)->u64{
+        if depth==0{return 1;}
+        let mut nodes=0;
+        for mv in get_legal_moves(board){
+            let mut after=board;
+            assert!(after.make_move(mv));
+            nodes+=perft(after, depth-1);
+        }
+        nodes
+    }
+
     #[test]
     fn test_with_piece() {
         let test_piece=ChessPiece{
@@ -671,8 +928,83 @@ mod tests {
         assert_eq!(get_file_u64(pos),1);
     }
     #[test]
+    fn test_square_index(){
+        assert_eq!(get_square_index(get_u64_pos(1,1)), 0);
+        assert_eq!(get_square_index(get_square_from_name("e4").unwrap()), 28);
+        assert_eq!(get_square_index(get_u64_pos(8,8)), 63);
+    }
+    #[test]
     fn test_no_pos(){
         let pos= 0b0000000000000000000000000000000000000000000000000000000000000000;
         assert_eq!(get_rank_u64(pos),0);
     }
+    #[test]
+    fn test_more_than_32_pieces(){
+        let fen="rnbqkbnr/pppppppp/nnnnnnnn/8/8/NNNNNNNN/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let mut board=ChessBoard::from_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(get_all_piece_map(board).count_ones(), 48);
+        assert!(board.make_move(Move{from: get_u64_pos(3,1), to: get_u64_pos(5,2), promotion: None}));
+        assert_eq!(get_all_piece_map(board).count_ones(), 48);
+        assert_eq!(board.pieces.len(), 48);
+    }
+    #[test]
+    fn test_piece_list(){
+        let mut pieces=PieceList::new();
+        for idx in 0..MAX_PIECES{
+            assert!(pieces.push(new_piece(idx as u8/8+1, idx as u8%8+1, ChessPieceKind::Pawn, ChessColour::White)));
+        }
+        assert!(!pieces.push(new_piece(1,1,ChessPieceKind::Queen,ChessColour::White)));
+        let a1=get_u64_pos(1,1);
+        let removed=pieces.remove(a1).unwrap();
+        assert!(removed.is_captured);
+        assert_eq!(pieces.len(), MAX_PIECES-1);
+        assert!(pieces.get(a1).is_none());
+        assert!(pieces.iter().all(|p| p.pos!=a1));
+        assert!(pieces.push(new_piece(1,1,ChessPieceKind::Queen,ChessColour::White)));
+        assert_eq!(pieces.get(a1).map(|p| p.kind), Some(ChessPieceKind::Queen));
+        for piece in pieces.iter(){
+            assert_eq!(pieces.get(piece.pos).map(|p| p.pos), Some(piece.pos));
+        }
+
+        // A king and rook trading squares, as when castling in Chess960.
+        let mut pieces=PieceList::new();
+        let (f1, g1, h1)=(get_u64_pos(1,6), get_u64_pos(1,7), get_u64_pos(1,8));
+        pieces.push(new_piece(1,6,ChessPieceKind::King,ChessColour::White));
+        pieces.push(new_piece(1,7,ChessPieceKind::Rook,ChessColour::White));
+        assert!(!pieces.move_pieces(&[(f1, g1)]));
+        assert!(!pieces.move_pieces(&[(h1, g1)]));
+        assert!(pieces.move_pieces(&[(f1, g1), (g1, f1)]));
+        assert_eq!(pieces.get(g1).map(|p| p.kind), Some(ChessPieceKind::King));
+        assert_eq!(pieces.get(f1).map(|p| p.kind), Some(ChessPieceKind::Rook));
+
+        let mut board=new_board();
+        for san in ["e4", "d5", "exd5"]{
+            assert!(board.make_move(board.parse_san(san).unwrap()));
+        }
+        assert_eq!(board.pieces.len(), 31);
+        assert!(board.pieces.iter().all(|p| !p.is_captured));
+    }
+    #[test]
+    fn test_perft_start(){
+        assert_eq!(perft(new_board(), 3), 8902);
+    }
+    #[test]
+    fn test_perft_tricky_positions(){
+        let kiwipete=ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft(kiwipete, 2), 2039);
+        let endgame=ChessBoard::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(endgame, 3), 2812);
+        let promotions=ChessBoard::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(perft(promotions, 2), 264);
+        let middlegame=ChessBoard::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        assert_eq!(perft(middlegame, 2), 1486);
+    }
+    #[test]
+    fn test_perft_chess960(){
+        let board=ChessBoard::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+        assert_eq!(perft(board, 2), 528);
+        let board=ChessBoard::from_fen("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9").unwrap();
+        assert_eq!(perft(board, 2), 807);
+    }
 }
@@ -0,0 +1,321 @@
+use super::*;
+use crate::fen::{FenError, START_FEN};
+use crate::game::{Game, GameResult, Termination};
+use std::fmt;
+
+const SEVEN_TAG_ROSTER:[&str;7]=["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+impl Game{
+    /// The game in PGN export format: the Seven Tag Roster (unknown values as `?`),
+    /// `Termination` once the game is over, `SetUp`/`FEN` for non-standard starts,
+    /// any other tags, then the movetext wrapped at 80 columns.
+    pub fn to_pgn(&self)->String{
+        let mut out=String::new();
+        for name in SEVEN_TAG_ROSTER{
+            let value=match name{
+                "Result"=>self.result().as_pgn(),
+                _=>self.get_tag(name).unwrap_or("?"),
+            };
+            out.push_str(&format!("[{} \"{}\"]\n", name, escape_tag(value)));
+        }
+        if let Some(termination)=self.termination(){
+            out.push_str(&format!("[Termination \"{}\"]\n", termination.as_pgn()));
+        }
+        let start_fen=self.start.to_fen();
+        if self.start.chess960{
+            out.push_str("[Variant \"Chess960\"]\n");
+        }
+        if start_fen!=START_FEN||self.start.chess960{
+            out.push_str("[SetUp \"1\"]\n");
+            out.push_str(&format!("[FEN \"{}\"]\n", start_fen));
+        }
+        for (name, value) in self.tags.iter(){
+            let generated=["Termination", "Variant", "SetUp", "FEN"];
+            if SEVEN_TAG_ROSTER.contains(&name.as_str())||generated.contains(&name.as_str()){continue;}
+            out.push_str(&format!("[{} \"{}\"]\n", name, escape_tag(value)));
+        }
+        out.push('\n');
+
+        let mut tokens:Vec<String>=Vec::new();
+        let mut board=self.start;
+        for (idx, mv) in self.moves.iter().enumerate(){
+            if board.current_move==ChessColour::White{
+                tokens.push(format!("{}.", board.move_number));
+            }
+            else if idx==0{
+                tokens.push(format!("{}...", board.move_number));
+            }
+            tokens.push(board.to_san(*mv));
+            board.make_move(*mv);
+        }
+        tokens.push(self.result().as_pgn().to_string());
+
+        let mut line=String::new();
+        for token in tokens{
+            if !line.is_empty()&&line.len()+1+token.len()>80{
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            if !line.is_empty(){
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        out
+    }
+}
+
+fn escape_tag(value:&str)->String{
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    InvalidTag(String),
+    InvalidFen(FenError),
+    /// A move that is not legal, or not SAN at all, at the given ply of the game.
+    IllegalMove{ply: usize, san: String},
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            PgnError::InvalidTag(line)=>write!(f, "invalid tag pair '{}'", line),
+            PgnError::InvalidFen(err)=>write!(f, "invalid FEN tag: {}", err),
+            PgnError::IllegalMove{ply, san}=>write!(f, "illegal move '{}' at ply {}", san, ply+1),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+// A game's tag pairs and unparsed movetext.
+struct RawGame {
+    tags: Vec<String>,
+    movetext: String,
+}
+
+fn split_games(text:&str)->Vec<RawGame>{
+    let mut out:Vec<RawGame>=Vec::new();
+    let mut in_movetext=false;
+    for line in text.lines(){
+        let line=line.trim();
+        // Lines starting with `%` are an escape mechanism for other programs.
+        if line.starts_with('%'){continue;}
+        if line.starts_with('['){
+            if in_movetext||out.is_empty(){
+                out.push(RawGame{tags: Vec::new(), movetext: String::new()});
+                in_movetext=false;
+            }
+            out.last_mut().unwrap().tags.push(line.to_string());
+        }
+        else if !line.is_empty(){
+            if out.is_empty(){
+                out.push(RawGame{tags: Vec::new(), movetext: String::new()});
+            }
+            let game=out.last_mut().unwrap();
+            game.movetext.push_str(line);
+            game.movetext.push('\n');
+            in_movetext=true;
+        }
+    }
+    out
+}
+
+fn parse_tag(line:&str)->Option<(String, String)>{
+    let inner=line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, rest)=inner.split_once(char::is_whitespace)?;
+    let rest=rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut value=String::new();
+    let mut escaped=false;
+    for c in rest.chars(){
+        if c=='\\'&&!escaped{
+            escaped=true;
+            continue;
+        }
+        value.push(c);
+        escaped=false;
+    }
+    Some((name.to_string(), value))
+}
+
+// Mainline SAN tokens, with comments, variations, NAGs, move numbers and the result removed.
+fn get_movetext_tokens(movetext:&str)->Vec<String>{
+    let mut tokens=Vec::new();
+    let mut token=String::new();
+    let mut chars=movetext.chars();
+    let mut variation_depth=0;
+    while let Some(c)=chars.next(){
+        let separator=match c{
+            '{'=>{
+                for c in chars.by_ref(){
+                    if c=='}'{break;}
+                }
+                true
+            }
+            ';'=>{
+                for c in chars.by_ref(){
+                    if c=='\n'{break;}
+                }
+                true
+            }
+            '('=>{
+                variation_depth+=1;
+                true
+            }
+            ')'=>{
+                variation_depth-=1;
+                true
+            }
+            c if c.is_whitespace()=>true,
+            _=>false,
+        };
+        if separator{
+            if !token.is_empty(){
+                tokens.push(std::mem::take(&mut token));
+            }
+        }
+        else if variation_depth==0{
+            token.push(c);
+        }
+    }
+    if !token.is_empty(){
+        tokens.push(token);
+    }
+    tokens.into_iter().filter_map(|token| {
+        // Move numbers may be glued to the move, as in `1.e4`.
+        let digits=token.trim_start_matches(|c:char| c.is_ascii_digit());
+        let token=if digits.starts_with('.') {digits.trim_start_matches('.')} else {token.as_str()};
+        if token.is_empty()||token.starts_with('$')||GameResult::from_pgn(token).is_some(){None} else {Some(token.to_string())}
+    }).collect()
+}
+
+fn get_game(raw:&RawGame)->Result<Game, PgnError>{
+    let mut tags=Vec::new();
+    for line in raw.tags.iter(){
+        tags.push(parse_tag(line).ok_or_else(|| PgnError::InvalidTag(line.clone()))?);
+    }
+    let get_tag=|name:&str| tags.iter().find(|(tag, _)| tag==name).map(|(_, value)| value.as_str());
+    let mut board=match get_tag("FEN"){
+        Some(fen)=>ChessBoard::from_fen(fen).map_err(PgnError::InvalidFen)?,
+        None=>new_board(),
+    };
+    if get_tag("Variant").is_some_and(|variant| variant.contains("960")){
+        board.chess960=true;
+    }
+    let mut game=Game::from_board(board);
+    for (ply, san) in get_movetext_tokens(&raw.movetext).into_iter().enumerate(){
+        let mv=game.board.parse_san(&san).ok_or(PgnError::IllegalMove{ply, san: san.clone()})?;
+        if !game.make_move(mv){
+            return Err(PgnError::IllegalMove{ply, san});
+        }
+    }
+    for (name, value) in tags.iter(){
+        game.set_tag(name, value);
+    }
+    // Results decided away from the board, such as resignations, only live in the tags.
+    let result=get_tag("Result").and_then(GameResult::from_pgn).unwrap_or(GameResult::Ongoing);
+    if !game.is_over()&&result!=GameResult::Ongoing{
+        let termination=match get_tag("Termination"){
+            Some("time forfeit") if result==GameResult::Draw=>Termination::TimeoutVsInsufficientMaterial,
+            Some("time forfeit")=>Termination::TimeForfeit,
+            Some("adjudication")=>Termination::Adjudication,
+            _ if result==GameResult::Draw=>Termination::Agreement,
+            _=>Termination::Resignation,
+        };
+        game.finish(result, termination);
+    }
+    Ok(game)
+}
+
+/// Reads every game of a PGN database. Only the mainline is kept; comments,
+/// variations and NAGs are skipped. A bad game does not stop the games after it.
+pub fn read_pgn(text:&str)->Vec<Result<Game, PgnError>>{
+    split_games(text).iter().map(get_game).collect()
+}
+
+impl Game{
+    /// Reads a single game, the first one if `text` holds several.
+    pub fn from_pgn(text:&str)->Result<Game, PgnError>{
+        match split_games(text).first(){
+            Some(raw)=>get_game(raw),
+            None=>Ok(Game::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from:&str, to:&str)->Move{
+        new_move(get_square_from_name(from).unwrap(), get_square_from_name(to).unwrap())
+    }
+
+    #[test]
+    fn test_export_result_and_termination(){
+        let mut game=Game::new();
+        game.set_tag("White", "Skye");
+        for (from, to) in [("f2","f3"),("e7","e5"),("g2","g4"),("d8","h4")]{
+            assert!(game.make_move(mv(from, to)));
+        }
+        assert_eq!(game.to_pgn(), "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n[White \"Skye\"]\n[Black \"?\"]\n[Result \"0-1\"]\n[Termination \"normal\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n");
+    }
+    #[test]
+    fn test_export_from_position(){
+        let mut game=Game::from_board(ChessBoard::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12").unwrap());
+        assert!(game.make_move(mv("e8","d7")));
+        assert!(game.resign(ChessColour::White));
+        let pgn=game.to_pgn();
+        assert!(pgn.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n12... Kd7 0-1\n"));
+    }
+    #[test]
+    fn test_read_database(){
+        let text="[Event \"First\"]
+[White \"A \\\"B\\\" C\"]
+[Result \"1-0\"]
+
+1. e4 {best by test} e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 $1 Nc6 3.Bb5 a6!? 4. 0-0 1-0
+
+[Event \"Second\"]
+[SetUp \"1\"]
+[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]
+[Result \"*\"]
+
+12... Kd7 ; a rest-of-line comment
+*
+
+[Event \"Third\"]
+
+1. e4 Ke6 2. Ke2 *
+";
+        let games=read_pgn(text);
+        assert_eq!(games.len(), 3);
+        let first=games[0].as_ref().unwrap();
+        assert_eq!(first.get_tag("White"), Some("A \"B\" C"));
+        assert_eq!(first.moves.len(), 7);
+        assert_eq!(first.result(), GameResult::WhiteWins);
+        assert_eq!(first.termination(), Some(Termination::Resignation));
+        let second=games[1].as_ref().unwrap();
+        assert_eq!(second.board.to_fen(), "8/3k4/8/8/8/8/4P3/4K3 w - - 1 13");
+        assert!(!second.is_over());
+        assert_eq!(games[2].as_ref().err(), Some(&PgnError::IllegalMove{ply: 1, san: "Ke6".to_string()}));
+    }
+    #[test]
+    fn test_round_trip(){
+        let mut game=Game::new();
+        for (from, to) in [("e2","e4"),("e7","e5"),("g1","f3"),("b8","c6"),("f1","b5")]{
+            assert!(game.make_move(mv(from, to)));
+        }
+        game.set_tag("Event", "Club \"open\"");
+        assert!(game.resign(ChessColour::Black));
+        let read=Game::from_pgn(&game.to_pgn()).unwrap();
+        assert_eq!(read.moves, game.moves);
+        assert_eq!(read.result(), GameResult::WhiteWins);
+        assert_eq!(read.to_pgn(), game.to_pgn());
+    }
+}
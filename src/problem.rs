@@ -0,0 +1,256 @@
+//! Solvers for chess problems: direct mates, helpmates, selfmates and reflexmates.
+
+use super::*;
+
+/// A move in a solution tree, with the replies that follow it. For the attacking
+/// side these are only the moves that keep the stipulation; for the defending side
+/// they are every legal move.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SolutionNode {
+    pub mv: Move,
+    pub children: Vec<SolutionNode>,
+}
+
+/// A key move of a problem with its full solution tree.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Solution {
+    pub key: Move,
+    /// The fewest moves, counting the key, in which the key meets the stipulation.
+    /// Fewer than asked for is a short solution.
+    pub moves: usize,
+    /// The replies to the key, each with all continuations that still meet the
+    /// stipulation in time. These are every defence, except in helpmates, where they
+    /// are only the cooperating moves.
+    pub variations: Vec<SolutionNode>,
+}
+
+impl Solution{
+    /// Lines, from the key on, after which the attacker had more than one way to
+    /// keep the stipulation.
+    pub fn duals(&self)->Vec<Vec<Move>>{
+        let mut out=Vec::new();
+        for variation in self.variations.iter(){
+            get_duals(variation, &mut vec![self.key, variation.mv], &mut out);
+        }
+        out
+    }
+}
+
+// `defence` is a defending move whose children are the attacker's continuations.
+fn get_duals(defence:&SolutionNode, line:&mut Vec<Move>, out:&mut Vec<Vec<Move>>){
+    if defence.children.len()>1{
+        out.push(line.clone());
+    }
+    for continuation in defence.children.iter(){
+        line.push(continuation.mv);
+        for reply in continuation.children.iter(){
+            line.push(reply.mv);
+            get_duals(reply, line, out);
+            line.pop();
+        }
+        line.pop();
+    }
+}
+
+fn get_after(board:ChessBoard, mv:Move)->ChessBoard{
+    let mut after=board;
+    after.make_move(mv);
+    after
+}
+
+fn is_mated(board:ChessBoard)->bool{
+    is_checked(board.current_move, board)&&get_legal_moves(board).is_empty()
+}
+
+// Checking moves first finds most mates sooner.
+fn get_ordered_moves(board:ChessBoard)->Vec<Move>{
+    let mut moves=get_legal_moves(board);
+    moves.sort_by_key(|mv| !get_after(board, *mv).is_in_check());
+    moves
+}
+
+fn get_mating_moves(board:ChessBoard)->Vec<Move>{
+    get_legal_moves(board).into_iter().filter(|mv| is_mated(get_after(board, *mv))).collect()
+}
+
+/// What a problem asks for, with White being the side to move.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stipulation {
+    /// White mates against any defence.
+    Mate,
+    /// Black and White cooperate so that White mates, Black moving first.
+    Helpmate,
+    /// White forces Black to mate White against Black's resistance.
+    Selfmate,
+    /// A selfmate in which either side must mate in one when it can.
+    Reflexmate,
+}
+
+// Moves of the side to move at the start of the problem. In a reflexmate a mate in
+// one for White has to be played, which ends the attempt.
+fn get_first_moves(board:ChessBoard, stipulation:Stipulation)->Vec<Move>{
+    match stipulation{
+        Stipulation::Reflexmate if !get_mating_moves(board).is_empty()=>Vec::new(),
+        Stipulation::Mate=>get_ordered_moves(board),
+        _=>get_legal_moves(board),
+    }
+}
+
+// Moves of the other side; in a reflexmate only the mates, if there are any.
+fn get_second_moves(board:ChessBoard, stipulation:Stipulation)->Vec<Move>{
+    let mates=if stipulation==Stipulation::Reflexmate {get_mating_moves(board)} else {Vec::new()};
+    if mates.is_empty() {get_legal_moves(board)} else {mates}
+}
+
+// The first side, to move, can meet the stipulation within `n` of its moves.
+fn is_solved(board:ChessBoard, stipulation:Stipulation, n:usize)->bool{
+    get_first_moves(board, stipulation).into_iter().any(|mv| is_forced(get_after(board, mv), stipulation, n))
+}
+
+// The second side is to move after a first-side move, which counted as the first
+// of `n`, and the stipulation is met or still within reach.
+fn is_forced(board:ChessBoard, stipulation:Stipulation, n:usize)->bool{
+    let moves=get_second_moves(board, stipulation);
+    let reaches=|mv:Move| {
+        let after=get_after(board, mv);
+        is_mated(after)||(n>1&&is_solved(after, stipulation, n-1))
+    };
+    match stipulation{
+        Stipulation::Mate if moves.is_empty()=>is_checked(board.current_move, board),
+        Stipulation::Mate=>n>1&&moves.into_iter().all(|mv| is_solved(get_after(board, mv), stipulation, n-1)),
+        Stipulation::Helpmate=>moves.into_iter().any(reaches),
+        Stipulation::Selfmate|Stipulation::Reflexmate=>!moves.is_empty()&&moves.into_iter().all(reaches),
+    }
+}
+
+fn get_first_tree(board:ChessBoard, stipulation:Stipulation, n:usize)->Vec<SolutionNode>{
+    get_first_moves(board, stipulation).into_iter()
+        .filter_map(|mv| {
+            let after=get_after(board, mv);
+            if !is_forced(after, stipulation, n){
+                return None;
+            }
+            let children=if is_mated(after) {Vec::new()} else {get_second_tree(after, stipulation, n)};
+            Some(SolutionNode{mv, children})
+        })
+        .collect()
+}
+
+fn get_second_tree(board:ChessBoard, stipulation:Stipulation, n:usize)->Vec<SolutionNode>{
+    get_second_moves(board, stipulation).into_iter()
+        .filter_map(|mv| {
+            let after=get_after(board, mv);
+            if is_mated(after){
+                return Some(SolutionNode{mv, children: Vec::new()});
+            }
+            let solved=n>1&&is_solved(after, stipulation, n-1);
+            // Defences are all listed; a helpmate only keeps the cooperating moves.
+            if !solved&&stipulation==Stipulation::Helpmate{
+                return None;
+            }
+            let children=if solved {get_first_tree(after, stipulation, n-1)} else {Vec::new()};
+            Some(SolutionNode{mv, children})
+        })
+        .collect()
+}
+
+/// Every first move that meets the stipulation in at most `n` moves of the side to
+/// move, with its full solution tree. A sound problem has exactly one solution,
+/// taking all `n` moves; further keys are cooks.
+pub fn solve(board:&ChessBoard, stipulation:Stipulation, n:usize)->Vec<Solution>{
+    let board=*board;
+    let mut out=Vec::new();
+    for key in get_first_moves(board, stipulation){
+        let after=get_after(board, key);
+        let Some(moves)=(1..=n).find(|moves| is_forced(after, stipulation, *moves)) else {continue;};
+        let variations=if is_mated(after) {Vec::new()} else {get_second_tree(after, stipulation, moves)};
+        out.push(Solution{key, moves, variations});
+    }
+    out
+}
+
+/// Every key move with which the side to move forces mate in at most `n` moves.
+pub fn solve_mate(board:&ChessBoard, n:usize)->Vec<Solution>{
+    solve(board, Stipulation::Mate, n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(solutions:&[Solution])->Vec<String>{
+        solutions.iter().map(|solution| solution.key.to_uci()).collect()
+    }
+
+    #[test]
+    fn test_mate_in_one(){
+        let board=ChessBoard::from_fen("k7/8/1K6/8/8/8/7Q/8 w - - 0 1").unwrap();
+        let solutions=solve_mate(&board, 1);
+        assert_eq!(keys(&solutions), ["h2h8"]);
+        assert!(solutions[0].variations.is_empty());
+        // Two rooks give two keys: the problem is cooked.
+        let board=ChessBoard::from_fen("k7/8/1K6/8/8/8/8/6RR w - - 0 1").unwrap();
+        let mut cooked=keys(&solve_mate(&board, 1));
+        cooked.sort();
+        assert_eq!(cooked, ["g1g8", "h1h8"]);
+        assert!(solve_mate(&ChessBoard::from_fen("k7/8/8/8/8/8/8/K7 w - - 0 1").unwrap(), 2).is_empty());
+    }
+    #[test]
+    fn test_mate_in_two(){
+        // The king takes the opposition, then the rook mates on the back rank.
+        let board=ChessBoard::from_fen("7k/8/5K2/8/8/8/8/R7 w - - 0 1").unwrap();
+        assert!(solve_mate(&board, 1).is_empty());
+        let solutions=solve_mate(&board, 2);
+        assert!(keys(&solutions).contains(&"f6g6".to_string()));
+        for solution in solutions.iter(){
+            assert_eq!(solution.moves, 2);
+            for defence in solution.variations.iter(){
+                assert!(!defence.children.is_empty());
+                for mate in defence.children.iter(){
+                    assert!(mate.children.is_empty());
+                    let after=get_after(get_after(get_after(board, solution.key), defence.mv), mate.mv);
+                    assert!(is_mated(after));
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_short_solutions_and_duals(){
+        let board=ChessBoard::from_fen("k7/8/1K6/8/8/8/7Q/8 w - - 0 1").unwrap();
+        let solutions=solve_mate(&board, 2);
+        let short:Vec<&Solution>=solutions.iter().filter(|solution| solution.moves<2).collect();
+        assert_eq!(short.len(), 1);
+        assert_eq!(short[0].key.to_uci(), "h2h8");
+        // 1. Qh7 Kb8 and both Qb7# and Qh8# mate.
+        let quiet=solutions.iter().find(|solution| solution.key.to_uci()=="h2h7").unwrap();
+        assert_eq!(quiet.moves, 2);
+        let duals:Vec<Vec<String>>=quiet.duals().iter().map(|line| line.iter().map(|mv| mv.to_uci()).collect()).collect();
+        assert_eq!(duals, [["h2h7", "a8b8"]]);
+    }
+    #[test]
+    fn test_helpmate(){
+        let board=ChessBoard::from_fen("7k/8/6K1/8/8/8/8/R7 b - - 0 1").unwrap();
+        let solutions=solve(&board, Stipulation::Helpmate, 1);
+        assert_eq!(keys(&solutions), ["h8g8"]);
+        let mate=&solutions[0].variations;
+        assert_eq!((mate.len(), mate[0].mv.to_uci(), mate[0].children.len()), (1, "a1a8".to_string(), 0));
+        assert!(solve(&board, Stipulation::Mate, 1).is_empty());
+    }
+    #[test]
+    fn test_selfmate_and_reflexmate(){
+        // Every king move uncovers the bishop's mate, once White has passed.
+        let board=ChessBoard::from_fen("b7/1k6/8/1P6/8/8/P6P/6BK w - - 0 1").unwrap();
+        for stipulation in [Stipulation::Selfmate, Stipulation::Reflexmate]{
+            let solutions=solve(&board, stipulation, 1);
+            assert_eq!(keys(&solutions), ["a2a3", "a2a4"]);
+            assert_eq!(solutions[0].variations.len(), 3);
+            assert!(solutions[0].variations.iter().all(|mate| is_mated(get_after(get_after(board, solutions[0].key), mate.mv))));
+        }
+        // Black would rather not play Ra1#, but in a reflexmate has to.
+        let board=ChessBoard::from_fen("r3k3/8/8/8/8/8/2P3PP/7K w - - 0 1").unwrap();
+        assert!(solve(&board, Stipulation::Selfmate, 1).is_empty());
+        let solutions=solve(&board, Stipulation::Reflexmate, 1);
+        assert_eq!(keys(&solutions), ["c2c3", "c2c4"]);
+        assert_eq!(solutions[0].variations.iter().map(|mate| mate.mv.to_uci()).collect::<Vec<_>>(), ["a8a1"]);
+    }
+}
@@ -0,0 +1,202 @@
+use super::*;
+use crate::status::GameStatus;
+
+fn get_kind_letter(kind:ChessPieceKind)->&'static str{
+    match kind{
+        ChessPieceKind::Pawn=>"",
+        ChessPieceKind::Knight=>"N",
+        ChessPieceKind::Bishop=>"B",
+        ChessPieceKind::Rook=>"R",
+        ChessPieceKind::Queen=>"Q",
+        ChessPieceKind::King=>"K",
+    }
+}
+
+impl ChessBoard{
+    /// Standard algebraic notation for a legal move, e.g. `Nbd2`, `exd6`, `O-O` or `e8=Q#`.
+    pub fn to_san(&self, mv:Move)->String{
+        let board=*self;
+        let Some(piece)=get_piece_bit_mask(mv.from, board) else {
+            return String::new();
+        };
+        let mut out=String::new();
+        let castling=if piece.kind==ChessPieceKind::King {get_castling(piece, mv.to, board)} else {None};
+        if let Some((_, king_side))=castling{
+            out.push_str(if king_side {"O-O"} else {"O-O-O"});
+        }
+        else{
+            let capture=(mv.to&get_piece_map(get_op_col(piece.colour), board))>0
+                ||(piece.kind==ChessPieceKind::Pawn&&get_file_u64(mv.from)!=get_file_u64(mv.to));
+            let from_name=get_square_name(mv.from);
+            if piece.kind==ChessPieceKind::Pawn{
+                if capture{
+                    out.push_str(&from_name[..1]);
+                }
+            }
+            else{
+                out.push_str(get_kind_letter(piece.kind));
+                let rivals:Vec<Move>=get_legal_moves(board).into_iter().filter(|other| {
+                    other.to==mv.to&&other.from!=mv.from&&get_piece_bit_mask(other.from, board).is_some_and(|p| p.kind==piece.kind)
+                }).collect();
+                if !rivals.is_empty(){
+                    let same_file=rivals.iter().any(|other| get_file_u64(other.from)==get_file_u64(mv.from));
+                    let same_rank=rivals.iter().any(|other| other.from.trailing_zeros()/8==mv.from.trailing_zeros()/8);
+                    if !same_file{
+                        out.push_str(&from_name[..1]);
+                    }
+                    else if !same_rank{
+                        out.push_str(&from_name[1..]);
+                    }
+                    else{
+                        out.push_str(&from_name);
+                    }
+                }
+            }
+            if capture{
+                out.push('x');
+            }
+            out.push_str(&get_square_name(mv.to));
+            if let Some(kind)=mv.promotion{
+                out.push('=');
+                out.push_str(get_kind_letter(kind));
+            }
+        }
+        let mut after=board;
+        if after.make_move(mv){
+            if let GameStatus::Checkmate(_)=after.status(){
+                out.push('#');
+            }
+            else if after.is_in_check(){
+                out.push('+');
+            }
+        }
+        out
+    }
+}
+
+fn get_kind_from_letter(c:char)->Option<ChessPieceKind>{
+    match c.to_ascii_uppercase(){
+        'N'=>Some(ChessPieceKind::Knight),
+        'B'=>Some(ChessPieceKind::Bishop),
+        'R'=>Some(ChessPieceKind::Rook),
+        'Q'=>Some(ChessPieceKind::Queen),
+        'K'=>Some(ChessPieceKind::King),
+        _=>None,
+    }
+}
+
+impl ChessBoard{
+    /// Finds the legal move written in standard algebraic notation. Check marks,
+    /// annotations and `0-0` style castling are accepted.
+    pub fn parse_san(&self, san:&str)->Option<Move>{
+        let board=*self;
+        let san=san.trim_end_matches(['+', '#', '!', '?']);
+        let legal=get_legal_moves(board);
+        if let Some(king_side)=match san{
+            "O-O"|"0-0"=>Some(true),
+            "O-O-O"|"0-0-0"=>Some(false),
+            _=>None,
+        }{
+            return legal.into_iter().find(|mv| {
+                let piece=get_piece_bit_mask(mv.from, board).unwrap();
+                piece.kind==ChessPieceKind::King&&get_castling(piece, mv.to, board).is_some_and(|(_, side)| side==king_side)
+            });
+        }
+
+        let (body, promotion)=match san.split_once('='){
+            Some((body, promo))=>(body, Some(get_kind_from_letter(promo.chars().next()?)?)),
+            None=>match san.chars().last(){
+                Some(c) if san.len()>2&&"NBRQ".contains(c)=>(&san[..san.len()-1], get_kind_from_letter(c)),
+                _=>(san, None),
+            },
+        };
+        let mut chars:Vec<char>=body.chars().filter(|c| *c!='x'&&*c!='-').collect();
+        let kind=match chars.first(){
+            Some(c) if c.is_ascii_uppercase()=>{
+                let kind=get_kind_from_letter(*c)?;
+                chars.remove(0);
+                kind
+            }
+            _=>ChessPieceKind::Pawn,
+        };
+        if chars.len()<2{return None;}
+        let to_name:String=chars[chars.len()-2..].iter().collect();
+        let to=get_square_from_name(&to_name)?;
+        let hint=&chars[..chars.len()-2];
+        let mut found:Option<Move>=None;
+        for mv in legal{
+            if mv.to!=to||mv.promotion!=promotion{continue;}
+            let piece=get_piece_bit_mask(mv.from, board)?;
+            if piece.kind!=kind{continue;}
+            if piece.kind==ChessPieceKind::King&&get_castling(piece, mv.to, board).is_some(){continue;}
+            let from_name=get_square_name(mv.from);
+            if !hint.iter().all(|c| from_name.contains(*c)){continue;}
+            if found.is_some(){return None;}
+            found=Some(mv);
+        }
+        found
+    }
+
+    /// Finds the legal move written in UCI notation. Standard castling may be given
+    /// as the king's two-square move or as king-takes-rook.
+    pub fn parse_uci(&self, uci:&str)->Option<Move>{
+        if !(4..=5).contains(&uci.len())||!uci.is_ascii(){return None;}
+        let from=get_square_from_name(&uci[0..2])?;
+        let to=get_square_from_name(&uci[2..4])?;
+        let promotion=match uci.chars().nth(4){
+            Some(c)=>Some(get_kind_from_letter(c).filter(|kind| *kind!=ChessPieceKind::King)?),
+            None=>None,
+        };
+        let legal=get_legal_moves(*self);
+        let mv=Move{from, to, promotion};
+        if legal.contains(&mv){
+            return Some(mv);
+        }
+        // King-takes-rook castling on a standard board maps onto the two-square king move.
+        let piece=get_piece_bit_mask(from, *self)?;
+        if piece.kind!=ChessPieceKind::King||promotion.is_some(){return None;}
+        let (_, king_side)=get_castling(piece, to, *self)?;
+        let (king_to, _)=get_castling_targets(piece.colour, king_side);
+        legal.into_iter().find(|mv| mv.from==from&&mv.to==king_to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from:&str, to:&str)->Move{
+        new_move(get_square_from_name(from).unwrap(), get_square_from_name(to).unwrap())
+    }
+
+    #[test]
+    fn test_san(){
+        let board=ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.to_san(mv("e1","g1")), "O-O");
+        assert_eq!(board.to_san(mv("e1","c1")), "O-O-O");
+        assert_eq!(board.to_san(mv("d5","e6")), "dxe6");
+        assert_eq!(board.to_san(mv("e5","f7")), "Nxf7");
+        assert_eq!(board.to_san(mv("c3","b1")), "Nb1");
+        assert_eq!(board.to_san(mv("e2","d3")), "Bd3");
+        let board=ChessBoard::from_fen("7k/P7/6K1/8/8/8/8/R3R3 w - - 0 1").unwrap();
+        assert_eq!(board.to_san(mv("a1","d1")), "Rad1");
+        assert_eq!(board.to_san(Move{from: get_square_from_name("a7").unwrap(), to: get_square_from_name("a8").unwrap(), promotion: Some(ChessPieceKind::Queen)}), "a8=Q#");
+        assert_eq!(board.to_san(mv("e1","e8")), "Re8#");
+    }
+    #[test]
+    fn test_parse(){
+        let board=ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for mv in get_legal_moves(board){
+            assert_eq!(board.parse_san(&board.to_san(mv)), Some(mv));
+            assert_eq!(board.parse_uci(&mv.to_uci()), Some(mv));
+        }
+        assert_eq!(board.parse_san("0-0"), Some(mv("e1","g1")));
+        assert_eq!(board.parse_uci("e1h1"), Some(mv("e1","g1")));
+        assert_eq!(board.parse_san("Nd2"), None);
+        let board=ChessBoard::from_fen("7k/P7/6K1/8/8/8/8/R3R3 w - - 0 1").unwrap();
+        assert_eq!(board.parse_san("R1d1"), None);
+        assert_eq!(board.parse_san("Rad1"), Some(mv("a1","d1")));
+        assert_eq!(board.parse_san("a8N").and_then(|mv| mv.promotion), Some(ChessPieceKind::Knight));
+        assert_eq!(board.parse_uci("a7a8q").and_then(|mv| mv.promotion), Some(ChessPieceKind::Queen));
+    }
+}
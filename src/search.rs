@@ -0,0 +1,433 @@
+use super::*;
+use crate::see::get_piece_value;
+use crate::transposition::{Bound, TranspositionTable, TtEntry};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub const MATE_SCORE:i32=30000;
+const INFINITY:i32=32000;
+const MAX_DEPTH:u8=64;
+// Depth searched when no limit at all is given.
+const DEFAULT_DEPTH:u8=4;
+// Size of the table a search makes for itself when not given one.
+const DEFAULT_TABLE_MEGABYTES:usize=4;
+// Nodes a thread counts before adding them to the shared total.
+const NODE_BATCH:u64=256;
+
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub movetime: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Centipawns from the side to move's point of view, or `MATE_SCORE` minus the
+    /// number of plies to mate.
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+/// One line of a multi-PV search.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PvLine {
+    /// In the same convention as `SearchResult::score`.
+    pub score: i32,
+    pub depth: u8,
+    pub pv: Vec<Move>,
+}
+
+impl PvLine{
+    pub fn best_move(&self)->Option<Move>{
+        self.pv.first().copied()
+    }
+
+    pub fn mate_in(&self)->Option<i32>{
+        get_mate_in(self.score)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SearchOptions {
+    /// How many of the best moves to find lines for.
+    pub multipv: usize,
+    /// Threads searching at once. With one the search is deterministic.
+    pub threads: usize,
+}
+
+impl Default for SearchOptions{
+    fn default()->SearchOptions{
+        SearchOptions{multipv: 1, threads: 1}
+    }
+}
+
+impl SearchResult{
+    /// Moves until mate, negative when the side to move is getting mated.
+    pub fn mate_in(&self)->Option<i32>{
+        get_mate_in(self.score)
+    }
+}
+
+pub fn get_mate_in(score:i32)->Option<i32>{
+    if score.abs()<MATE_SCORE-i32::from(MAX_DEPTH)*2{
+        return None;
+    }
+    let plies=MATE_SCORE-score.abs();
+    let moves=(plies+1)/2;
+    Some(if score>0 {moves} else {-moves})
+}
+
+// Small bonus for standing towards the centre, by distance from the edge (0-3).
+const CENTRE_BONUS:[i32;4]=[0, 4, 8, 12];
+
+fn get_square_bonus(piece:ChessPiece)->i32{
+    let square=piece.pos.trailing_zeros() as i32;
+    let file=square%8;
+    let rank=square/8;
+    let centre=CENTRE_BONUS[file.min(7-file) as usize]+CENTRE_BONUS[rank.min(7-rank) as usize];
+    match piece.kind{
+        ChessPieceKind::Pawn=>{
+            let advance=if piece.colour==ChessColour::White {rank-1} else {6-rank};
+            advance*advance*3
+        }
+        ChessPieceKind::Knight|ChessPieceKind::Bishop=>centre,
+        ChessPieceKind::Queen=>centre/2,
+        ChessPieceKind::Rook|ChessPieceKind::King=>0,
+    }
+}
+
+/// Static evaluation in centipawns from the side to move's point of view.
+pub fn evaluate(board:ChessBoard)->i32{
+    let mut score=0;
+    for piece in board.pieces{
+        if piece.is_captured{continue;}
+        let value=get_piece_value(piece.kind)+get_square_bonus(piece);
+        score+=if piece.colour==board.current_move {value} else {-value};
+    }
+    score
+}
+
+// Mate scores are stored relative to the node rather than the root, so they stay
+// right when the position is reached at another ply.
+fn get_tt_score(score:i32, ply:i32)->i32{
+    match get_mate_in(score){
+        Some(moves) if moves>0=>score+ply,
+        Some(_)=>score-ply,
+        None=>score,
+    }
+}
+
+fn get_score_from_tt(score:i32, ply:i32)->i32{
+    match get_mate_in(score){
+        Some(moves) if moves>0=>score-ply,
+        Some(_)=>score+ply,
+        None=>score,
+    }
+}
+
+struct Searcher<'a> {
+    start: Instant,
+    limits: SearchLimits,
+    nodes: u64,
+    /// Nodes of every thread, this one's added in batches.
+    total_nodes: &'a AtomicU64,
+    /// Nodes of this thread already added to `total_nodes`.
+    counted: u64,
+    stopped: bool,
+    /// Set from outside to end the search early.
+    stop: &'a AtomicBool,
+    table: &'a TranspositionTable,
+    /// Best move of the previous iteration, searched first at the root.
+    root_move: Option<Move>,
+    /// Root moves already taken by better lines of a multi-PV search.
+    excluded: Vec<Move>,
+}
+
+impl Searcher<'_>{
+    fn should_stop(&mut self)->bool{
+        if self.stopped{return true;}
+        if self.stop.load(Ordering::Relaxed){
+            self.stopped=true;
+        }
+        if self.nodes-self.counted>=NODE_BATCH{
+            self.count_nodes();
+        }
+        let searched=self.total_nodes.load(Ordering::Relaxed)+self.nodes-self.counted;
+        if self.limits.nodes.is_some_and(|nodes| searched>=nodes){
+            self.stopped=true;
+        }
+        if self.nodes.is_multiple_of(256)&&self.limits.movetime.is_some_and(|time| self.start.elapsed()>=time){
+            self.stopped=true;
+        }
+        self.stopped
+    }
+
+    fn count_nodes(&mut self){
+        self.total_nodes.fetch_add(self.nodes-self.counted, Ordering::Relaxed);
+        self.counted=self.nodes;
+    }
+
+    fn quiescence(&mut self, board:ChessBoard, mut alpha:i32, beta:i32)->i32{
+        self.nodes+=1;
+        let stand_pat=evaluate(board);
+        if stand_pat>=beta{return stand_pat;}
+        alpha=alpha.max(stand_pat);
+        let enemies=get_piece_map(get_op_col(board.current_move), board);
+        let mut captures:Vec<(i32, Move)>=get_legal_moves(board).into_iter()
+            .filter(|mv| (mv.to&enemies)>0||mv.promotion==Some(ChessPieceKind::Queen))
+            .map(|mv| (board.see(mv), mv))
+            .filter(|(see, _)| *see>=0)
+            .collect();
+        captures.sort_by_key(|(see, _)| -see);
+        for (_, mv) in captures{
+            if self.should_stop(){break;}
+            let mut after=board;
+            after.make_move(mv);
+            let score=-self.quiescence(after, -beta, -alpha);
+            if score>=beta{return score;}
+            alpha=alpha.max(score);
+        }
+        alpha
+    }
+
+    fn negamax(&mut self, board:ChessBoard, depth:u8, ply:i32, mut alpha:i32, beta:i32, pv:&mut Vec<Move>)->i32{
+        pv.clear();
+        if ply>0&&(board.rule_50_moves>=100||board.repetition_count()>=2){
+            return 0;
+        }
+        if depth==0{
+            return self.quiescence(board, alpha, beta);
+        }
+        self.nodes+=1;
+        let key=board.polyglot_key();
+        let entry=self.table.probe(key);
+        // The root always searches, so that it has a full pv.
+        if let Some(entry)=entry.filter(|entry| ply>0&&entry.depth>=depth){
+            let score=get_score_from_tt(entry.score, ply);
+            let cutoff=match entry.bound{
+                Bound::Exact=>true,
+                Bound::Lower=>score>=beta,
+                Bound::Upper=>score<=alpha,
+            };
+            if cutoff{return score;}
+        }
+        let mut moves=get_legal_moves(board);
+        if ply==0{
+            moves.retain(|mv| !self.excluded.contains(mv));
+        }
+        let table_move=entry.and_then(|entry| entry.best_move);
+        let moves=order_moves(board, moves, if ply==0 {self.root_move.or(table_move)} else {table_move});
+        if moves.is_empty(){
+            return if is_checked(board.current_move, board) {-(MATE_SCORE-ply)} else {0};
+        }
+        let start_alpha=alpha;
+        let mut best=-INFINITY;
+        let mut best_move=None;
+        let mut child_pv=Vec::new();
+        for mv in moves{
+            if self.should_stop(){break;}
+            let mut after=board;
+            after.make_move(mv);
+            let score=-self.negamax(after, depth-1, ply+1, -beta, -alpha, &mut child_pv);
+            if score>best{
+                best=score;
+                best_move=Some(mv);
+                if score>alpha{
+                    alpha=score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.extend_from_slice(&child_pv);
+                }
+            }
+            if alpha>=beta{break;}
+        }
+        // A root missing the moves of better lines says nothing about the position.
+        if !self.stopped&&(ply>0||self.excluded.is_empty()){
+            let bound=if best>=beta {Bound::Lower} else if best>start_alpha {Bound::Exact} else {Bound::Upper};
+            self.table.store(key, TtEntry{best_move, score: get_tt_score(best, ply), depth, bound});
+        }
+        best
+    }
+
+    // Iterative deepening from `first_depth`, finding `count` lines at each depth.
+    fn iterate(&mut self, board:ChessBoard, first_depth:u8, max_depth:u8, count:usize)->Vec<PvLine>{
+        let mut lines:Vec<PvLine>=Vec::new();
+        let mut pv=Vec::new();
+        for depth in first_depth.min(max_depth)..=max_depth{
+            let mut found:Vec<PvLine>=Vec::new();
+            self.excluded.clear();
+            for idx in 0..count{
+                self.root_move=lines.get(idx).and_then(|line| line.best_move());
+                let score=self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+                // A line cut short is only kept at the first depth, which has nothing better.
+                if self.stopped&&(depth>first_depth||pv.is_empty()){break;}
+                self.excluded.push(pv[0]);
+                found.push(PvLine{score, depth, pv: pv.clone()});
+            }
+            found.sort_by_key(|line| -line.score);
+            // The moves a depth cut short did not get to keep their line from the depth before.
+            for line in lines.iter(){
+                if found.len()>=count{break;}
+                if found.iter().all(|other| other.best_move()!=line.best_move()){
+                    found.push(line.clone());
+                }
+            }
+            lines=found;
+            if self.stopped||lines.iter().all(|line| line.mate_in().is_some()){break;}
+        }
+        self.count_nodes();
+        lines
+    }
+}
+
+// Previous best move first, then captures by most valuable victim and least valuable attacker.
+fn order_moves(board:ChessBoard, moves:Vec<Move>, pv_move:Option<Move>)->Vec<Move>{
+    let mut keyed:Vec<(i32, Move)>=moves.into_iter().map(|mv| {
+        let mut key=0;
+        if Some(mv)==pv_move{
+            key=INFINITY;
+        }
+        else if let Some(victim)=get_piece_bit_mask(mv.to, board).filter(|p| p.colour!=board.current_move){
+            let attacker=get_piece_bit_mask(mv.from, board).unwrap();
+            key=get_piece_value(victim.kind)*10-get_piece_value(attacker.kind)/10;
+        }
+        if let Some(kind)=mv.promotion{
+            key+=get_piece_value(kind);
+        }
+        (key, mv)
+    }).collect();
+    keyed.sort_by_key(|(key, _)| -key);
+    keyed.into_iter().map(|(_, mv)| mv).collect()
+}
+
+/// Iterative deepening alpha-beta search. Stops at whichever limit comes first and
+/// returns the result of the last fully searched depth.
+pub fn search(board:ChessBoard, limits:SearchLimits)->SearchResult{
+    search_until(board, limits, &AtomicBool::new(false))
+}
+
+/// Like `search`, but also stops once `stop` is set, e.g. by another thread.
+pub fn search_until(board:ChessBoard, limits:SearchLimits, stop:&AtomicBool)->SearchResult{
+    let table=TranspositionTable::new(DEFAULT_TABLE_MEGABYTES);
+    let (mut lines, nodes)=run_search(board, limits, &SearchOptions::default(), &table, stop);
+    let line=lines.remove(0);
+    let best_move=line.best_move().or_else(|| get_legal_moves(board).first().copied());
+    SearchResult{best_move, score: line.score, depth: line.depth, nodes, pv: line.pv}
+}
+
+/// Searches for the best `options.multipv` moves, each with its own line, best
+/// first. A position without legal moves has a single line with no moves.
+pub fn search_lines(board:ChessBoard, limits:SearchLimits, options:&SearchOptions, stop:&AtomicBool)->Vec<PvLine>{
+    run_search(board, limits, options, &TranspositionTable::new(DEFAULT_TABLE_MEGABYTES), stop).0
+}
+
+/// The lines of `search_lines` using `table`, which may be kept between searches,
+/// and the number of nodes all threads searched for them.
+///
+/// With more than one thread the others are helpers (Lazy SMP): they search the
+/// same position and share what they find through the table, while the lines come
+/// from the main thread alone.
+pub fn run_search(board:ChessBoard, limits:SearchLimits, options:&SearchOptions, table:&TranspositionTable, stop:&AtomicBool)->(Vec<PvLine>, u64){
+    let max_depth=match limits{
+        SearchLimits{depth: Some(depth), ..}=>depth.min(MAX_DEPTH),
+        SearchLimits{movetime: None, nodes: None, ..}=>DEFAULT_DEPTH,
+        _=>MAX_DEPTH,
+    }.max(1);
+    let legal=get_legal_moves(board).len();
+    if legal==0{
+        let score=if is_checked(board.current_move, board) {-MATE_SCORE} else {0};
+        return (vec![PvLine{score, depth: 1, pv: Vec::new()}], 0);
+    }
+    let count=options.multipv.clamp(1, legal);
+    let start=Instant::now();
+    let total_nodes=AtomicU64::new(0);
+    // Helpers stop with the main thread.
+    let helpers_stop=AtomicBool::new(false);
+    let new_searcher=|stop| Searcher{start, limits, nodes: 0, total_nodes: &total_nodes, counted: 0, stopped: false, stop, table, root_move: None, excluded: Vec::new()};
+    let mut lines=std::thread::scope(|scope| {
+        for idx in 1..options.threads.max(1){
+            let mut helper=new_searcher(&helpers_stop);
+            // Half the helpers start a depth ahead, so the threads do not all search alike.
+            scope.spawn(move || helper.iterate(board, 1+(idx%2) as u8, max_depth, count));
+        }
+        let lines=new_searcher(stop).iterate(board, 1, max_depth, count);
+        helpers_stop.store(true, Ordering::Relaxed);
+        lines
+    });
+    // Stopped before the first depth finished: reported as depth 1, as it always
+    // has been, and `search` falls back to the first legal move.
+    if lines.is_empty(){
+        lines.push(PvLine{score: 0, depth: 1, pv: Vec::new()});
+    }
+    (lines, total_nodes.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_mate_in_one(){
+        let board=ChessBoard::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result=search(board, SearchLimits{depth: Some(2), ..Default::default()});
+        assert_eq!(result.best_move.map(|mv| mv.to_uci()), Some("a1a8".to_string()));
+        assert_eq!(result.mate_in(), Some(1));
+    }
+    #[test]
+    fn test_wins_hanging_queen(){
+        let board=ChessBoard::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result=search(board, SearchLimits{depth: Some(1), ..Default::default()});
+        assert_eq!(result.best_move.map(|mv| mv.to_uci()), Some("d2d5".to_string()));
+        assert!(result.score>400);
+    }
+    #[test]
+    fn test_stop_flag(){
+        let stop=AtomicBool::new(true);
+        let result=search_until(new_board(), SearchLimits{depth: Some(MAX_DEPTH), ..Default::default()}, &stop);
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move.is_some());
+    }
+    #[test]
+    fn test_multipv(){
+        let board=ChessBoard::from_fen("7k/8/6K1/8/8/8/8/RR6 w - - 0 1").unwrap();
+        let options=SearchOptions{multipv: 3, ..Default::default()};
+        let lines=search_lines(board, SearchLimits{depth: Some(2), ..Default::default()}, &options, &AtomicBool::new(false));
+        assert_eq!(lines.len(), 3);
+        let mut mates:Vec<String>=lines[..2].iter().map(|line| line.best_move().unwrap().to_uci()).collect();
+        mates.sort();
+        assert_eq!(mates, ["a1a8", "b1b8"]);
+        assert!(lines[..2].iter().all(|line| line.mate_in()==Some(1)));
+        assert!(lines[2].mate_in().is_none());
+        assert!(lines.windows(2).all(|pair| pair[0].score>=pair[1].score));
+
+        let lines=search_lines(ChessBoard::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap(), SearchLimits::default(), &options, &AtomicBool::new(false));
+        assert_eq!(lines, [PvLine{score: -MATE_SCORE, depth: 1, pv: Vec::new()}]);
+
+        // Stopped during depth 3 after its first line: the others stay at depth 2.
+        let board=ChessBoard::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 2 3").unwrap();
+        let lines=search_lines(board, SearchLimits{nodes: Some(3000), ..Default::default()}, &options, &AtomicBool::new(false));
+        let found:Vec<(u8, String)>=lines.iter().map(|line| (line.depth, line.best_move().unwrap().to_uci())).collect();
+        assert_eq!(found, [(3, "f3f7".to_string()), (2, "c4f7".to_string()), (2, "a2a4".to_string())]);
+    }
+    #[test]
+    fn test_threads(){
+        let board=ChessBoard::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 2 3").unwrap();
+        let limits=SearchLimits{depth: Some(3), ..Default::default()};
+        let first=search(board, limits);
+        assert_eq!(search(board, limits), first);
+        assert_eq!(first.best_move.map(|mv| mv.to_uci()), Some("f3f7".to_string()));
+
+        let table=TranspositionTable::new(1);
+        let stop=AtomicBool::new(false);
+        let (lines, nodes)=run_search(board, limits, &SearchOptions{threads: 4, ..Default::default()}, &table, &stop);
+        assert_eq!(lines[0].best_move(), first.best_move);
+        assert_eq!(lines[0].mate_in(), Some(1));
+        assert!(nodes>0);
+        let limited=run_search(new_board(), SearchLimits{nodes: Some(1000), ..Default::default()}, &SearchOptions{threads: 4, ..Default::default()}, &table, &stop);
+        assert!(limited.1>=1000);
+    }
+}
@@ -0,0 +1,136 @@
+use super::*;
+
+const CAPTURE_ORDER:[ChessPieceKind;6]=[
+    ChessPieceKind::Pawn,
+    ChessPieceKind::Knight,
+    ChessPieceKind::Bishop,
+    ChessPieceKind::Rook,
+    ChessPieceKind::Queen,
+    ChessPieceKind::King,
+];
+
+pub fn get_piece_value(kind:ChessPieceKind)->i32{
+    match kind{
+        ChessPieceKind::Pawn=>100,
+        ChessPieceKind::Knight=>320,
+        ChessPieceKind::Bishop=>330,
+        ChessPieceKind::Rook=>500,
+        ChessPieceKind::Queen=>900,
+        ChessPieceKind::King=>20000,
+    }
+}
+
+fn get_least_valuable_attacker(attackers:u64, col:ChessColour, board:ChessBoard)->Option<ChessPiece>{
+    for kind in CAPTURE_ORDER{
+        for piece in board.pieces{
+            if piece.is_captured||piece.colour!=col||piece.kind!=kind{continue;}
+            if (piece.pos&attackers)>0{
+                return Some(piece);
+            }
+        }
+    }
+    None
+}
+
+impl ChessBoard{
+    /// Static exchange evaluation of `mv` in centipawns for the side making it.
+    ///
+    /// Both sides keep recapturing on `mv.to` with their least valuable attacker,
+    /// sliders lined up behind a piece join in once it has left the line, and either
+    /// side may stop whenever continuing would lose material. Pins are ignored.
+    pub fn see(&self, mv:Move)->i32{
+        let board=*self;
+        let Some(piece)=get_piece_bit_mask(mv.from, board) else {
+            return 0;
+        };
+        let mut occupied=get_all_piece_map(board)&!mv.from;
+        let mut gain:[i32;64]=[0;64];
+        gain[0]=match get_piece_bit_mask(mv.to, board){
+            Some(target)=>get_piece_value(target.kind),
+            None if piece.kind==ChessPieceKind::Pawn&&get_file_u64(mv.from)!=get_file_u64(mv.to)=>{
+                let ep_pawn=if piece.colour==ChessColour::White {mv.to>>8} else {mv.to<<8};
+                occupied&=!ep_pawn;
+                get_piece_value(ChessPieceKind::Pawn)
+            }
+            None=>0,
+        };
+        let mut on_square=get_piece_value(piece.kind);
+        if let Some(kind)=mv.promotion{
+            gain[0]+=get_piece_value(kind)-get_piece_value(ChessPieceKind::Pawn);
+            on_square=get_piece_value(kind);
+        }
+
+        let mut side=get_op_col(piece.colour);
+        let mut depth=0;
+        loop{
+            let attackers=get_attackers(mv.to, occupied, board);
+            let Some(attacker)=get_least_valuable_attacker(attackers, side, board) else {
+                break;
+            };
+            if attacker.kind==ChessPieceKind::King{
+                let defenders=get_attackers(mv.to, occupied&!attacker.pos, board)&get_piece_map(get_op_col(side), board);
+                if defenders>0{
+                    break;
+                }
+            }
+            depth+=1;
+            gain[depth]=on_square-gain[depth-1];
+            on_square=get_piece_value(attacker.kind);
+            occupied&=!attacker.pos;
+            side=get_op_col(side);
+        }
+        while depth>0{
+            gain[depth-1]=gain[depth-1].min(-gain[depth]);
+            depth-=1;
+        }
+        gain[0]
+    }
+
+    /// Whether the exchange started by `mv` nets at least `threshold` centipawns.
+    pub fn see_ge(&self, mv:Move, threshold:i32)->bool{
+        self.see(mv)>=threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChessColour::{Black, White};
+    use ChessPieceKind::*;
+
+    // White to move, kings on the given (rank, file) squares.
+    fn board(kings:[(u8, u8);2], pieces:&[(ChessColour, ChessPieceKind, u8, u8)])->ChessBoard{
+        let [(white_rank, white_file), (black_rank, black_file)]=kings;
+        let mut template=vec![(White,King,white_rank,white_file),(Black,King,black_rank,black_file)];
+        template.extend_from_slice(pieces);
+        // One king a side and one piece a square.
+        assert!(pieces.iter().all(|(_, kind, _, _)| *kind!=King));
+        let occupied=template.iter().fold(0, |occupied, (_, _, rank, file)| occupied|get_u64_pos(*rank, *file));
+        assert_eq!(occupied.count_ones() as usize, template.len());
+        board_from_template(&template, White)
+    }
+
+    #[test]
+    fn test_undefended_capture(){
+        let b=board([(1,1),(8,1)], &[(White,Pawn,4,4),(Black,Knight,5,5)]);
+        assert_eq!(b.see(new_move(get_u64_pos(4,4),get_u64_pos(5,5))),320);
+    }
+    #[test]
+    fn test_defended_capture(){
+        let b=board([(1,1),(8,1)], &[(White,Rook,1,5),(Black,Pawn,5,5),(Black,Pawn,6,4)]);
+        assert_eq!(b.see(new_move(get_u64_pos(1,5),get_u64_pos(5,5))),-400);
+        assert!(!b.see_ge(new_move(get_u64_pos(1,5),get_u64_pos(5,5)),0));
+    }
+    #[test]
+    fn test_xray_recapture(){
+        let b=board([(1,1),(8,1)], &[(White,Rook,1,5),(White,Rook,2,5),(Black,Pawn,5,5),(Black,Rook,8,5)]);
+        assert_eq!(b.see(new_move(get_u64_pos(2,5),get_u64_pos(5,5))),100);
+        let b=board([(1,8),(8,1)], &[(White,Bishop,2,2),(White,Queen,1,1),(Black,Knight,6,6),(Black,Pawn,7,7)]);
+        assert_eq!(b.see(new_move(get_u64_pos(2,2),get_u64_pos(6,6))),90);
+    }
+    #[test]
+    fn test_king_cannot_recapture_defended(){
+        let b=board([(1,1),(6,5)], &[(White,Knight,3,3),(White,Bishop,2,7),(Black,Pawn,5,4)]);
+        assert_eq!(b.see(new_move(get_u64_pos(3,3),get_u64_pos(5,4))),100);
+    }
+}
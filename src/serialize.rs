@@ -0,0 +1,192 @@
+//! `serde` support, behind the `serde` feature. For readable formats such as JSON,
+//! boards are written as FEN and moves in UCI notation. Binary formats get the
+//! 32-byte packed position of `encoding::encode_position` and a move in a `u16`.
+
+use super::*;
+use crate::clock::Clock;
+use crate::encoding::{decode_position, encode_position, POSITION_SIZE};
+use crate::game::{DrawOffer, Game, GameResult, Termination};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+impl Serialize for ChessBoard{
+    fn serialize<S:Serializer>(&self, serializer:S)->Result<S::Ok, S::Error>{
+        if serializer.is_human_readable(){
+            return serializer.serialize_str(&self.to_fen());
+        }
+        encode_position(self).map_err(serde::ser::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessBoard{
+    fn deserialize<D:Deserializer<'de>>(deserializer:D)->Result<ChessBoard, D::Error>{
+        if !deserializer.is_human_readable(){
+            let bytes=<[u8;POSITION_SIZE]>::deserialize(deserializer)?;
+            return decode_position(&bytes).map_err(D::Error::custom);
+        }
+        let fen=String::deserialize(deserializer)?;
+        ChessBoard::from_fen(&fen).map_err(D::Error::custom)
+    }
+}
+
+const PROMOTIONS:[ChessPieceKind;4]=[ChessPieceKind::Knight, ChessPieceKind::Bishop, ChessPieceKind::Rook, ChessPieceKind::Queen];
+
+// The bit of the from square in bits 0-5, that of the to square in bits 6-11 and the
+// promotion in bits 12-14, as one more than its index in `PROMOTIONS`.
+fn get_move_code(mv:Move)->u16{
+    let promotion=mv.promotion.and_then(|kind| PROMOTIONS.iter().position(|p| *p==kind)).map_or(0, |idx| idx+1);
+    mv.from.trailing_zeros() as u16|(mv.to.trailing_zeros() as u16)<<6|(promotion as u16)<<12
+}
+
+fn get_move_from_code(code:u16)->Option<Move>{
+    let promotion=match (code>>12) as usize{
+        0=>None,
+        idx@1..=4=>Some(PROMOTIONS[idx-1]),
+        _=>return None,
+    };
+    Some(Move{from: 1<<(code&63), to: 1<<((code>>6)&63), promotion})
+}
+
+impl Serialize for Move{
+    fn serialize<S:Serializer>(&self, serializer:S)->Result<S::Ok, S::Error>{
+        if serializer.is_human_readable(){
+            return serializer.serialize_str(&self.to_uci());
+        }
+        serializer.serialize_u16(get_move_code(*self))
+    }
+}
+
+// Reads back what `Move::to_uci` wrote; unlike `ChessBoard::parse_uci` there is no
+// board to check the move against.
+fn get_move_from_uci(uci:&str)->Option<Move>{
+    if !(4..=5).contains(&uci.len())||!uci.is_ascii(){return None;}
+    let from=get_square_from_name(&uci[0..2])?;
+    let to=get_square_from_name(&uci[2..4])?;
+    let promotion=match uci.chars().nth(4){
+        Some(c)=>Some(fen::get_piece_from_char(c).map(|(_, kind)| kind).filter(|kind| !matches!(kind, ChessPieceKind::King|ChessPieceKind::Pawn))?),
+        None=>None,
+    };
+    Some(Move{from, to, promotion})
+}
+
+impl<'de> Deserialize<'de> for Move{
+    fn deserialize<D:Deserializer<'de>>(deserializer:D)->Result<Move, D::Error>{
+        if !deserializer.is_human_readable(){
+            let code=u16::deserialize(deserializer)?;
+            return get_move_from_code(code).ok_or_else(|| D::Error::custom(format!("invalid move code {}", code)));
+        }
+        let uci=String::deserialize(deserializer)?;
+        get_move_from_uci(&uci).ok_or_else(|| D::Error::custom(format!("invalid UCI move '{}'", uci)))
+    }
+}
+
+// The parts of a game that cannot be rebuilt from its moves.
+#[derive(Serialize, Deserialize)]
+struct GameData {
+    start: ChessBoard,
+    moves: Vec<Move>,
+    tags: Vec<(String, String)>,
+    result: GameResult,
+    termination: Option<Termination>,
+    draw_offers: Vec<DrawOffer>,
+    clock: Option<Clock>,
+}
+
+impl Serialize for Game{
+    fn serialize<S:Serializer>(&self, serializer:S)->Result<S::Ok, S::Error>{
+        GameData{
+            start: self.start,
+            moves: self.moves.clone(),
+            tags: self.tags.clone(),
+            result: self.result,
+            termination: self.termination,
+            draw_offers: self.draw_offers.clone(),
+            clock: self.clock.clone(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game{
+    /// Replays the moves from the start position, failing on an illegal one.
+    fn deserialize<D:Deserializer<'de>>(deserializer:D)->Result<Game, D::Error>{
+        let data=GameData::deserialize(deserializer)?;
+        let mut game=Game::from_board(data.start);
+        for mv in data.moves{
+            if !game.board.make_move(mv){
+                return Err(D::Error::custom(format!("illegal move '{}'", mv.to_uci())));
+            }
+            game.moves.push(mv);
+        }
+        game.tags=data.tags;
+        game.result=data.result;
+        game.termination=data.termination;
+        game.draw_offers=data.draw_offers;
+        game.clock=data.clock;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_board_and_move(){
+        let board=ChessBoard::from_fen("r3k2r/8/8/8/4pP2/8/8/R3K2R b KQkq f3 0 1").unwrap();
+        let json=serde_json::to_string(&board).unwrap();
+        assert_eq!(json, "\"r3k2r/8/8/8/4pP2/8/8/R3K2R b KQkq f3 0 1\"");
+        assert_eq!(serde_json::from_str::<ChessBoard>(&json).unwrap().to_fen(), board.to_fen());
+        assert!(serde_json::from_str::<ChessBoard>("\"8/8 w\"").is_err());
+
+        let mv=Move{from: get_square_from_name("a7").unwrap(), to: get_square_from_name("a8").unwrap(), promotion: Some(ChessPieceKind::Knight)};
+        assert_eq!(serde_json::to_string(&mv).unwrap(), "\"a7a8n\"");
+        assert_eq!(serde_json::from_str::<Move>("\"a7a8n\"").unwrap(), mv);
+        assert!(serde_json::from_str::<Move>("\"a7a8k\"").is_err());
+        assert_eq!(serde_json::to_string(&ChessColour::White).unwrap(), "\"White\"");
+    }
+    #[test]
+    fn test_game(){
+        let mut game=Game::new();
+        game.clock=Some(Clock::fischer(Duration::from_secs(300), Duration::from_secs(2)));
+        game.set_tag("Event", "Test");
+        for san in ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]{
+            let mv=game.board.parse_san(san).unwrap();
+            assert!(game.make_move(mv));
+        }
+        let json=serde_json::to_string(&game).unwrap();
+        assert!(json.contains("\"moves\":[\"e2e4\",\"e7e5\",\"d1h5\""));
+        let read:Game=serde_json::from_str(&json).unwrap();
+        assert_eq!(read.moves, game.moves);
+        assert_eq!(read.board.to_fen(), game.board.to_fen());
+        assert_eq!((read.result(), read.termination()), (GameResult::WhiteWins, Some(Termination::Checkmate)));
+        assert_eq!(read.get_tag("Event"), Some("Test"));
+        assert_eq!(read.clock, game.clock);
+        let illegal=json.replace("e7e5", "e7e4");
+        assert!(serde_json::from_str::<Game>(&illegal).is_err());
+    }
+    #[test]
+    fn test_binary(){
+        let board=ChessBoard::from_fen("r3k2r/8/8/8/4pP2/8/8/R3K2R b KQkq f3 0 1").unwrap();
+        let bytes=postcard::to_allocvec(&board).unwrap();
+        assert_eq!(bytes.len(), POSITION_SIZE);
+        assert_eq!(postcard::from_bytes::<ChessBoard>(&bytes).unwrap().to_fen(), board.to_fen());
+
+        for uci in ["e2e4", "h7h8q", "a2a1n"]{
+            let mv=get_move_from_uci(uci).unwrap();
+            let bytes=postcard::to_allocvec(&mv).unwrap();
+            assert!(bytes.len()<=3);
+            assert_eq!(postcard::from_bytes::<Move>(&bytes).unwrap(), mv);
+        }
+        assert!(postcard::from_bytes::<Move>(&postcard::to_allocvec(&0x7000u16).unwrap()).is_err());
+
+        let mut game=Game::new();
+        for san in ["d4", "d5", "c4", "dxc4"]{
+            let mv=game.board.parse_san(san).unwrap();
+            assert!(game.make_move(mv));
+        }
+        let read:Game=postcard::from_bytes(&postcard::to_allocvec(&game).unwrap()).unwrap();
+        assert_eq!(read.moves, game.moves);
+        assert_eq!(read.board.to_fen(), game.board.to_fen());
+    }
+}
@@ -0,0 +1,142 @@
+use super::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameStatus {
+    Ongoing,
+    /// The colour that delivered mate.
+    Checkmate(ChessColour),
+    Stalemate,
+    InsufficientMaterial,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    /// The colour whose opponent ran out of time.
+    Timeout(ChessColour),
+    /// A flag fell but the opponent could not have mated by any series of moves.
+    TimeoutVsInsufficientMaterial,
+}
+
+impl GameStatus{
+    pub fn is_over(&self)->bool{
+        *self!=GameStatus::Ongoing
+    }
+
+    /// The winning colour, or `None` for draws and games still in progress.
+    pub fn winner(&self)->Option<ChessColour>{
+        match self{
+            GameStatus::Checkmate(col)|GameStatus::Timeout(col)=>Some(*col),
+            _=>None,
+        }
+    }
+}
+
+const DARK_SQUARES:u64=0x55AA55AA55AA55AA;
+
+/// Whether `col` still has the material to mate against some defence by the opponent.
+/// A lone king, a lone minor piece against a bare king, or bishops that all stand on
+/// squares of one colour with no other material on the board cannot.
+pub fn has_mating_material(col:ChessColour, board:ChessBoard)->bool{
+    let own=get_piece_map(col, board)&!get_king_pos(col, board);
+    let other=get_piece_map(get_op_col(col), board)&!get_king_pos(get_op_col(col), board);
+    let heavy=get_kind_map(ChessPieceKind::Pawn, col, board)|get_kind_map(ChessPieceKind::Rook, col, board)|get_kind_map(ChessPieceKind::Queen, col, board);
+    if own==0{return false;}
+    if heavy>0{return true;}
+    let knights=get_kind_map(ChessPieceKind::Knight, col, board);
+    let bishops=get_kind_map(ChessPieceKind::Bishop, col, board);
+    if own.count_ones()==1&&other==0{return false;}
+    if knights==0{
+        // Bishops alone mate only if a bishop or knight of either side can cover the other square colour.
+        let all_bishops=bishops|get_kind_map(ChessPieceKind::Bishop, get_op_col(col), board);
+        let other_blockers=other&!all_bishops;
+        let single_colour=(all_bishops&DARK_SQUARES)==0||(all_bishops&!DARK_SQUARES)==0;
+        return !(single_colour&&other_blockers==0);
+    }
+    true
+}
+
+impl ChessBoard{
+    /// Number of times the current position has occurred since the last capture or pawn move.
+    pub fn repetition_count(&self)->usize{
+        let current=hash_board_state(*self);
+        let last=(self.rule_50_moves as usize).min(self.rule_repetition.len()-1);
+        self.rule_repetition[..=last].iter().filter(|state| **state==current).count()
+    }
+
+    pub fn status(&self)->GameStatus{
+        let board=*self;
+        if get_legal_moves(board).is_empty(){
+            if is_checked(board.current_move, board){
+                return GameStatus::Checkmate(get_op_col(board.current_move));
+            }
+            return GameStatus::Stalemate;
+        }
+        if !has_mating_material(ChessColour::White, board)&&!has_mating_material(ChessColour::Black, board){
+            return GameStatus::InsufficientMaterial;
+        }
+        if self.rule_50_moves>=100{
+            return GameStatus::FiftyMoveRule;
+        }
+        if self.repetition_count()>=3{
+            return GameStatus::ThreefoldRepetition;
+        }
+        GameStatus::Ongoing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board:&mut ChessBoard, moves:&[(&str, &str)]){
+        for (from, to) in moves{
+            assert!(board.make_move(new_move(get_square_from_name(from).unwrap(), get_square_from_name(to).unwrap())));
+        }
+    }
+
+    #[test]
+    fn test_checkmate_and_stalemate(){
+        let mut board=new_board();
+        play(&mut board, &[("f2","f3"),("e7","e5"),("g2","g4"),("d8","h4")]);
+        assert_eq!(board.status(), GameStatus::Checkmate(ChessColour::Black));
+        let board=ChessBoard::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.status(), GameStatus::Stalemate);
+    }
+    #[test]
+    fn test_insufficient_material(){
+        let board=ChessBoard::from_fen("8/8/4k3/8/8/2B5/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(board.status(), GameStatus::InsufficientMaterial);
+        let board=ChessBoard::from_fen("8/8/4kb2/8/8/2B5/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(board.status(), GameStatus::InsufficientMaterial);
+        let board=ChessBoard::from_fen("8/8/4k1b1/8/8/2B5/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(board.status(), GameStatus::Ongoing);
+        assert!(!has_mating_material(ChessColour::Black, ChessBoard::from_fen("8/8/4k3/8/8/8/4K3/7R w - - 0 1").unwrap()));
+    }
+    #[test]
+    fn test_repetition_and_fifty_moves(){
+        let mut board=new_board();
+        let shuffle=[("g1","f3"),("g8","f6"),("f3","g1"),("f6","g8")];
+        play(&mut board, &shuffle);
+        assert_eq!(board.repetition_count(), 2);
+        play(&mut board, &shuffle);
+        assert_eq!(board.status(), GameStatus::ThreefoldRepetition);
+        let board=ChessBoard::from_fen("8/8/4k3/8/8/8/4K3/7R w - - 100 80").unwrap();
+        assert_eq!(board.status(), GameStatus::FiftyMoveRule);
+    }
+    #[test]
+    fn test_repetition_needs_same_rights(){
+        // The rooks come back, but without the right to castle short.
+        let mut board=new_board();
+        play(&mut board, &[("g1","f3"),("g8","f6"),("h1","g1"),("h8","g8"),("g1","h1"),("g8","h8"),("f3","g1"),("f6","g8"),("g1","f3"),("g8","f6")]);
+        assert_eq!(board.repetition_count(), 2);
+        assert_eq!(board.status(), GameStatus::Ongoing);
+        play(&mut board, &[("f3","g1"),("f6","g8"),("g1","f3"),("g8","f6")]);
+        assert_eq!(board.status(), GameStatus::ThreefoldRepetition);
+        // Only the first time may the d-pawn take en passant.
+        let mut board=ChessBoard::from_fen("4k1n1/8/8/8/3p4/8/4P3/4K1N1 w - - 0 1").unwrap();
+        let shuffle=[("g8","f6"),("g1","f3"),("f6","g8"),("f3","g1")];
+        play(&mut board, &[("e2","e4")]);
+        play(&mut board, &shuffle);
+        assert_eq!(board.repetition_count(), 1);
+        play(&mut board, &shuffle);
+        assert_eq!(board.repetition_count(), 2);
+    }
+}
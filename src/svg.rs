@@ -0,0 +1,241 @@
+use super::*;
+use std::fmt::Write;
+
+const LIGHT_SQUARE:&str="#f0d9b5";
+const DARK_SQUARE:&str="#b58863";
+const LAST_MOVE:&str="#9bc700";
+const DEFAULT_ANNOTATION:&str="#15781b";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Arrow {
+    pub from: u64,
+    pub to: u64,
+    /// Any SVG colour, e.g. `#15781b` or `red`.
+    pub colour: String,
+}
+
+impl Arrow{
+    pub fn new(from:u64, to:u64)->Arrow{
+        Arrow{from, to, colour: DEFAULT_ANNOTATION.to_string()}
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SvgOptions {
+    /// Width of one square in pixels.
+    pub square_size: u32,
+    pub coordinates: bool,
+    /// Draw the board from Black's side.
+    pub flipped: bool,
+    pub last_move: Option<Move>,
+    /// Put a red glow under the king of the side to move when it is in check.
+    pub check: bool,
+    pub arrows: Vec<Arrow>,
+    /// Squares to circle, each with its colour.
+    pub circles: Vec<(u64, String)>,
+}
+
+impl Default for SvgOptions{
+    fn default()->SvgOptions{
+        SvgOptions{square_size: 45, coordinates: true, flipped: false, last_move: None, check: true, arrows: Vec::new(), circles: Vec::new()}
+    }
+}
+
+// Piece outlines on a 45 by 45 square, filled white or black when drawn.
+fn get_piece_outline(kind:ChessPieceKind)->&'static str{
+    match kind{
+        ChessPieceKind::King=>"M21.5 4h2v2.5h2.5v2h-2.5v3h-2v-3H19v-2h2.5zM22.5 25C22.5 25 27 17.5 25.5 14.5C24.5 12 20.5 12 19.5 14.5C18 17.5 22.5 25 22.5 25zM12 37C17 40 28 40 33 37V30C37 28 40 24 38 20C36 16 29 15.5 22.5 22C16 15.5 9 16 7 20C5 24 8 28 12 30z",
+        ChessPieceKind::Queen=>"M9 26L9 15L14.5 24L15.5 12L20 23.5L22.5 11L25 23.5L29.5 12L30.5 24L36 15L36 26C36 28 34 30 33 31C33.5 33 34.5 35 35 37C28 39.5 17 39.5 10 37C10.5 35 11.5 33 12 31C11 30 9 28 9 26zM7 13a2 2 0 1 0 4 0a2 2 0 1 0-4 0zM13.5 10a2 2 0 1 0 4 0a2 2 0 1 0-4 0zM20.5 9a2 2 0 1 0 4 0a2 2 0 1 0-4 0zM27.5 10a2 2 0 1 0 4 0a2 2 0 1 0-4 0zM34 13a2 2 0 1 0 4 0a2 2 0 1 0-4 0z",
+        ChessPieceKind::Rook=>"M9 39h27v-3h-3v-4l-1.5-2.5V17l3-3V9h-4v2h-6V9h-4v2h-6V9h-4v5l3 3v12.5L12 32v4H9z",
+        ChessPieceKind::Bishop=>"M20 8a2.5 2.5 0 1 0 5 0a2.5 2.5 0 1 0-5 0zM22.5 11C17 15 14.5 21 17 27H28C30.5 21 28 15 22.5 11zM16 27h13v3H16zM11 39c3-2.5 7-3.5 9-9h5c2 5.5 6 6.5 9 9z",
+        ChessPieceKind::Knight=>"M12 39H34C35 28 33 16 24 11L22 7L19.5 10.5C15 12 11 19 9 25C8.5 27 10 28.5 12 28L14 27C16 26.5 19 25 21 24C17 29 13 33 12 39z",
+        ChessPieceKind::Pawn=>"M22.5 9c-2.2 0-4 1.8-4 4 0 .9.3 1.7.8 2.4-2 1.1-3.3 3.3-3.3 5.6 0 2 .9 3.8 2.4 4.9-3.1 1.6-6.9 5.5-6.9 13.1h22c0-7.6-3.8-11.5-6.9-13.1 1.5-1.1 2.4-2.9 2.4-4.9 0-2.3-1.3-4.5-3.3-5.6.5-.7.8-1.5.8-2.4 0-2.2-1.8-4-4-4z",
+    }
+}
+
+fn get_piece_id(kind:ChessPieceKind)->&'static str{
+    match kind{
+        ChessPieceKind::King=>"king",
+        ChessPieceKind::Queen=>"queen",
+        ChessPieceKind::Rook=>"rook",
+        ChessPieceKind::Bishop=>"bishop",
+        ChessPieceKind::Knight=>"knight",
+        ChessPieceKind::Pawn=>"pawn",
+    }
+}
+
+const PIECE_KINDS:[ChessPieceKind;6]=[
+    ChessPieceKind::King,
+    ChessPieceKind::Queen,
+    ChessPieceKind::Rook,
+    ChessPieceKind::Bishop,
+    ChessPieceKind::Knight,
+    ChessPieceKind::Pawn,
+];
+
+// Colours come from the caller, so they are escaped before going into an attribute.
+fn escape_attribute(value:&str)->String{
+    let mut out=String::with_capacity(value.len());
+    for c in value.chars(){
+        match c{
+            '&'=>out.push_str("&amp;"),
+            '<'=>out.push_str("&lt;"),
+            '>'=>out.push_str("&gt;"),
+            '"'=>out.push_str("&quot;"),
+            '\''=>out.push_str("&apos;"),
+            _=>out.push(c),
+        }
+    }
+    out
+}
+
+// Column and row on the drawn board, counted from the top left.
+fn get_square_cell(pos:u64, flipped:bool)->(u32, u32){
+    let file=u32::from(get_file_u64(pos))-1;
+    let rank=pos.trailing_zeros()/8;
+    if flipped {(7-file, rank)} else {(file, 7-rank)}
+}
+
+impl ChessBoard{
+    /// A standalone SVG diagram of the position. The piece outlines are embedded in
+    /// the file, so it needs no fonts or external images.
+    pub fn to_svg(&self, options:&SvgOptions)->String{
+        let board=*self;
+        let size=options.square_size;
+        let margin=if options.coordinates {size/2} else {0};
+        let total=size*8+margin*2;
+        let centre=|pos:u64|->(f64, f64){
+            let (col, row)=get_square_cell(pos, options.flipped);
+            (f64::from(margin+col*size)+f64::from(size)/2.0, f64::from(margin+row*size)+f64::from(size)/2.0)
+        };
+        let mut out=String::new();
+        let _=writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"1.1\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">", total);
+        let _=writeln!(out, "<defs>");
+        let _=writeln!(out, "<radialGradient id=\"check\"><stop offset=\"0%\" stop-color=\"#ff0000\" stop-opacity=\"1\"/><stop offset=\"100%\" stop-color=\"#ff0000\" stop-opacity=\"0\"/></radialGradient>");
+        for kind in PIECE_KINDS{
+            let _=writeln!(out, "<path id=\"{}\" d=\"{}\" stroke-width=\"1.5\" stroke-linejoin=\"round\"/>", get_piece_id(kind), get_piece_outline(kind));
+        }
+        let _=writeln!(out, "</defs>");
+        if options.coordinates{
+            let _=writeln!(out, "<rect x=\"0\" y=\"0\" width=\"{0}\" height=\"{0}\" fill=\"#262421\"/>", total);
+        }
+
+        let highlighted=options.last_move.map(|mv| mv.from|mv.to).unwrap_or(0);
+        for square in 0..64{
+            let pos=1u64<<square;
+            let (col, row)=get_square_cell(pos, options.flipped);
+            let dark=!(get_file_u64(pos)+(square/8) as u8).is_multiple_of(2);
+            let colour=if dark {DARK_SQUARE} else {LIGHT_SQUARE};
+            let (x, y)=(margin+col*size, margin+row*size);
+            let _=writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" class=\"square {}\"/>", x, y, size, size, colour, get_square_name(pos));
+            if (pos&highlighted)>0{
+                let _=writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.5\" class=\"lastmove\"/>", x, y, size, size, LAST_MOVE);
+            }
+        }
+
+        if options.coordinates{
+            let font=size/3;
+            for idx in 0..8u8{
+                let file_name=char::from(if options.flipped {b'h'-idx} else {b'a'+idx});
+                let rank_name=char::from(if options.flipped {b'1'+idx} else {b'8'-idx});
+                let along=margin+u32::from(idx)*size+size/2;
+                let _=writeln!(out, "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"sans-serif\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#e5e5e5\">{}</text>", along, total-margin/2, font, file_name);
+                let _=writeln!(out, "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"sans-serif\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#e5e5e5\">{}</text>", margin/2, along, font, rank_name);
+            }
+        }
+
+        if options.check&&board.is_in_check(){
+            let (x, y)=centre(get_king_pos(board.current_move, board));
+            let _=writeln!(out, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"url(#check)\" class=\"check\"/>", x, y, f64::from(size)/2.0);
+        }
+
+        for piece in board.pieces.iter().filter(|p| !p.is_captured){
+            let (col, row)=get_square_cell(piece.pos, options.flipped);
+            let fill=if piece.colour==ChessColour::White {"#ffffff"} else {"#000000"};
+            let _=writeln!(out, "<use xlink:href=\"#{}\" transform=\"translate({},{}) scale({})\" fill=\"{}\" stroke=\"#000000\" class=\"piece\"/>", get_piece_id(piece.kind), margin+col*size, margin+row*size, f64::from(size)/45.0, fill);
+        }
+
+        for (pos, colour) in options.circles.iter(){
+            let (x, y)=centre(*pos);
+            let width=f64::from(size)/15.0;
+            let _=writeln!(out, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" opacity=\"0.8\" class=\"circle\"/>", x, y, f64::from(size)/2.0-width, escape_attribute(colour), width);
+        }
+
+        for arrow in options.arrows.iter(){
+            let (x1, y1)=centre(arrow.from);
+            let (x2, y2)=centre(arrow.to);
+            let (dx, dy)=(x2-x1, y2-y1);
+            let length=(dx*dx+dy*dy).sqrt();
+            if length==0.0{continue;}
+            let (ux, uy)=(dx/length, dy/length);
+            let head=f64::from(size)*0.4;
+            let width=f64::from(size)/6.0;
+            // The shaft stops where the head starts so the tip stays sharp under opacity.
+            let (bx, by)=(x2-ux*head, y2-uy*head);
+            let colour=escape_attribute(&arrow.colour);
+            let _=writeln!(out, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\" opacity=\"0.8\" class=\"arrow\"/>", x1, y1, bx, by, colour, width);
+            let _=writeln!(out, "<polygon points=\"{},{} {},{} {},{}\" fill=\"{}\" opacity=\"0.8\" class=\"arrow\"/>", x2, y2, bx-uy*head*0.6, by+ux*head*0.6, bx+uy*head*0.6, by-ux*head*0.6, colour);
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name:&str)->u64{
+        get_square_from_name(name).unwrap()
+    }
+
+    #[test]
+    fn test_start_position(){
+        let svg=new_board().to_svg(&SvgOptions::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("class=\"piece\"").count(), 32);
+        assert_eq!(svg.matches("class=\"square ").count(), 64);
+        assert!(!svg.contains("class=\"check\""));
+        assert!(!svg.contains("<image"));
+    }
+    #[test]
+    fn test_orientation(){
+        let options=SvgOptions{coordinates: false, ..Default::default()};
+        let svg=new_board().to_svg(&options);
+        assert!(svg.contains("<rect x=\"0\" y=\"315\" width=\"45\" height=\"45\" fill=\"#b58863\" class=\"square a1\"/>"));
+        let options=SvgOptions{coordinates: false, flipped: true, ..Default::default()};
+        let svg=new_board().to_svg(&options);
+        assert!(svg.contains("<rect x=\"315\" y=\"0\" width=\"45\" height=\"45\" fill=\"#b58863\" class=\"square a1\"/>"));
+    }
+    #[test]
+    fn test_annotations(){
+        let board=ChessBoard::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let options=SvgOptions{
+            last_move: Some(new_move(square("d8"), square("h4"))),
+            arrows: vec![Arrow::new(square("h4"), square("e1"))],
+            circles: vec![(square("e1"), "red".to_string())],
+            ..Default::default()
+        };
+        let svg=board.to_svg(&options);
+        assert_eq!(svg.matches("class=\"lastmove\"").count(), 2);
+        assert_eq!(svg.matches("class=\"check\"").count(), 1);
+        assert_eq!(svg.matches("class=\"arrow\"").count(), 2);
+        assert!(svg.contains("stroke=\"red\""));
+    }
+    #[test]
+    fn test_pieces_and_escaping(){
+        let options=SvgOptions{
+            arrows: vec![Arrow{from: square("e2"), to: square("e4"), colour: "red\" onload=\"alert(1)".to_string()}],
+            circles: vec![(square("e4"), "<script>".to_string())],
+            ..Default::default()
+        };
+        let svg=new_board().to_svg(&options);
+        assert_eq!(svg.matches("<path id=\"").count(), 6);
+        assert!(svg.contains("<use xlink:href=\"#king\" transform=\"translate(202,337) scale(1)\" fill=\"#ffffff\""));
+        assert!(!svg.contains("class=\"piece\">"));
+        assert!(svg.contains("stroke=\"red&quot; onload=&quot;alert(1)\""));
+        assert!(svg.contains("stroke=\"&lt;script&gt;\""));
+        assert!(!svg.contains("<script>"));
+    }
+}
@@ -0,0 +1,1295 @@
+//! Probing of Syzygy WDL and DTZ endgame tablebases, a port of the Fathom prober
+//! (MIT, Ronald de Man, basil00 and Jon Dart) onto this crate's board.
+
+use super::*;
+use crate::fen::get_piece_char;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const MAX_TABLE_PIECES:usize=7;
+const WDL_TO_DTZ:[i32;5]=[-1, -101, 0, 101, 1];
+const WDL_TO_MAP:[usize;5]=[1, 3, 0, 2, 0];
+const PA_FLAGS:[u8;5]=[8, 0, 0, 0, 4];
+const FILE_TO_FILE:[usize;8]=[0, 1, 2, 3, 3, 2, 1, 0];
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind{
+    fn suffix(&self)->&'static str{
+        match self{
+            TableKind::Wdl=>"rtbw",
+            TableKind::Dtz=>"rtbz",
+        }
+    }
+
+    fn magic(&self)->u32{
+        match self{
+            TableKind::Wdl=>0x5d23e871,
+            TableKind::Dtz=>0xa50c66d7,
+        }
+    }
+}
+
+/// Win/draw/loss from the side to move's point of view. Cursed wins and blessed
+/// losses are decided results that the 50-move rule turns into draws.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl{
+    fn from_value(value:i32)->Wdl{
+        match value{
+            ..=-2=>Wdl::Loss,
+            -1=>Wdl::BlessedLoss,
+            0=>Wdl::Draw,
+            1=>Wdl::CursedWin,
+            _=>Wdl::Win,
+        }
+    }
+
+    /// The value Syzygy uses, -2 for a loss up to 2 for a win.
+    pub fn value(&self)->i32{
+        *self as i32-2
+    }
+}
+
+#[derive(Debug)]
+pub enum SyzygyError {
+    Io(io::Error),
+    /// No table for this material, e.g. `KRPvKR`.
+    MissingTable(String),
+    CorruptedTable(String),
+    /// Tablebases hold no positions with castling rights.
+    Castling,
+}
+
+impl fmt::Display for SyzygyError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            SyzygyError::Io(err)=>write!(f, "could not read tablebase: {}", err),
+            SyzygyError::MissingTable(name)=>write!(f, "no tablebase for {}", name),
+            SyzygyError::CorruptedTable(name)=>write!(f, "tablebase file {} is corrupted", name),
+            SyzygyError::Castling=>write!(f, "positions with castling rights are not in the tablebases"),
+        }
+    }
+}
+
+impl std::error::Error for SyzygyError {}
+
+impl From<io::Error> for SyzygyError{
+    fn from(err:io::Error)->SyzygyError{
+        SyzygyError::Io(err)
+    }
+}
+
+/// A root move scored by the DTZ tables.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RootMove {
+    pub mv: Move,
+    /// The result after this move, from the mover's point of view.
+    pub wdl: Wdl,
+    /// Plies until the next capture or pawn move, counted from the root and signed
+    /// like `wdl`; 1 for an immediate mate.
+    pub dtz: i32,
+}
+
+
+const OFF_DIAG:[i8;64]=[
+    0, -1, -1, -1, -1, -1, -1, -1,
+    1, 0, -1, -1, -1, -1, -1, -1,
+    1, 1, 0, -1, -1, -1, -1, -1,
+    1, 1, 1, 0, -1, -1, -1, -1,
+    1, 1, 1, 1, 0, -1, -1, -1,
+    1, 1, 1, 1, 1, 0, -1, -1,
+    1, 1, 1, 1, 1, 1, 0, -1,
+    1, 1, 1, 1, 1, 1, 1, 0,
+];
+
+const TRIANGLE:[u8;64]=[
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6,
+];
+
+const FLIP_DIAG:[u8;64]=[
+    0, 8, 16, 24, 32, 40, 48, 56,
+    1, 9, 17, 25, 33, 41, 49, 57,
+    2, 10, 18, 26, 34, 42, 50, 58,
+    3, 11, 19, 27, 35, 43, 51, 59,
+    4, 12, 20, 28, 36, 44, 52, 60,
+    5, 13, 21, 29, 37, 45, 53, 61,
+    6, 14, 22, 30, 38, 46, 54, 62,
+    7, 15, 23, 31, 39, 47, 55, 63,
+];
+
+const LOWER:[u8;64]=[
+    28, 0, 1, 2, 3, 4, 5, 6,
+    0, 29, 7, 8, 9, 10, 11, 12,
+    1, 7, 30, 13, 14, 15, 16, 17,
+    2, 8, 13, 31, 18, 19, 20, 21,
+    3, 9, 14, 18, 32, 22, 23, 24,
+    4, 10, 15, 19, 22, 33, 25, 26,
+    5, 11, 16, 20, 23, 25, 34, 27,
+    6, 12, 17, 21, 24, 26, 27, 35,
+];
+
+const DIAG:[u8;64]=[
+    0, 0, 0, 0, 0, 0, 0, 8,
+    0, 1, 0, 0, 0, 0, 9, 0,
+    0, 0, 2, 0, 0, 10, 0, 0,
+    0, 0, 0, 3, 11, 0, 0, 0,
+    0, 0, 0, 12, 4, 0, 0, 0,
+    0, 0, 13, 0, 0, 5, 0, 0,
+    0, 14, 0, 0, 0, 0, 6, 0,
+    15, 0, 0, 0, 0, 0, 0, 7,
+];
+
+const FLAP:[[u8;64];2]=[
+    [
+        0, 0, 0, 0, 0, 0, 0, 0,
+        0, 6, 12, 18, 18, 12, 6, 0,
+        1, 7, 13, 19, 19, 13, 7, 1,
+        2, 8, 14, 20, 20, 14, 8, 2,
+        3, 9, 15, 21, 21, 15, 9, 3,
+        4, 10, 16, 22, 22, 16, 10, 4,
+        5, 11, 17, 23, 23, 17, 11, 5,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+    [
+        0, 0, 0, 0, 0, 0, 0, 0,
+        0, 1, 2, 3, 3, 2, 1, 0,
+        4, 5, 6, 7, 7, 6, 5, 4,
+        8, 9, 10, 11, 11, 10, 9, 8,
+        12, 13, 14, 15, 15, 14, 13, 12,
+        16, 17, 18, 19, 19, 18, 17, 16,
+        20, 21, 22, 23, 23, 22, 21, 20,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+];
+
+const PAWN_TWIST:[[u8;64];2]=[
+    [
+        0, 0, 0, 0, 0, 0, 0, 0,
+        47, 35, 23, 11, 10, 22, 34, 46,
+        45, 33, 21, 9, 8, 20, 32, 44,
+        43, 31, 19, 7, 6, 18, 30, 42,
+        41, 29, 17, 5, 4, 16, 28, 40,
+        39, 27, 15, 3, 2, 14, 26, 38,
+        37, 25, 13, 1, 0, 12, 24, 36,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+    [
+        0, 0, 0, 0, 0, 0, 0, 0,
+        47, 45, 43, 41, 40, 42, 44, 46,
+        39, 37, 35, 33, 32, 34, 36, 38,
+        31, 29, 27, 25, 24, 26, 28, 30,
+        23, 21, 19, 17, 16, 18, 20, 22,
+        15, 13, 11, 9, 8, 10, 12, 14,
+        7, 5, 3, 1, 0, 2, 4, 6,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+];
+
+const KK_IDX:[[i16;64];10]=[
+    [
+        -1, -1, -1, 0, 1, 2, 3, 4,
+        -1, -1, -1, 5, 6, 7, 8, 9,
+        10, 11, 12, 13, 14, 15, 16, 17,
+        18, 19, 20, 21, 22, 23, 24, 25,
+        26, 27, 28, 29, 30, 31, 32, 33,
+        34, 35, 36, 37, 38, 39, 40, 41,
+        42, 43, 44, 45, 46, 47, 48, 49,
+        50, 51, 52, 53, 54, 55, 56, 57,
+    ],
+    [
+        58, -1, -1, -1, 59, 60, 61, 62,
+        63, -1, -1, -1, 64, 65, 66, 67,
+        68, 69, 70, 71, 72, 73, 74, 75,
+        76, 77, 78, 79, 80, 81, 82, 83,
+        84, 85, 86, 87, 88, 89, 90, 91,
+        92, 93, 94, 95, 96, 97, 98, 99,
+        100, 101, 102, 103, 104, 105, 106, 107,
+        108, 109, 110, 111, 112, 113, 114, 115,
+    ],
+    [
+        116, 117, -1, -1, -1, 118, 119, 120,
+        121, 122, -1, -1, -1, 123, 124, 125,
+        126, 127, 128, 129, 130, 131, 132, 133,
+        134, 135, 136, 137, 138, 139, 140, 141,
+        142, 143, 144, 145, 146, 147, 148, 149,
+        150, 151, 152, 153, 154, 155, 156, 157,
+        158, 159, 160, 161, 162, 163, 164, 165,
+        166, 167, 168, 169, 170, 171, 172, 173,
+    ],
+    [
+        174, -1, -1, -1, 175, 176, 177, 178,
+        179, -1, -1, -1, 180, 181, 182, 183,
+        184, -1, -1, -1, 185, 186, 187, 188,
+        189, 190, 191, 192, 193, 194, 195, 196,
+        197, 198, 199, 200, 201, 202, 203, 204,
+        205, 206, 207, 208, 209, 210, 211, 212,
+        213, 214, 215, 216, 217, 218, 219, 220,
+        221, 222, 223, 224, 225, 226, 227, 228,
+    ],
+    [
+        229, 230, -1, -1, -1, 231, 232, 233,
+        234, 235, -1, -1, -1, 236, 237, 238,
+        239, 240, -1, -1, -1, 241, 242, 243,
+        244, 245, 246, 247, 248, 249, 250, 251,
+        252, 253, 254, 255, 256, 257, 258, 259,
+        260, 261, 262, 263, 264, 265, 266, 267,
+        268, 269, 270, 271, 272, 273, 274, 275,
+        276, 277, 278, 279, 280, 281, 282, 283,
+    ],
+    [
+        284, 285, 286, 287, 288, 289, 290, 291,
+        292, 293, -1, -1, -1, 294, 295, 296,
+        297, 298, -1, -1, -1, 299, 300, 301,
+        302, 303, -1, -1, -1, 304, 305, 306,
+        307, 308, 309, 310, 311, 312, 313, 314,
+        315, 316, 317, 318, 319, 320, 321, 322,
+        323, 324, 325, 326, 327, 328, 329, 330,
+        331, 332, 333, 334, 335, 336, 337, 338,
+    ],
+    [
+        -1, -1, 339, 340, 341, 342, 343, 344,
+        -1, -1, 345, 346, 347, 348, 349, 350,
+        -1, -1, 441, 351, 352, 353, 354, 355,
+        -1, -1, -1, 442, 356, 357, 358, 359,
+        -1, -1, -1, -1, 443, 360, 361, 362,
+        -1, -1, -1, -1, -1, 444, 363, 364,
+        -1, -1, -1, -1, -1, -1, 445, 365,
+        -1, -1, -1, -1, -1, -1, -1, 446,
+    ],
+    [
+        -1, -1, -1, 366, 367, 368, 369, 370,
+        -1, -1, -1, 371, 372, 373, 374, 375,
+        -1, -1, -1, 376, 377, 378, 379, 380,
+        -1, -1, -1, 447, 381, 382, 383, 384,
+        -1, -1, -1, -1, 448, 385, 386, 387,
+        -1, -1, -1, -1, -1, 449, 388, 389,
+        -1, -1, -1, -1, -1, -1, 450, 390,
+        -1, -1, -1, -1, -1, -1, -1, 451,
+    ],
+    [
+        452, 391, 392, 393, 394, 395, 396, 397,
+        -1, -1, -1, -1, 398, 399, 400, 401,
+        -1, -1, -1, -1, 402, 403, 404, 405,
+        -1, -1, -1, -1, 406, 407, 408, 409,
+        -1, -1, -1, -1, 453, 410, 411, 412,
+        -1, -1, -1, -1, -1, 454, 413, 414,
+        -1, -1, -1, -1, -1, -1, 455, 415,
+        -1, -1, -1, -1, -1, -1, -1, 456,
+    ],
+    [
+        457, 416, 417, 418, 419, 420, 421, 422,
+        -1, 458, 423, 424, 425, 426, 427, 428,
+        -1, -1, -1, -1, -1, 429, 430, 431,
+        -1, -1, -1, -1, -1, 432, 433, 434,
+        -1, -1, -1, -1, -1, 435, 436, 437,
+        -1, -1, -1, -1, -1, 459, 438, 439,
+        -1, -1, -1, -1, -1, -1, 460, 440,
+        -1, -1, -1, -1, -1, -1, -1, 461,
+    ],
+];
+
+struct Indices {
+    binomial: [[usize;64];MAX_TABLE_PIECES],
+    pawn_idx: [[usize;24];6],
+    pawn_factor: [[usize;4];6],
+}
+
+fn get_indices()->&'static Indices{
+    static INDICES:OnceLock<Indices>=OnceLock::new();
+    INDICES.get_or_init(|| {
+        let mut binomial=[[0;64];MAX_TABLE_PIECES];
+        for (k, row) in binomial.iter_mut().enumerate(){
+            for (n, value) in row.iter_mut().enumerate(){
+                let mut f=1;
+                let mut l=1;
+                for i in 0..k{
+                    f*=n.wrapping_sub(i);
+                    l*=i+1;
+                }
+                *value=f/l;
+            }
+        }
+        let mut pawn_idx=[[0;24];6];
+        let mut pawn_factor=[[0;4];6];
+        for i in 0..6{
+            let mut s=0;
+            for j in 0..24{
+                pawn_idx[i][j]=s;
+                s+=binomial[i][PAWN_TWIST[0][(1+j%6)*8+j/6] as usize];
+                if (j+1)%6==0{
+                    pawn_factor[i][j/6]=s;
+                    s=0;
+                }
+            }
+        }
+        Indices{binomial, pawn_idx, pawn_factor}
+    })
+}
+
+fn read_u16(data:&[u8], pos:usize)->usize{
+    usize::from(u16::from_le_bytes([data[pos], data[pos+1]]))
+}
+
+fn read_u32(data:&[u8], pos:usize)->u32{
+    u32::from_le_bytes([data[pos], data[pos+1], data[pos+2], data[pos+3]])
+}
+
+// Big-endian read that pads past the end of the file with zeros.
+fn read_be(data:&[u8], pos:usize, len:usize)->u64{
+    (0..len).fold(0, |out, i| (out<<8)|u64::from(data.get(pos+i).copied().unwrap_or(0)))
+}
+
+// The material of a table or position, e.g. `KQvKR`, white's pieces first.
+fn get_material_name(board:ChessBoard, flip:bool)->String{
+    let (first, second)=if flip {(ChessColour::Black, ChessColour::White)} else {(ChessColour::White, ChessColour::Black)};
+    let side=|col:ChessColour| PIECE_ORDER.iter()
+        .map(|kind| get_piece_char(*kind, ChessColour::White).to_string().repeat(get_kind_map(*kind, col, board).count_ones() as usize))
+        .collect::<String>();
+    format!("{}v{}", side(first), side(second))
+}
+
+#[derive(Clone, Debug)]
+struct Material {
+    num: usize,
+    symmetric: bool,
+    has_pawns: bool,
+    // Encode the two kings together, used when no other piece is unique.
+    kk_enc: bool,
+    // Pawn counts of the leading side first.
+    pawns: [usize;2],
+}
+
+impl Material{
+    fn from_name(name:&str)->Option<Material>{
+        let (white, black)=name.split_once('v')?;
+        if !white.starts_with('K')||!black.starts_with('K'){return None;}
+        let mut counts=[[0usize;6];2];
+        for (side, pieces) in [white, black].into_iter().enumerate(){
+            for c in pieces.chars(){
+                let idx=PIECE_ORDER.iter().position(|kind| get_piece_char(*kind, ChessColour::White)==c)?;
+                counts[side][idx]+=1;
+            }
+        }
+        let num=white.len()+black.len();
+        if num>MAX_TABLE_PIECES{return None;}
+        let mut pawns=[counts[0][5], counts[1][5]];
+        if pawns[1]>0&&(pawns[0]==0||pawns[0]>pawns[1]){
+            pawns.swap(0, 1);
+        }
+        let unique=counts.iter().flatten().filter(|count| **count==1).count();
+        Some(Material{num, symmetric: white==black, has_pawns: pawns[0]+pawns[1]>0, kk_enc: unique==2, pawns})
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    idx_bits: u32,
+    block_size: u32,
+    min_len: usize,
+    const_value: [u8;2],
+    offset: usize,
+    sym_pat: usize,
+    sym_len: Vec<u8>,
+    base: Vec<u64>,
+    index_table: usize,
+    size_table: usize,
+    data: usize,
+}
+
+fn calc_sym_len(data:&[u8], d:&mut PairsData, s:usize, done:&mut [bool]){
+    let w=d.sym_pat+3*s;
+    let s2=(usize::from(data[w+2])<<4)|(usize::from(data[w+1])>>4);
+    if s2==0x0fff{
+        d.sym_len[s]=0;
+    }
+    else{
+        let s1=((usize::from(data[w+1])&0xf)<<8)|usize::from(data[w]);
+        if !done[s1]{calc_sym_len(data, d, s1, done);}
+        if !done[s2]{calc_sym_len(data, d, s2, done);}
+        d.sym_len[s]=d.sym_len[s1]+d.sym_len[s2]+1;
+    }
+    done[s]=true;
+}
+
+// Reads a compressed table's header at `pos`, returning it with its flags and the
+// sizes of its index table, size table and data.
+fn setup_pairs(data:&[u8], pos:&mut usize, tb_size:usize, kind:TableKind)->Option<(PairsData, u8, [usize;3])>{
+    let start=*pos;
+    let flags=*data.get(start)?;
+    if flags&0x80!=0{
+        *pos+=2;
+        let value=if kind==TableKind::Wdl {data[start+1]} else {0};
+        return Some((PairsData{const_value: [value, 0], ..Default::default()}, flags, [0;3]));
+    }
+    let header=data.get(start..start+10)?;
+    let block_size=u32::from(header[1]);
+    let idx_bits=u32::from(header[2]);
+    let real_num_blocks=read_u32(data, start+4) as usize;
+    let num_blocks=real_num_blocks+usize::from(header[3]);
+    let max_len=usize::from(header[8]);
+    let min_len=usize::from(header[9]);
+    if min_len==0||max_len<min_len||max_len>64||idx_bits==0||idx_bits>=64{return None;}
+    let h=max_len-min_len+1;
+    let num_syms=read_u16(data, start+10+2*h);
+    let sym_pat=start+12+2*h;
+    if data.len()<sym_pat+3*num_syms{return None;}
+    let mut d=PairsData{
+        idx_bits,
+        block_size,
+        min_len,
+        const_value: [0;2],
+        offset: start+10,
+        sym_pat,
+        sym_len: vec![0;num_syms],
+        base: vec![0;h],
+        ..Default::default()
+    };
+    *pos=sym_pat+3*num_syms+(num_syms&1);
+
+    let mut done=vec![false;num_syms];
+    for s in 0..num_syms{
+        if !done[s]{calc_sym_len(data, &mut d, s, &mut done);}
+    }
+    for i in (0..h-1).rev(){
+        let next=read_u16(data, d.offset+2*i) as u64;
+        let prev=read_u16(data, d.offset+2*(i+1)) as u64;
+        d.base[i]=d.base[i+1].wrapping_add(next).wrapping_sub(prev)/2;
+    }
+    for (i, base) in d.base.iter_mut().enumerate(){
+        *base<<=64-(min_len+i);
+    }
+    let num_indices=(tb_size+(1<<idx_bits)-1)>>idx_bits;
+    Some((d, flags, [6*num_indices, 2*num_blocks, real_num_blocks<<block_size]))
+}
+
+// The two value bytes stored for position index `idx`.
+fn decompress_pairs(data:&[u8], d:&PairsData, idx:usize)->[u8;2]{
+    if d.idx_bits==0{
+        return d.const_value;
+    }
+    let main_idx=idx>>d.idx_bits;
+    let mut lit_idx=(idx&((1<<d.idx_bits)-1)) as i64-(1i64<<(d.idx_bits-1));
+    let mut block=read_u32(data, d.index_table+6*main_idx) as usize;
+    lit_idx+=read_u16(data, d.index_table+6*main_idx+4) as i64;
+    let block_length=|block:usize| read_u16(data, d.size_table+2*block) as i64;
+    while lit_idx<0{
+        block-=1;
+        lit_idx+=block_length(block)+1;
+    }
+    while lit_idx>block_length(block){
+        lit_idx-=block_length(block)+1;
+        block+=1;
+    }
+
+    let mut ptr=d.data+(block<<d.block_size);
+    let mut code=read_be(data, ptr, 8);
+    ptr+=8;
+    // Bits of `code` already consumed and refilled from below.
+    let mut bit_count=0;
+    let mut sym;
+    loop{
+        let mut len=d.min_len;
+        while code<d.base[len-d.min_len]{
+            len+=1;
+        }
+        sym=read_u16(data, d.offset+2*(len-d.min_len))+((code-d.base[len-d.min_len])>>(64-len)) as usize;
+        let sym_len=i64::from(d.sym_len[sym]);
+        if lit_idx<sym_len+1{break;}
+        lit_idx-=sym_len+1;
+        code<<=len;
+        bit_count+=len;
+        if bit_count>=32{
+            bit_count-=32;
+            code|=read_be(data, ptr, 4)<<bit_count;
+            ptr+=4;
+        }
+    }
+    // Expand the symbol's pair tree down to the wanted value.
+    while d.sym_len[sym]!=0{
+        let w=d.sym_pat+3*sym;
+        let left=((usize::from(data[w+1])&0xf)<<8)|usize::from(data[w]);
+        let left_len=i64::from(d.sym_len[left]);
+        if lit_idx<left_len+1{
+            sym=left;
+        }
+        else{
+            lit_idx-=left_len+1;
+            sym=(usize::from(data[w+2])<<4)|(usize::from(data[w+1])>>4);
+        }
+    }
+    [data[d.sym_pat+3*sym], data[d.sym_pat+3*sym+1]]
+}
+
+#[derive(Clone, Debug, Default)]
+struct EncInfo {
+    precomp: PairsData,
+    factor: [usize;MAX_TABLE_PIECES],
+    pieces: [u8;MAX_TABLE_PIECES],
+    norm: [usize;MAX_TABLE_PIECES],
+}
+
+// Count placements of `k` identical pieces on `n` squares.
+fn subfactor(k:usize, n:usize)->usize{
+    let mut f=n;
+    let mut l=1;
+    for i in 1..k{
+        f*=n-i;
+        l*=i+1;
+    }
+    f/l
+}
+
+fn init_enc_info(material:&Material, tb:&[u8], shift:u32, t:usize)->(EncInfo, usize){
+    let mut ei=EncInfo::default();
+    let pawn_enc=material.has_pawns;
+    let more_pawns=pawn_enc&&material.pawns[1]>0;
+    for i in 0..material.num{
+        ei.pieces[i]=(tb[i+1+usize::from(more_pawns)]>>shift)&0x0f;
+    }
+    let order=usize::from((tb[0]>>shift)&0x0f);
+    let order2=if more_pawns {usize::from((tb[1]>>shift)&0x0f)} else {0x0f};
+
+    let mut k=if pawn_enc {material.pawns[0]} else if material.kk_enc {2} else {3};
+    ei.norm[0]=k;
+    if more_pawns{
+        ei.norm[k]=material.pawns[1];
+        k+=ei.norm[k];
+    }
+    let mut i=k;
+    while i<material.num{
+        let mut j=i;
+        while j<material.num&&ei.pieces[j]==ei.pieces[i]{
+            ei.norm[i]+=1;
+            j+=1;
+        }
+        i+=ei.norm[i];
+    }
+
+    let indices=get_indices();
+    let mut n=64-k;
+    let mut f=1;
+    let mut i=0;
+    while k<material.num||i==order||i==order2{
+        if i==order{
+            ei.factor[0]=f;
+            f*=if pawn_enc {indices.pawn_factor[ei.norm[0]-1][t]} else if material.kk_enc {462} else {31332};
+        }
+        else if i==order2{
+            ei.factor[ei.norm[0]]=f;
+            f*=subfactor(ei.norm[ei.norm[0]], 48-ei.norm[0]);
+        }
+        else{
+            ei.factor[k]=f;
+            f*=subfactor(ei.norm[k], n);
+            n-=ei.norm[k];
+            k+=ei.norm[k];
+        }
+        i+=1;
+    }
+    (ei, f)
+}
+
+// Position index of the squares in `p`, which are reordered and mirrored in place.
+fn encode(p:&mut [usize], ei:&EncInfo, material:&Material)->usize{
+    let n=material.num;
+    let indices=get_indices();
+    if p[0]&0x04!=0{
+        for sq in p.iter_mut(){
+            *sq^=0x07;
+        }
+    }
+    let mut idx;
+    let mut k;
+    if !material.has_pawns{
+        if p[0]&0x20!=0{
+            for sq in p.iter_mut(){
+                *sq^=0x38;
+            }
+        }
+        for i in 0..n{
+            if OFF_DIAG[p[i]]!=0{
+                if OFF_DIAG[p[i]]>0&&i<(if material.kk_enc {2} else {3}){
+                    for sq in p.iter_mut(){
+                        *sq=usize::from(FLIP_DIAG[*sq]);
+                    }
+                }
+                break;
+            }
+        }
+        if material.kk_enc{
+            idx=KK_IDX[usize::from(TRIANGLE[p[0]])][p[1]] as usize;
+            k=2;
+        }
+        else{
+            let s1=usize::from(p[1]>p[0]);
+            let s2=usize::from(p[2]>p[0])+usize::from(p[2]>p[1]);
+            let diag=|sq:usize| usize::from(DIAG[sq]);
+            idx=if OFF_DIAG[p[0]]!=0{
+                usize::from(TRIANGLE[p[0]])*63*62+(p[1]-s1)*62+(p[2]-s2)
+            }
+            else if OFF_DIAG[p[1]]!=0{
+                6*63*62+diag(p[0])*28*62+usize::from(LOWER[p[1]])*62+p[2]-s2
+            }
+            else if OFF_DIAG[p[2]]!=0{
+                6*63*62+4*28*62+diag(p[0])*7*28+(diag(p[1])-s1)*28+usize::from(LOWER[p[2]])
+            }
+            else{
+                6*63*62+4*28*62+4*7*28+diag(p[0])*7*6+(diag(p[1])-s1)*6+(diag(p[2])-s2)
+            };
+            k=3;
+        }
+        idx*=ei.factor[0];
+    }
+    else{
+        let leading=material.pawns[0];
+        for i in 1..leading{
+            for j in i+1..leading{
+                if PAWN_TWIST[0][p[i]]<PAWN_TWIST[0][p[j]]{
+                    p.swap(i, j);
+                }
+            }
+        }
+        k=leading;
+        idx=indices.pawn_idx[k-1][usize::from(FLAP[0][p[0]])];
+        for i in 1..k{
+            idx+=indices.binomial[k-i][usize::from(PAWN_TWIST[0][p[i]])];
+        }
+        idx*=ei.factor[0];
+        if material.pawns[1]>0{
+            let t=k+material.pawns[1];
+            p[k..t].sort_unstable();
+            let mut s=0;
+            for i in k..t{
+                let skips=p[..k].iter().filter(|other| p[i]>**other).count();
+                s+=indices.binomial[i-k+1][p[i]-skips-8];
+            }
+            idx+=s*ei.factor[k];
+            k=t;
+        }
+    }
+    while k<n{
+        let t=k+ei.norm[k];
+        p[k..t].sort_unstable();
+        let mut s=0;
+        for i in k..t{
+            let skips=p[..k].iter().filter(|other| p[i]>**other).count();
+            s+=indices.binomial[i-k+1][p[i]-skips];
+        }
+        idx+=s*ei.factor[k];
+        k=t;
+    }
+    idx
+}
+
+struct Table {
+    data: Vec<u8>,
+    material: Material,
+    // WDL: one per leading-pawn file (or one without pawns) for each side to move.
+    // DTZ: one per file, storing only one side to move.
+    ei: Vec<Option<EncInfo>>,
+    dtz_flags: [u8;4],
+    dtz_map: usize,
+    dtz_map_idx: [[usize;4];4],
+}
+
+impl Table{
+    fn parse(data:Vec<u8>, material:Material, kind:TableKind)->Option<Table>{
+        if data.len()<5||read_u32(&data, 0)!=kind.magic(){return None;}
+        let split=kind==TableKind::Wdl&&data[4]&0x01!=0;
+        let num=if material.has_pawns {4} else {1};
+        let mut pos=5;
+        let mut ei:Vec<Option<EncInfo>>=vec![None;if kind==TableKind::Wdl {2*num} else {num}];
+        let mut tb_size=[[0;2];4];
+        for t in 0..num{
+            let header=data.get(pos..pos+material.num+2)?;
+            let (info, size)=init_enc_info(&material, header, 0, t);
+            ei[t]=Some(info);
+            tb_size[t][0]=size;
+            if split{
+                let (info, size)=init_enc_info(&material, header, 4, t);
+                ei[num+t]=Some(info);
+                tb_size[t][1]=size;
+            }
+            pos+=material.num+1+usize::from(material.has_pawns&&material.pawns[1]>0);
+        }
+        pos+=pos&1;
+
+        // Index table, size table and data sizes of each sub-table.
+        let mut sizes=[[[0;2];4];3];
+        let mut dtz_flags=[0;4];
+        for t in 0..num{
+            let (pairs, flags, size)=setup_pairs(&data, &mut pos, tb_size[t][0], kind)?;
+            ei[t].as_mut()?.precomp=pairs;
+            for (part, value) in size.into_iter().enumerate(){
+                sizes[part][t][0]=value;
+            }
+            dtz_flags[t]=flags;
+            if split{
+                let (pairs, _, size)=setup_pairs(&data, &mut pos, tb_size[t][1], kind)?;
+                ei[num+t].as_mut()?.precomp=pairs;
+                for (part, value) in size.into_iter().enumerate(){
+                    sizes[part][t][1]=value;
+                }
+            }
+        }
+
+        let dtz_map=pos;
+        let mut dtz_map_idx=[[0;4];4];
+        if kind==TableKind::Dtz{
+            for t in 0..num{
+                if dtz_flags[t]&2==0{continue;}
+                if dtz_flags[t]&16==0{
+                    for idx in dtz_map_idx[t].iter_mut(){
+                        *idx=pos+1-dtz_map;
+                        pos+=1+usize::from(*data.get(pos)?);
+                    }
+                }
+                else{
+                    pos+=pos&1;
+                    for idx in dtz_map_idx[t].iter_mut(){
+                        *idx=(pos-dtz_map)/2+1;
+                        pos+=2+2*read_u16(&data, pos);
+                    }
+                }
+            }
+            pos+=pos&1;
+        }
+
+        let sides=if split {2} else {1};
+        for (part, part_sizes) in sizes.iter().enumerate(){
+            for t in 0..num{
+                for side in 0..sides{
+                    if part==2{
+                        pos=(pos+0x3f)&!0x3f;
+                    }
+                    let d=&mut ei[side*num+t].as_mut()?.precomp;
+                    match part{
+                        0=>d.index_table=pos,
+                        1=>d.size_table=pos,
+                        _=>d.data=pos,
+                    }
+                    pos+=part_sizes[t][side];
+                }
+            }
+        }
+        if pos>data.len(){return None;}
+        Some(Table{data, material, ei, dtz_flags, dtz_map, dtz_map_idx})
+    }
+}
+
+// Tables read so far; files that failed to parse are remembered as None.
+type TableCache=HashMap<(String, TableKind), Option<Arc<Table>>>;
+
+/// A directory of Syzygy tables. Files are read into memory the first time a
+/// position needs them.
+pub struct Tablebase {
+    dir: PathBuf,
+    // Material names with their WDL and DTZ files present.
+    available: HashMap<String, [bool;2]>,
+    loaded: Mutex<TableCache>,
+}
+
+// A table lookup that has to be repeated from the other side's point of view,
+// because DTZ tables only store one side to move.
+const OTHER_SIDE:i32=i32::MIN;
+
+impl Tablebase{
+    /// Finds the `.rtbw` and `.rtbz` files in `dir`; files with the wrong size or
+    /// name are skipped.
+    pub fn open<P:AsRef<Path>>(dir:P)->Result<Tablebase, SyzygyError>{
+        let mut available:HashMap<String, [bool;2]>=HashMap::new();
+        for entry in std::fs::read_dir(dir.as_ref())?{
+            let entry=entry?;
+            let path=entry.path();
+            let (Some(stem), Some(ext))=(path.file_stem().and_then(|s| s.to_str()), path.extension().and_then(|s| s.to_str())) else {continue;};
+            let slot=match ext{
+                "rtbw"=>0,
+                "rtbz"=>1,
+                _=>continue,
+            };
+            if Material::from_name(stem).is_none()||entry.metadata()?.len()%64!=16{continue;}
+            available.entry(stem.to_string()).or_default()[slot]=true;
+        }
+        Ok(Tablebase{dir: dir.as_ref().to_path_buf(), available, loaded: Mutex::new(HashMap::new())})
+    }
+
+    /// Pieces in the largest complete WDL table found.
+    pub fn max_pieces(&self)->usize{
+        self.available.iter().filter(|(_, files)| files[0]).map(|(name, _)| name.len()-1).max().unwrap_or(0)
+    }
+
+    fn get_table(&self, name:&str, kind:TableKind)->Result<Arc<Table>, SyzygyError>{
+        let mut loaded=self.loaded.lock().unwrap_or_else(|err| err.into_inner());
+        let key=(name.to_string(), kind);
+        if let Some(table)=loaded.get(&key){
+            return table.clone().ok_or_else(|| SyzygyError::CorruptedTable(format!("{}.{}", name, kind.suffix())));
+        }
+        let material=Material::from_name(name).ok_or_else(|| SyzygyError::MissingTable(name.to_string()))?;
+        let data=std::fs::read(self.dir.join(format!("{}.{}", name, kind.suffix())))?;
+        let table=Table::parse(data, material, kind).map(Arc::new);
+        loaded.insert(key, table.clone());
+        table.ok_or_else(|| SyzygyError::CorruptedTable(format!("{}.{}", name, kind.suffix())))
+    }
+
+    // Looks up the raw table value: WDL -2..2, or DTZ for a position whose WDL
+    // value is `wdl`, or OTHER_SIDE.
+    fn probe_table(&self, board:ChessBoard, wdl:i32, kind:TableKind)->Result<i32, SyzygyError>{
+        let name=get_material_name(board, false);
+        if kind==TableKind::Wdl&&name=="KvK"{
+            return Ok(0);
+        }
+        let flipped=get_material_name(board, true);
+        let slot=if kind==TableKind::Wdl {0} else {1};
+        let has=|name:&str| self.available.get(name).is_some_and(|files| files[slot]);
+        let (table_name, mut flip)=if has(&name) {(name.clone(), false)} else if has(&flipped) {(flipped, true)} else {
+            return Err(SyzygyError::MissingTable(name));
+        };
+        let table=self.get_table(&table_name, kind)?;
+        let material=&table.material;
+        let white_to_move=board.current_move==ChessColour::White;
+        let bside;
+        if material.symmetric{
+            flip=board.current_move==ChessColour::Black;
+            bside=false;
+        }
+        else{
+            bside=white_to_move==flip;
+        }
+
+        let fill=|pc:u8, mirror:usize, p:&mut Vec<usize>|{
+            let mut col=if pc>>3==0 {ChessColour::White} else {ChessColour::Black};
+            if flip{
+                col=get_op_col(col);
+            }
+            let kind=match pc&7{
+                1=>ChessPieceKind::Pawn,
+                2=>ChessPieceKind::Knight,
+                3=>ChessPieceKind::Bishop,
+                4=>ChessPieceKind::Rook,
+                5=>ChessPieceKind::Queen,
+                _=>ChessPieceKind::King,
+            };
+            let mut squares:Vec<usize>=board.pieces.iter()
+                .filter(|piece| !piece.is_captured&&piece.colour==col&&piece.kind==kind)
+                .map(|piece| get_square_index(piece.pos)^mirror)
+                .collect();
+            squares.sort_unstable();
+            p.extend(squares);
+        };
+        let fill_from=|ei:&EncInfo, mirror:usize, p:&mut Vec<usize>|{
+            while p.len()<material.num{
+                let before=p.len();
+                fill(ei.pieces[p.len()], mirror, p);
+                if p.len()==before{break;}
+            }
+        };
+        let corrupted=|| SyzygyError::CorruptedTable(format!("{}.{}", table_name, kind.suffix()));
+
+        let mut p:Vec<usize>=Vec::with_capacity(MAX_TABLE_PIECES);
+        let mut t=0;
+        let ei;
+        let mut flags=0;
+        if !material.has_pawns{
+            if kind==TableKind::Dtz{
+                flags=table.dtz_flags[0];
+                if (flags&1==1)!=bside&&!material.symmetric{
+                    return Ok(OTHER_SIDE);
+                }
+            }
+            let idx=if kind==TableKind::Wdl {usize::from(bside)} else {0};
+            ei=table.ei.get(idx).and_then(|ei| ei.as_ref()).ok_or_else(corrupted)?;
+            fill_from(ei, 0, &mut p);
+        }
+        else{
+            let mirror=if flip {0x38} else {0};
+            let first=table.ei[0].as_ref().ok_or_else(corrupted)?;
+            fill(first.pieces[0], mirror, &mut p);
+            // The leading pawn is the one nearest the a-file edge on the first twisted ranks.
+            for i in 1..material.pawns[0]{
+                if FLAP[0][p[0]]>FLAP[0][p[i]]{
+                    p.swap(0, i);
+                }
+            }
+            t=FILE_TO_FILE[p[0]&7];
+            if kind==TableKind::Dtz{
+                flags=table.dtz_flags[t];
+                if (flags&1==1)!=bside&&!material.symmetric{
+                    return Ok(OTHER_SIDE);
+                }
+            }
+            let idx=if kind==TableKind::Wdl {t+4*usize::from(bside)} else {t};
+            ei=table.ei.get(idx).and_then(|ei| ei.as_ref()).ok_or_else(corrupted)?;
+            fill_from(ei, mirror, &mut p);
+        }
+        if p.len()!=material.num{
+            return Err(corrupted());
+        }
+        let idx=encode(&mut p, ei, material);
+        let w=decompress_pairs(&table.data, &ei.precomp, idx);
+        if kind==TableKind::Wdl{
+            return Ok(i32::from(w[0])-2);
+        }
+
+        let mut value=usize::from(w[0])+((usize::from(w[1])&0x0f)<<8);
+        if flags&2!=0{
+            let m=WDL_TO_MAP[(wdl+2) as usize];
+            let idx=table.dtz_map_idx[t][m]+value;
+            value=if flags&16==0{
+                usize::from(table.data[table.dtz_map+idx])
+            }
+            else{
+                read_u16(&table.data, table.dtz_map+2*idx)
+            };
+        }
+        if flags&PA_FLAGS[(wdl+2) as usize]==0||wdl&1!=0{
+            value*=2;
+        }
+        Ok(value as i32)
+    }
+
+    // Alpha-beta over captures, for positions without en passant rights.
+    fn probe_ab(&self, board:ChessBoard, mut alpha:i32, beta:i32)->Result<i32, SyzygyError>{
+        for mv in get_captures(board){
+            let mut after=board;
+            after.make_move(mv);
+            let value=-self.probe_ab(after, -beta, -alpha)?;
+            if value>alpha{
+                if value>=beta{
+                    return Ok(value);
+                }
+                alpha=value;
+            }
+        }
+        let value=self.probe_table(board, 0, TableKind::Wdl)?;
+        Ok(alpha.max(value))
+    }
+
+    // WDL value with captures resolved. The flag is set when the best move is a
+    // winning capture or an en passant capture, which probe_dtz relies on.
+    fn probe_wdl_value(&self, board:ChessBoard)->Result<(i32, bool), SyzygyError>{
+        let mut best_capture=-3;
+        let mut best_ep=-3;
+        for mv in get_captures(board){
+            let mut after=board;
+            after.make_move(mv);
+            let value=-self.probe_ab(after, -2, -best_capture)?;
+            if value>best_capture{
+                if value==2{
+                    return Ok((2, true));
+                }
+                if !is_en_passant(mv, board){
+                    best_capture=value;
+                }
+                else if value>best_ep{
+                    best_ep=value;
+                }
+            }
+        }
+        let value=self.probe_table(board, 0, TableKind::Wdl)?;
+        if best_ep>best_capture{
+            if best_ep>value{
+                return Ok((best_ep, true));
+            }
+            best_capture=best_ep;
+        }
+        if best_capture>=value{
+            return Ok((best_capture, best_capture>0));
+        }
+        // Without the en passant right the position may be stalemate, which the
+        // table scores as a draw.
+        if best_ep>-3&&value==0{
+            let others=get_legal_moves(board).into_iter().any(|mv| !is_en_passant(mv, board));
+            if !others&&!board.is_in_check(){
+                return Ok((best_ep, true));
+            }
+        }
+        Ok((value, false))
+    }
+
+    fn probe_dtz_value(&self, board:ChessBoard)->Result<i32, SyzygyError>{
+        let (wdl, decided)=self.probe_wdl_value(board)?;
+        if wdl==0{
+            return Ok(0);
+        }
+        if decided{
+            return Ok(WDL_TO_DTZ[(wdl+2) as usize]);
+        }
+        let moves=get_legal_moves(board);
+        let is_pawn_move=|mv:&Move| get_piece_bit_mask(mv.from, board).is_some_and(|p| p.kind==ChessPieceKind::Pawn);
+        if wdl>0{
+            for mv in moves.iter().filter(|mv| is_pawn_move(mv)&&!is_capture(**mv, board)){
+                let mut after=board;
+                after.make_move(*mv);
+                if -self.probe_wdl_value(after)?.0==wdl{
+                    return Ok(WDL_TO_DTZ[(wdl+2) as usize]);
+                }
+            }
+        }
+        let dtz=self.probe_table(board, wdl, TableKind::Dtz)?;
+        if dtz!=OTHER_SIDE{
+            return Ok(WDL_TO_DTZ[(wdl+2) as usize]+if wdl>0 {dtz} else {-dtz});
+        }
+        // Only the other side to move is stored, so search one ply.
+        let mut best=if wdl>0 {i32::MAX} else {WDL_TO_DTZ[(wdl+2) as usize]};
+        for mv in moves.iter().filter(|mv| !is_capture(**mv, board)&&!is_pawn_move(mv)){
+            let mut after=board;
+            after.make_move(*mv);
+            let value=-self.probe_dtz_value(after)?;
+            if value==1&&is_mate(after){
+                best=1;
+            }
+            else if wdl>0{
+                if value>0&&value+1<best{
+                    best=value+1;
+                }
+            }
+            else if value-1<best{
+                best=value-1;
+            }
+        }
+        Ok(best)
+    }
+
+    fn check_probe(&self, board:&ChessBoard)->Result<(), SyzygyError>{
+        for col in [ChessColour::White, ChessColour::Black]{
+            let Some(king)=get_piece_bit_mask(get_king_pos(col, *board), *board) else {continue;};
+            if get_castling_rook(king, true, *board).is_some()||get_castling_rook(king, false, *board).is_some(){
+                return Err(SyzygyError::Castling);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn probe_wdl(&self, board:&ChessBoard)->Result<Wdl, SyzygyError>{
+        self.check_probe(board)?;
+        Ok(Wdl::from_value(self.probe_wdl_value(*board)?.0))
+    }
+
+    /// Distance to zeroing in plies, the number of moves until the next capture or
+    /// pawn move with best play: positive when the side to move wins, negative when
+    /// it loses and 0 for draws. Values beyond 100 are cursed wins or blessed losses.
+    /// The result can be one ply too long when the 50-move rule is not in sight.
+    pub fn probe_dtz(&self, board:&ChessBoard)->Result<i32, SyzygyError>{
+        self.check_probe(board)?;
+        self.probe_dtz_value(*board)
+    }
+
+    /// The move that keeps the best result and, among winning moves, reaches a
+    /// capture or pawn move soonest while respecting the 50-move rule.
+    pub fn best_root_move(&self, board:&ChessBoard)->Result<Option<RootMove>, SyzygyError>{
+        self.check_probe(board)?;
+        let rule_50=i32::from(board.rule_50_moves);
+        let mut best:Option<(i32, RootMove)>=None;
+        for mv in get_legal_moves(*board){
+            let mut after=*board;
+            after.make_move(mv);
+            let mut dtz=if after.rule_50_moves==0{
+                WDL_TO_DTZ[(-self.probe_wdl_value(after)?.0+2) as usize]
+            }
+            else{
+                let value=-self.probe_dtz_value(after)?;
+                value+value.signum()
+            };
+            if dtz==2&&is_mate(after){
+                dtz=1;
+            }
+            // Wins that stay inside the 50-move rule rank highest, then shorter ones;
+            // losing moves rank by how long they hold out.
+            let rank=if dtz>0{
+                if dtz+rule_50<=99 {1000-dtz} else {-dtz}
+            }
+            else if dtz<0{
+                if -dtz*2+rule_50<100 {-2000-dtz} else {-1000+dtz}
+            }
+            else{
+                -1000
+            };
+            let wdl=match dtz{
+                0=>Wdl::Draw,
+                d if d>0=>if d+rule_50<=100 {Wdl::Win} else {Wdl::CursedWin},
+                d=>if -d+rule_50<=100 {Wdl::Loss} else {Wdl::BlessedLoss},
+            };
+            if best.is_none_or(|(top, _)| rank>top){
+                best=Some((rank, RootMove{mv, wdl, dtz}));
+            }
+        }
+        Ok(best.map(|(_, root)| root))
+    }
+}
+
+fn is_en_passant(mv:Move, board:ChessBoard)->bool{
+    get_piece_bit_mask(mv.from, board).is_some_and(|p| p.kind==ChessPieceKind::Pawn)
+        &&get_file_u64(mv.from)!=get_file_u64(mv.to)
+        &&(mv.to&get_all_piece_map(board))==0
+}
+
+fn is_capture(mv:Move, board:ChessBoard)->bool{
+    (mv.to&get_piece_map(get_op_col(board.current_move), board))>0||is_en_passant(mv, board)
+}
+
+fn get_captures(board:ChessBoard)->Vec<Move>{
+    get_legal_moves(board).into_iter().filter(|mv| is_capture(*mv, board)).collect()
+}
+
+fn is_mate(board:ChessBoard)->bool{
+    is_checked(board.current_move, board)&&get_legal_moves(board).is_empty()
+}
+
+#[cfg(test)]
+mod fixtures;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name:&str)->PathBuf{
+        let dir=std::env::temp_dir().join(format!("skye-syzygy-{}-{}", name, std::process::id()));
+        let _=std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // The tables needed by `check_tables`.
+    const REAL_TABLES:[&str;6]=["KQvK", "KRvK", "KBvK", "KNvK", "KPvK", "KPvKP"];
+
+    // Real tables are not shipped with the crate, so the test using them is ignored
+    // by default. Point SKYE_SYZYGY_PATH at a directory with the tables above and run:
+    //
+    //     SKYE_SYZYGY_PATH=/path/to/syzygy cargo test --lib syzygy -- --ignored
+    fn real_tables()->Tablebase{
+        let dir=std::env::var("SKYE_SYZYGY_PATH").expect("SKYE_SYZYGY_PATH must name a directory of Syzygy tables");
+        for name in REAL_TABLES{
+            for ext in ["rtbw", "rtbz"]{
+                let path=Path::new(&dir).join(format!("{}.{}", name, ext));
+                assert!(path.is_file(), "missing table {}", path.display());
+            }
+        }
+        Tablebase::open(dir).unwrap()
+    }
+
+    // Known results, which the generated fixtures and real tables both have to give.
+    fn check_tables(tb:&Tablebase){
+        let probe=|fen:&str| {
+            let board=ChessBoard::from_fen(fen).unwrap();
+            (tb.probe_wdl(&board).unwrap(), tb.probe_dtz(&board).unwrap())
+        };
+        // For pawns about to queen, whose DTZ looks into the missing KQvKP table.
+        let probe_wdl=|fen:&str| tb.probe_wdl(&ChessBoard::from_fen(fen).unwrap()).unwrap();
+        assert_eq!(probe("8/8/8/8/8/1k6/8/K1Q5 w - - 0 1").0, Wdl::Win);
+        assert_eq!(probe("8/8/8/8/8/1k6/8/K1Q5 b - - 0 1").0, Wdl::Loss);
+        assert_eq!(probe("8/8/8/8/3k4/8/3K4/6N1 w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe("8/8/4k3/8/8/3K4/8/5B2 b - - 0 1"), (Wdl::Draw, 0));
+        // KRvK: mate in one, mate in two, and the side about to be mated.
+        assert_eq!(probe("k7/8/1K6/8/8/8/8/7R w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("k7/8/2K5/8/8/8/8/7R w - - 0 1"), (Wdl::Win, 3));
+        assert_eq!(probe("k7/8/1K6/8/8/8/8/7R b - - 0 1"), (Wdl::Loss, -2));
+        // KPvK: opposition in front of the pawn, stalemate on the rook file, and a
+        // pawn push that keeps the win, for white's pawn and for black's.
+        assert_eq!(probe("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1").0, Wdl::Draw);
+        assert_eq!(probe("k7/P7/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe("8/8/8/8/8/8/4P3/4K2k w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("4k2K/4p3/8/8/8/8/8/8 b - - 0 1"), (Wdl::Win, 1));
+        // KPvKP, stored for white to move only: a pawn that queens first with
+        // either side to move, stalemate, and a pawn won by the king.
+        assert_eq!(probe_wdl("8/P6p/8/8/8/8/8/K6k w - - 0 1"), Wdl::Win);
+        assert_eq!(probe_wdl("k6K/8/8/8/8/8/p6P/8 b - - 0 1"), Wdl::Win);
+        assert_eq!(probe("8/8/8/8/8/p7/P7/K1k5 w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe("7k/8/8/5K2/3p4/3P4/8/8 w - - 0 1"), (Wdl::Win, 3));
+        assert_eq!(probe("7k/8/8/4K3/3p4/3P4/8/8 b - - 0 1"), (Wdl::Loss, -2));
+
+        let board=ChessBoard::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let root=tb.best_root_move(&board).unwrap().unwrap();
+        assert_eq!((root.mv.to_uci().as_str(), root.wdl, root.dtz), ("h1h8", Wdl::Win, 1));
+        let board=ChessBoard::from_fen("7k/8/8/5K2/3p4/3P4/8/8 w - - 0 1").unwrap();
+        let root=tb.best_root_move(&board).unwrap().unwrap();
+        assert_eq!((root.wdl, root.dtz), (Wdl::Win, 3));
+
+        // Best play from both sides mates within the distance the table gives.
+        let mut board=ChessBoard::from_fen("8/8/8/4k3/8/8/8/K6R w - - 0 1").unwrap();
+        let dtz=tb.probe_dtz(&board).unwrap();
+        let mut plies=0;
+        while let Some(root)=tb.best_root_move(&board).unwrap(){
+            board.make_move(root.mv);
+            plies+=1;
+        }
+        assert!(is_mate(board)&&plies<=dtz, "{} plies against a distance of {}", plies, dtz);
+    }
+
+    #[test]
+    fn test_without_tables(){
+        assert!(matches!(Tablebase::open("/nonexistent/syzygy"), Err(SyzygyError::Io(_))));
+        let dir=scratch_dir("empty");
+        std::fs::write(dir.join("README"), "not a table").unwrap();
+        let tb=Tablebase::open(&dir).unwrap();
+        assert_eq!(tb.max_pieces(), 0);
+        let bare=ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1").unwrap();
+        assert_eq!(tb.probe_wdl(&bare).unwrap(), Wdl::Draw);
+        let board=ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/7Q w - - 0 1").unwrap();
+        assert!(matches!(tb.probe_wdl(&board), Err(SyzygyError::MissingTable(name)) if name=="KQvK"));
+        assert!(matches!(tb.probe_wdl(&new_board()), Err(SyzygyError::Castling)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn test_corrupted_table(){
+        let dir=scratch_dir("corrupted");
+        std::fs::write(dir.join("KQvK.rtbw"), [0u8;80]).unwrap();
+        // Wrong size, so the file is not picked up at all.
+        std::fs::write(dir.join("KRvK.rtbw"), [0u8;70]).unwrap();
+        let tb=Tablebase::open(&dir).unwrap();
+        assert_eq!(tb.max_pieces(), 3);
+        // Black's material is looked up through the colour-flipped table.
+        let board=ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/7q w - - 0 1").unwrap();
+        assert!(matches!(tb.probe_wdl(&board), Err(SyzygyError::CorruptedTable(name)) if name=="KQvK.rtbw"));
+        let board=ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/7R w - - 0 1").unwrap();
+        assert!(matches!(tb.probe_wdl(&board), Err(SyzygyError::MissingTable(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn test_material(){
+        let material=Material::from_name("KRPvKP").unwrap();
+        assert!(material.has_pawns&&!material.symmetric&&!material.kk_enc);
+        assert_eq!((material.num, material.pawns), (5, [1, 1]));
+        let material=Material::from_name("KPPvKP").unwrap();
+        assert_eq!(material.pawns, [1, 2]);
+        assert!(Material::from_name("KNNvK").unwrap().kk_enc);
+        assert!(!Material::from_name("KNNvKB").unwrap().kk_enc);
+        assert!(Material::from_name("KQvKQ").unwrap().symmetric);
+        assert!(Material::from_name("KXvK").is_none());
+        let board=ChessBoard::from_fen("8/8/4k3/3p4/8/3K4/8/5BR1 w - - 0 1").unwrap();
+        assert_eq!(get_material_name(board, false), "KRBvKP");
+        assert_eq!(get_material_name(board, true), "KPvKRB");
+    }
+    #[test]
+    fn test_probe_fixtures(){
+        let tb=Tablebase::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/syzygy")).unwrap();
+        assert_eq!(tb.max_pieces(), 4);
+        check_tables(&tb);
+    }
+    #[test]
+    #[ignore="needs Syzygy tables in SKYE_SYZYGY_PATH"]
+    fn test_probe_real_tables(){
+        check_tables(&real_tables());
+    }
+}
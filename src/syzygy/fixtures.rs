@@ -0,0 +1,681 @@
+//! Writes the small Syzygy tables the tests probe, in `tests/syzygy`. The values
+//! come from a retrograde analysis over every placement of the pieces, with captures
+//! and promotions looked up in distance-to-mate tables, and the files from a plain
+//! Re-Pair and Huffman coder laid out the way `Table::parse` reads them.
+//!
+//! Regenerate them with
+//!
+//!     cargo test --release --lib syzygy::fixtures -- --ignored
+
+use super::*;
+use crate::dtm::{get_attacks, is_king_attacked, Dtm, DtmTablebase, Placed};
+
+/// The tables written: every three piece table, which black's material reaches by
+/// flipping colours, and KPvKP for pawns on both sides and a symmetric table.
+pub(super) const FIXTURE_TABLES:[&str;6]=["KQvK", "KRvK", "KBvK", "KNvK", "KPvK", "KPvKP"];
+
+const UNKNOWN:i8=2;
+const ILLEGAL:i8=3;
+const NO_EXIT:i8=-2;
+const FIXTURE_PROMOTIONS:[ChessPieceKind;4]=[ChessPieceKind::Queen, ChessPieceKind::Rook, ChessPieceKind::Bishop, ChessPieceKind::Knight];
+
+// A move from an analysed position: to another position with the same material, or
+// out of it by a capture or promotion, worth `Exit` to the mover.
+enum Step {
+    Exit(i8),
+    Inner {
+        idx: usize,
+        zeroing: bool,
+        // After a double step, the best the opponent gets from capturing en passant.
+        en_passant: Option<i8>,
+    },
+}
+
+// Win, draw or loss for the side to move and the signed distance to zeroing of
+// every placement, indexed by the side to move and then each piece's square.
+struct Analysis {
+    pieces: Vec<(ChessPieceKind, ChessColour)>,
+    wdl: Vec<i8>,
+    dtz: Vec<i16>,
+    has_moves: Vec<bool>,
+    in_check: Vec<bool>,
+    // The best capture or promotion for the side to move, NO_EXIT without one.
+    exit: Vec<i8>,
+}
+
+fn get_dtm_wdl(dtm:Dtm)->i8{
+    match dtm{
+        Dtm::Win(_)=>1,
+        Dtm::Draw=>0,
+        Dtm::Loss(_)=>-1,
+    }
+}
+
+fn get_material_pieces(name:&str)->Vec<(ChessPieceKind, ChessColour)>{
+    let (white, black)=name.split_once('v').unwrap();
+    let mut out=Vec::new();
+    for (pieces, col) in [(white, ChessColour::White), (black, ChessColour::Black)]{
+        out.extend(pieces.chars().map(|c| (fen::get_piece_from_char(c).unwrap().1, col)));
+    }
+    out
+}
+
+impl Analysis{
+    fn new(name:&str, dtm:&DtmTablebase)->Analysis{
+        let pieces=get_material_pieces(name);
+        let size=2*64usize.pow(pieces.len() as u32);
+        let mut analysis=Analysis{
+            pieces,
+            wdl: vec![ILLEGAL;size],
+            dtz: vec![0;size],
+            has_moves: vec![false;size],
+            in_check: vec![false;size],
+            exit: vec![NO_EXIT;size],
+        };
+        let mut steps=Vec::new();
+        for idx in 0..size{
+            let Some((placed, stm))=analysis.get_position(idx) else {continue;};
+            let placed=&placed[..analysis.pieces.len()];
+            analysis.wdl[idx]=UNKNOWN;
+            analysis.in_check[idx]=is_king_attacked(stm, placed);
+            analysis.get_steps(idx, dtm, true, &mut steps);
+            let has_moves=!steps.is_empty();
+            analysis.has_moves[idx]=has_moves;
+            for step in steps.iter(){
+                if let Step::Exit(value)=step{
+                    analysis.exit[idx]=analysis.exit[idx].max(*value);
+                }
+            }
+        }
+        analysis.solve_wdl(dtm);
+        analysis.solve_dtz(dtm);
+        analysis
+    }
+
+    fn get_index(&self, placed:&[Placed], stm:ChessColour)->usize{
+        placed.iter().fold(usize::from(stm==ChessColour::Black), |idx, piece| idx*64+piece.2)
+    }
+
+    // The placement behind an index, if it is a legal position.
+    fn get_position(&self, idx:usize)->Option<([Placed;4], ChessColour)>{
+        let n=self.pieces.len();
+        let mut placed=[(ChessPieceKind::King, ChessColour::White, 0);4];
+        let mut rest=idx;
+        for i in (0..n).rev(){
+            placed[i]=(self.pieces[i].0, self.pieces[i].1, rest%64);
+            rest/=64;
+        }
+        let stm=if rest==1 {ChessColour::Black} else {ChessColour::White};
+        let placed_n=&placed[..n];
+        let occupied=placed_n.iter().fold(0u64, |out, p| out|(1<<p.2));
+        let pawn_on_edge=placed_n.iter().any(|p| p.0==ChessPieceKind::Pawn&&!(8..56).contains(&p.2));
+        if occupied.count_ones() as usize!=n||pawn_on_edge||is_king_attacked(get_op_col(stm), placed_n){
+            return None;
+        }
+        Some((placed, stm))
+    }
+
+    // The value to the opponent of the position after a move.
+    fn get_reply_value(&self, idx:usize, en_passant:Option<i8>)->i8{
+        let value=self.wdl[idx];
+        match en_passant{
+            None=>value,
+            Some(1)=>1,
+            Some(capture) if !self.has_moves[idx]=>capture,
+            Some(_) if value==UNKNOWN=>UNKNOWN,
+            Some(capture)=>value.max(capture),
+        }
+    }
+
+    // Every legal move of the position, leaving out captures and promotions unless
+    // `exits` is set.
+    fn get_steps(&self, idx:usize, dtm:&DtmTablebase, exits:bool, out:&mut Vec<Step>){
+        out.clear();
+        let Some((all, stm))=self.get_position(idx) else {return;};
+        let n=self.pieces.len();
+        let placed=&all[..n];
+        let occupied=placed.iter().fold(0u64, |out, p| out|(1<<p.2));
+        let own=placed.iter().filter(|p| p.1==stm).fold(0u64, |out, p| out|(1<<p.2));
+        let exit=|after:&[Placed]| -get_dtm_wdl(dtm.probe(after, get_op_col(stm)).expect("missing distance-to-mate table"));
+        for (i, &(kind, col, from)) in placed.iter().enumerate(){
+            if col!=stm{continue;}
+            let mut targets=get_attacks(kind, col, from, occupied)&!own;
+            if kind==ChessPieceKind::Pawn{
+                targets&=occupied;
+                let forward=if col==ChessColour::White {from+8} else {from-8};
+                if (occupied&(1<<forward))==0{
+                    targets|=1<<forward;
+                    let start=if col==ChessColour::White {from/8==1} else {from/8==6};
+                    let double=if col==ChessColour::White {from+16} else {from.wrapping_sub(16)};
+                    if start&&(occupied&(1<<double))==0{
+                        targets|=1<<double;
+                    }
+                }
+            }
+            while targets>0{
+                let to=targets.trailing_zeros() as usize;
+                targets&=targets-1;
+                let captured=placed.iter().position(|p| p.2==to);
+                let mut after:Vec<Placed>=placed.iter().enumerate().filter(|(j, _)| Some(*j)!=captured).map(|(_, p)| *p).collect();
+                let moved=if captured.is_some_and(|j| j<i) {i-1} else {i};
+                after[moved].2=to;
+                if is_king_attacked(stm, &after){continue;}
+                if kind==ChessPieceKind::Pawn&&!(8..56).contains(&to){
+                    if exits{
+                        for promotion in FIXTURE_PROMOTIONS{
+                            after[moved].0=promotion;
+                            out.push(Step::Exit(exit(&after)));
+                        }
+                    }
+                }
+                else if captured.is_some(){
+                    if exits{
+                        out.push(Step::Exit(exit(&after)));
+                    }
+                }
+                else{
+                    let mut next=all;
+                    next[i].2=to;
+                    let en_passant=if kind==ChessPieceKind::Pawn&&from.abs_diff(to)==16 {self.get_en_passant(&next[..n], i, dtm)} else {None};
+                    out.push(Step::Inner{idx: self.get_index(&next[..n], get_op_col(stm)), zeroing: kind==ChessPieceKind::Pawn, en_passant});
+                }
+            }
+        }
+    }
+
+    // The best en passant capture of the pawn `i` that just double stepped, for the
+    // side capturing it.
+    fn get_en_passant(&self, placed:&[Placed], i:usize, dtm:&DtmTablebase)->Option<i8>{
+        let (_, mover, to)=placed[i];
+        let capturer=get_op_col(mover);
+        let passed=if mover==ChessColour::White {to-8} else {to+8};
+        let mut best=None;
+        for (j, &(kind, col, sq)) in placed.iter().enumerate(){
+            if kind!=ChessPieceKind::Pawn||col!=capturer||sq/8!=to/8||(sq%8).abs_diff(to%8)!=1{continue;}
+            let mut after:Vec<Placed>=placed.to_vec();
+            after[j].2=passed;
+            after.remove(i);
+            if is_king_attacked(capturer, &after){continue;}
+            let value=-get_dtm_wdl(dtm.probe(&after, mover).expect("missing distance-to-mate table"));
+            best=best.max(Some(value));
+        }
+        best
+    }
+
+    fn solve_wdl(&mut self, dtm:&DtmTablebase){
+        for idx in 0..self.wdl.len(){
+            if self.wdl[idx]!=UNKNOWN{continue;}
+            if !self.has_moves[idx]{
+                self.wdl[idx]=if self.in_check[idx] {-1} else {0};
+            }
+            else if self.exit[idx]==1{
+                self.wdl[idx]=1;
+            }
+        }
+        let mut steps=Vec::new();
+        let mut changed=true;
+        while changed{
+            changed=false;
+            for idx in 0..self.wdl.len(){
+                if self.wdl[idx]!=UNKNOWN{continue;}
+                self.get_steps(idx, dtm, false, &mut steps);
+                // Lost until a move is found that does not lose.
+                let mut value=if self.exit[idx]<=-1 {-1} else {UNKNOWN};
+                for step in steps.iter(){
+                    let Step::Inner{idx: next, en_passant, ..}=*step else {continue;};
+                    match self.get_reply_value(next, en_passant){
+                        -1=>{
+                            value=1;
+                            break;
+                        }
+                        1=>{}
+                        _=>value=UNKNOWN,
+                    }
+                }
+                if value!=UNKNOWN{
+                    self.wdl[idx]=value;
+                    changed=true;
+                }
+            }
+        }
+        for value in self.wdl.iter_mut(){
+            if *value==UNKNOWN{
+                *value=0;
+            }
+        }
+    }
+
+    fn solve_dtz(&mut self, dtm:&DtmTablebase){
+        let mut steps=Vec::new();
+        let mut pass:i16=1;
+        loop{
+            let mut open=false;
+            for idx in 0..self.wdl.len(){
+                let wdl=self.wdl[idx];
+                if wdl==ILLEGAL||wdl==0||self.dtz[idx]!=0{continue;}
+                open=true;
+                self.get_steps(idx, dtm, false, &mut steps);
+                if wdl==1{
+                    let mut dtz=None;
+                    if self.exit[idx]==1{
+                        dtz=Some(1);
+                    }
+                    for step in steps.iter(){
+                        let Step::Inner{idx: next, zeroing, en_passant}=*step else {continue;};
+                        if self.get_reply_value(next, en_passant)!=-1{continue;}
+                        let mated=!self.has_moves[next]&&self.in_check[next];
+                        if zeroing||mated{
+                            dtz=Some(1);
+                        }
+                        else if pass>1&&self.dtz[next]==1-pass{
+                            dtz=dtz.or(Some(pass));
+                        }
+                    }
+                    if let Some(dtz)=dtz{
+                        self.dtz[idx]=dtz;
+                    }
+                }
+                else{
+                    // The longest the loss can be held off, once every reply is known.
+                    let mut dtz=-1;
+                    let mut known=true;
+                    for step in steps.iter(){
+                        let Step::Inner{idx: next, zeroing, ..}=*step else {continue;};
+                        if !zeroing&&self.dtz[next]>0{
+                            dtz=dtz.min(-self.dtz[next]-1);
+                        }
+                        else if !zeroing{
+                            known=false;
+                        }
+                    }
+                    if known{
+                        self.dtz[idx]=dtz;
+                    }
+                }
+            }
+            if !open{break;}
+            pass+=1;
+            assert!(pass<=100, "a cursed win or blessed loss, which these tables do not handle");
+        }
+    }
+}
+
+fn get_piece_code(kind:ChessPieceKind, col:ChessColour)->u8{
+    let code=match kind{
+        ChessPieceKind::Pawn=>1,
+        ChessPieceKind::Knight=>2,
+        ChessPieceKind::Bishop=>3,
+        ChessPieceKind::Rook=>4,
+        ChessPieceKind::Queen=>5,
+        ChessPieceKind::King=>6,
+    };
+    if col==ChessColour::Black {code+8} else {code}
+}
+
+// A symbol of the compressed data: a value, or a pair of symbols.
+#[derive(Copy, Clone)]
+struct Symbol {
+    pair: Option<(usize, usize)>,
+    value: u16,
+    // Values the symbol expands to.
+    len: usize,
+}
+
+const MAX_SYMBOLS:usize=4095;
+const MAX_CODE_LEN:usize=32;
+const BLOCK_SIZE:u32=8;
+const IDX_BITS:u32=12;
+const MAX_BLOCK_VALUES:usize=60000;
+
+// Replaces the most frequent adjacent pairs of symbols with new symbols until the
+// symbol table is full or no pair repeats.
+fn pair_symbols(values:&[u16])->(Vec<Symbol>, Vec<usize>){
+    let mut symbols:Vec<Symbol>=Vec::new();
+    let mut leaves:HashMap<u16, usize>=HashMap::new();
+    let mut seq:Vec<usize>=Vec::with_capacity(values.len());
+    for value in values{
+        let sym=*leaves.entry(*value).or_insert_with(|| {
+            symbols.push(Symbol{pair: None, value: *value, len: 1});
+            symbols.len()-1
+        });
+        seq.push(sym);
+    }
+    loop{
+        let mut counts:HashMap<(usize, usize), usize>=HashMap::new();
+        let mut i=0;
+        while i+1<seq.len(){
+            let pair=(seq[i], seq[i+1]);
+            *counts.entry(pair).or_default()+=1;
+            // A run of one symbol holds half as many pairs as it has places.
+            i+=if pair.0==pair.1&&i+2<seq.len()&&seq[i+2]==pair.0 {2} else {1};
+        }
+        let mut best:Vec<((usize, usize), usize)>=counts.into_iter()
+            .filter(|((a, b), count)| *count>=4&&symbols[*a].len+symbols[*b].len<=256)
+            .collect();
+        best.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut chosen:HashMap<(usize, usize), usize>=HashMap::new();
+        let mut used:Vec<usize>=Vec::new();
+        for ((a, b), _) in best{
+            if symbols.len()>=MAX_SYMBOLS||chosen.len()>=64{break;}
+            if used.contains(&a)||used.contains(&b){continue;}
+            used.extend([a, b]);
+            symbols.push(Symbol{pair: Some((a, b)), value: 0, len: symbols[a].len+symbols[b].len});
+            chosen.insert((a, b), symbols.len()-1);
+        }
+        if chosen.is_empty(){break;}
+        let mut next=Vec::with_capacity(seq.len());
+        let mut i=0;
+        while i<seq.len(){
+            if let Some(sym)=seq.get(i+1).and_then(|b| chosen.get(&(seq[i], *b))){
+                next.push(*sym);
+                i+=2;
+            }
+            else{
+                next.push(seq[i]);
+                i+=1;
+            }
+        }
+        seq=next;
+    }
+    (symbols, seq)
+}
+
+// Huffman code lengths, flattening the counts until no code is longer than the
+// prober reads at once.
+fn get_code_lengths(counts:&[usize])->Vec<usize>{
+    let mut counts=counts.to_vec();
+    loop{
+        let mut lengths=vec![0;counts.len()];
+        let mut nodes:Vec<(usize, Vec<usize>)>=counts.iter().enumerate().filter(|(_, count)| **count>0).map(|(sym, count)| (*count, vec![sym])).collect();
+        if nodes.len()==1{
+            lengths[nodes[0].1[0]]=1;
+            return lengths;
+        }
+        while nodes.len()>1{
+            nodes.sort_unstable_by_key(|node| std::cmp::Reverse(node.0));
+            let (count_a, syms_a)=nodes.pop().unwrap();
+            let (count_b, syms_b)=nodes.pop().unwrap();
+            for sym in syms_a.iter().chain(syms_b.iter()){
+                lengths[*sym]+=1;
+            }
+            nodes.push((count_a+count_b, [syms_a, syms_b].concat()));
+        }
+        if lengths.iter().all(|len| *len<=MAX_CODE_LEN){
+            return lengths;
+        }
+        for count in counts.iter_mut().filter(|count| **count>0){
+            *count=count.div_ceil(2);
+        }
+    }
+}
+
+// A sub-table as written: its header, then its index table, size table and data.
+struct Compressed {
+    header: Vec<u8>,
+    index: Vec<u8>,
+    sizes: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fn compress(values:&[u16], flags:u8, kind:TableKind)->Compressed{
+    let empty=|header:Vec<u8>| Compressed{header, index: Vec::new(), sizes: Vec::new(), data: Vec::new()};
+    if values.iter().all(|value| *value==values[0])&&(kind==TableKind::Wdl||values[0]==0){
+        return empty(vec![0x80|flags, values[0] as u8]);
+    }
+    let (symbols, seq)=pair_symbols(values);
+    let mut counts=vec![0;symbols.len()];
+    for sym in seq.iter(){
+        counts[*sym]+=1;
+    }
+    let lengths=get_code_lengths(&counts);
+
+    // Longer codes take the lower symbol numbers; symbols only used inside pairs
+    // come last and get no code.
+    let mut order:Vec<usize>=(0..symbols.len()).collect();
+    order.sort_by_key(|sym| (lengths[*sym]==0, std::cmp::Reverse(lengths[*sym]), *sym));
+    let mut number=vec![0;symbols.len()];
+    for (n, sym) in order.iter().enumerate(){
+        number[*sym]=n;
+    }
+    let min_len=*lengths.iter().filter(|len| **len>0).min().unwrap();
+    let max_len=*lengths.iter().max().unwrap();
+    let h=max_len-min_len+1;
+    let mut per_len=vec![0usize;h];
+    for len in lengths.iter().filter(|len| **len>0){
+        per_len[len-min_len]+=1;
+    }
+    let mut offset=vec![0usize;h];
+    let mut base=vec![0u64;h];
+    for i in (0..h-1).rev(){
+        offset[i]=offset[i+1]+per_len[i+1];
+        base[i]=(base[i+1]+per_len[i+1] as u64)/2;
+    }
+    let code=|sym:usize| (base[lengths[sym]-min_len]+(number[sym]-offset[lengths[sym]-min_len]) as u64, lengths[sym]);
+
+    // Blocks of whole symbols, each within the block size and the values a size
+    // table entry can count.
+    let block_bits=8usize<<BLOCK_SIZE;
+    let mut blocks:Vec<(Vec<usize>, usize)>=vec![(Vec::new(), 0)];
+    let mut bits=0;
+    for sym in seq.iter(){
+        let (_, len)=code(*sym);
+        let last=blocks.last_mut().unwrap();
+        if bits+len>block_bits||last.1+symbols[*sym].len>MAX_BLOCK_VALUES{
+            blocks.push((Vec::new(), 0));
+            bits=0;
+        }
+        let last=blocks.last_mut().unwrap();
+        last.0.push(*sym);
+        last.1+=symbols[*sym].len;
+        bits+=len;
+    }
+    let mut data=Vec::new();
+    let mut sizes=Vec::new();
+    for (syms, count) in blocks.iter(){
+        let mut block=vec![0u8;1<<BLOCK_SIZE];
+        let mut pos=0;
+        for sym in syms{
+            let (value, len)=code(*sym);
+            for bit in (0..len).rev(){
+                if (value>>bit)&1==1{
+                    block[pos/8]|=0x80>>(pos%8);
+                }
+                pos+=1;
+            }
+        }
+        data.extend(block);
+        sizes.extend(((count-1) as u16).to_le_bytes());
+    }
+    let mut index=Vec::new();
+    let num_indices=(values.len()+(1<<IDX_BITS)-1)>>IDX_BITS;
+    for i in 0..num_indices{
+        let mid=(i<<IDX_BITS)+(1<<(IDX_BITS-1));
+        let mut start=0;
+        let mut block=0;
+        while block+1<blocks.len()&&start+blocks[block].1<=mid{
+            start+=blocks[block].1;
+            block+=1;
+        }
+        index.extend((block as u32).to_le_bytes());
+        index.extend(u16::try_from(mid-start).unwrap().to_le_bytes());
+    }
+
+    let mut header=vec![flags, BLOCK_SIZE as u8, IDX_BITS as u8, 0];
+    header.extend((blocks.len() as u32).to_le_bytes());
+    header.extend([max_len as u8, min_len as u8]);
+    for value in offset.iter(){
+        header.extend((*value as u16).to_le_bytes());
+    }
+    header.extend((symbols.len() as u16).to_le_bytes());
+    for sym in order.iter(){
+        let bytes=match symbols[*sym].pair{
+            None=>{
+                let value=symbols[*sym].value;
+                [value as u8, 0xf0|((value>>8) as u8&0x0f), 0xff]
+            }
+            Some((left, right))=>{
+                let (left, right)=(number[left], number[right]);
+                [left as u8, ((left>>8) as u8&0x0f)|((right as u8&0x0f)<<4), (right>>4) as u8]
+            }
+        };
+        header.extend(bytes);
+    }
+    if symbols.len()%2==1{
+        header.push(0);
+    }
+    Compressed{header, index, sizes, data}
+}
+
+// The table file for the material `name`, storing both sides to move in WDL tables
+// without symmetry and white to move otherwise.
+fn write_table(analysis:&Analysis, name:&str, kind:TableKind)->Vec<u8>{
+    let material=Material::from_name(name).unwrap();
+    let more_pawns=material.has_pawns&&material.pawns[1]>0;
+    let num=if material.has_pawns {4} else {1};
+    let sides=if kind==TableKind::Wdl&&!material.symmetric {2} else {1};
+    // The leading pawns, the other side's pawns, then the rest in material order.
+    let leading=if material.has_pawns&&analysis.pieces.iter().filter(|p| p.0==ChessPieceKind::Pawn&&p.1==ChessColour::White).count()==material.pawns[0] {ChessColour::White} else {ChessColour::Black};
+    let mut order:Vec<usize>=(0..analysis.pieces.len()).collect();
+    order.sort_by_key(|i| {
+        let (kind, col)=analysis.pieces[*i];
+        (match (kind, col==leading){
+            (ChessPieceKind::Pawn, true)=>0,
+            (ChessPieceKind::Pawn, false)=>1,
+            _=>2,
+        }, *i)
+    });
+    // Pawns are indexed after the other pieces, which compresses better as the
+    // kings then change from one index to the next.
+    let mut groups:Vec<u8>=analysis.pieces.iter().filter(|p| p.0!=ChessPieceKind::Pawn).map(|p| get_piece_code(p.0, p.1)).collect();
+    groups.dedup();
+    let pawns_at=if material.has_pawns {groups.len() as u8} else {0};
+    let mut piece_header=vec![pawns_at|(pawns_at<<4)];
+    if more_pawns{
+        piece_header.push((pawns_at+1)|((pawns_at+1)<<4));
+    }
+    for i in order.iter(){
+        let code=get_piece_code(analysis.pieces[*i].0, analysis.pieces[*i].1);
+        piece_header.push(code|(code<<4));
+    }
+
+    let mut infos=Vec::new();
+    let mut values:Vec<Vec<Option<u16>>>=Vec::new();
+    for side in 0..sides{
+        for t in 0..num{
+            let (info, size)=init_enc_info(&material, &piece_header, 4*side as u32, t);
+            infos.push(info);
+            values.push(vec![None;size]);
+        }
+    }
+    for idx in 0..analysis.wdl.len(){
+        let wdl=analysis.wdl[idx];
+        // The prober never reads the distance of a draw.
+        if wdl==ILLEGAL||(kind==TableKind::Dtz&&wdl==0){continue;}
+        let Some((placed, stm))=analysis.get_position(idx) else {continue;};
+        let side=usize::from(stm==ChessColour::Black);
+        if side>=sides{continue;}
+        let squares=|code:u8| {
+            let mut out:Vec<usize>=placed[..analysis.pieces.len()].iter().filter(|p| get_piece_code(p.0, p.1)==code).map(|p| p.2).collect();
+            out.sort_unstable();
+            out
+        };
+        let mut p:Vec<usize>=Vec::new();
+        let mut t=0;
+        if material.has_pawns{
+            p=squares(infos[0].pieces[0]);
+            for i in 1..material.pawns[0]{
+                if FLAP[0][p[0]]>FLAP[0][p[i]]{
+                    p.swap(0, i);
+                }
+            }
+            t=FILE_TO_FILE[p[0]&7];
+        }
+        let info=&infos[side*num+t];
+        while p.len()<material.num{
+            p.extend(squares(info.pieces[p.len()]));
+        }
+        let table_idx=encode(&mut p, info, &material);
+        let value=match kind{
+            TableKind::Wdl=>(2*wdl+2) as u16,
+            TableKind::Dtz=>analysis.dtz[idx].unsigned_abs()-1,
+        };
+        let slot=&mut values[side*num+t][table_idx];
+        assert!(slot.is_none_or(|old| old==value), "{} positions at index {} differ", name, table_idx);
+        *slot=Some(value);
+    }
+
+    // Indices nothing is stored at take the value before them, which compresses best.
+    let flags=if kind==TableKind::Dtz {4|8} else {0};
+    let compressed:Vec<Compressed>=values.iter().map(|values| {
+        let first=values.iter().flatten().next().copied().unwrap_or(0);
+        let filled:Vec<u16>=values.iter().scan(first, |last, value| {
+            *last=value.unwrap_or(*last);
+            Some(*last)
+        }).collect();
+        compress(&filled, flags, kind)
+    }).collect();
+
+    let mut out=kind.magic().to_le_bytes().to_vec();
+    out.push(u8::from(sides==2)|(u8::from(material.has_pawns)<<1));
+    for _ in 0..num{
+        out.extend(piece_header.iter());
+    }
+    if out.len()%2==1{
+        out.push(0);
+    }
+    for t in 0..num{
+        for side in 0..sides{
+            out.extend(compressed[side*num+t].header.iter());
+        }
+    }
+    if kind==TableKind::Dtz&&out.len()%2==1{
+        out.push(0);
+    }
+    for part in 0..3{
+        for t in 0..num{
+            for side in 0..sides{
+                let sub=&compressed[side*num+t];
+                match part{
+                    0=>out.extend(sub.index.iter()),
+                    1=>out.extend(sub.sizes.iter()),
+                    _=>{
+                        out.resize((out.len()+0x3f)&!0x3f, 0);
+                        out.extend(sub.data.iter());
+                    }
+                }
+            }
+        }
+    }
+    out.resize(out.len()+(80-out.len()%64)%64, 0);
+
+    // Read every value back through the prober's own decoder.
+    let table=Table::parse(out.clone(), material, kind).expect("written table does not parse");
+    for (sub, values) in values.iter().enumerate(){
+        let (side, t)=(sub/num, sub%num);
+        let info=table.ei[if kind==TableKind::Wdl {side*num+t} else {t}].as_ref().unwrap();
+        for (idx, value) in values.iter().enumerate(){
+            let Some(value)=value else {continue;};
+            let w=decompress_pairs(&table.data, &info.precomp, idx);
+            let read=if kind==TableKind::Wdl {u16::from(w[0])} else {u16::from(w[0])|(u16::from(w[1]&0x0f)<<8)};
+            assert_eq!(read, *value, "{} side {} file {} index {}", name, side, t, idx);
+        }
+    }
+    out
+}
+
+#[test]
+#[ignore="writes tests/syzygy; run in release, it takes minutes"]
+fn write_fixtures(){
+    let mut dtm=DtmTablebase::new();
+    for name in FIXTURE_TABLES{
+        dtm.generate(name).unwrap();
+    }
+    let dir=Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/syzygy");
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in FIXTURE_TABLES{
+        let analysis=Analysis::new(name, &dtm);
+        for kind in [TableKind::Wdl, TableKind::Dtz]{
+            std::fs::write(dir.join(format!("{}.{}", name, kind.suffix())), write_table(&analysis, name, kind)).unwrap();
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! A transposition table shared by search threads without locks. Each slot holds
+//! the entry's data and its key xored with that data, so a slot torn by two threads
+//! writing at once fails the key check instead of returning a wrong entry.
+
+use super::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Two u64s per slot.
+const SLOT_BYTES:usize=16;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The search failed high: the score is at least this.
+    Lower,
+    /// The search failed low: the score is at most this.
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+fn get_promotion_code(kind:Option<ChessPieceKind>)->u64{
+    match kind{
+        Some(ChessPieceKind::Knight)=>1,
+        Some(ChessPieceKind::Bishop)=>2,
+        Some(ChessPieceKind::Rook)=>3,
+        Some(ChessPieceKind::Queen)=>4,
+        _=>0,
+    }
+}
+
+// From and to squares in six bits each, then the promotion. Zero is no move, as a
+// move never ends where it starts.
+fn pack_move(mv:Option<Move>)->u64{
+    let Some(mv)=mv else {return 0;};
+    u64::from(mv.from.trailing_zeros())|u64::from(mv.to.trailing_zeros())<<6|get_promotion_code(mv.promotion)<<12
+}
+
+fn unpack_move(bits:u64)->Option<Move>{
+    let (from, to)=(bits&0x3F, (bits>>6)&0x3F);
+    if from==to{return None;}
+    let promotion=match (bits>>12)&0x7{
+        1=>Some(ChessPieceKind::Knight),
+        2=>Some(ChessPieceKind::Bishop),
+        3=>Some(ChessPieceKind::Rook),
+        4=>Some(ChessPieceKind::Queen),
+        _=>None,
+    };
+    Some(Move{from: 1<<from, to: 1<<to, promotion})
+}
+
+impl TtEntry{
+    // Never zero, since the bound code starts at one; zero marks an empty slot.
+    fn pack(&self)->u64{
+        let bound:u64=match self.bound{
+            Bound::Exact=>1,
+            Bound::Lower=>2,
+            Bound::Upper=>3,
+        };
+        pack_move(self.best_move)|u64::from(self.score as i16 as u16)<<16|u64::from(self.depth)<<32|bound<<40
+    }
+
+    fn unpack(data:u64)->Option<TtEntry>{
+        let bound=match (data>>40)&0x3{
+            1=>Bound::Exact,
+            2=>Bound::Lower,
+            3=>Bound::Upper,
+            _=>return None,
+        };
+        Some(TtEntry{
+            best_move: unpack_move(data&0xFFFF),
+            score: i32::from((data>>16) as u16 as i16),
+            depth: (data>>32) as u8,
+            bound,
+        })
+    }
+}
+
+/// Fixed-size table of search results by Zobrist key (`ChessBoard::polyglot_key`).
+/// A new entry replaces one for another position, or a shallower one for the same.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64;2]>,
+}
+
+impl TranspositionTable{
+    /// A table taking about `megabytes` of memory, at least one slot.
+    pub fn new(megabytes:usize)->TranspositionTable{
+        let len=(megabytes*1024*1024/SLOT_BYTES).max(1);
+        TranspositionTable{slots: (0..len).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect()}
+    }
+
+    pub fn len(&self)->usize{
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.slots.is_empty()
+    }
+
+    fn get_slot(&self, key:u64)->&[AtomicU64;2]{
+        &self.slots[(key%self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key:u64)->Option<TtEntry>{
+        let slot=self.get_slot(key);
+        let data=slot[1].load(Ordering::Relaxed);
+        if data==0||slot[0].load(Ordering::Relaxed)^data!=key{
+            return None;
+        }
+        TtEntry::unpack(data)
+    }
+
+    pub fn store(&self, key:u64, entry:TtEntry){
+        let slot=self.get_slot(key);
+        if let Some(old)=self.probe(key){
+            if old.depth>entry.depth&&entry.bound!=Bound::Exact{return;}
+        }
+        let data=entry.pack();
+        slot[0].store(key^data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    /// Empties the table, e.g. for a new game.
+    pub fn clear(&self){
+        for slot in self.slots.iter(){
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe(){
+        let table=TranspositionTable::new(1);
+        assert_eq!(table.len(), 65536);
+        let board=new_board();
+        let key=board.polyglot_key();
+        assert_eq!(table.probe(key), None);
+        let mv=board.parse_uci("e2e4");
+        let entry=TtEntry{best_move: mv, score: -29990, depth: 7, bound: Bound::Upper};
+        table.store(key, entry);
+        assert_eq!(table.probe(key), Some(entry));
+        // A shallower bound does not replace a deeper entry, but an exact score does.
+        table.store(key, TtEntry{best_move: None, score: 5, depth: 3, bound: Bound::Lower});
+        assert_eq!(table.probe(key), Some(entry));
+        let promotion=ChessBoard::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap().parse_uci("a7a8n");
+        let exact=TtEntry{best_move: promotion, score: 12, depth: 1, bound: Bound::Exact};
+        table.store(key, exact);
+        assert_eq!(table.probe(key), Some(exact));
+        table.clear();
+        assert_eq!(table.probe(key), None);
+    }
+    #[test]
+    fn test_torn_slot_is_rejected(){
+        let table=TranspositionTable::new(0);
+        assert_eq!(table.len(), 1);
+        table.store(1, TtEntry{best_move: None, score: 1, depth: 1, bound: Bound::Exact});
+        // Another thread's data landing without its key.
+        let data=TtEntry{best_move: None, score: 2, depth: 2, bound: Bound::Exact}.pack();
+        table.slots[0][1].store(data, Ordering::Relaxed);
+        assert_eq!(table.probe(1), None);
+        assert_eq!(table.probe(2), None);
+    }
+}
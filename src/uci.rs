@@ -0,0 +1,534 @@
+//! A client for UCI engines run as child processes.
+
+use super::*;
+use crate::fen::START_FEN;
+use crate::game::Game;
+use crate::search::{SearchLimits, MATE_SCORE};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+// How long an engine gets to exit after `quit`.
+const QUIT_TIMEOUT:Duration=Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum UciError {
+    Io(io::Error),
+    /// The engine closed its output, usually by exiting.
+    Exited,
+    /// No reply within the engine's timeout; names what was waited for.
+    Timeout(&'static str),
+    /// A best move that is not legal in the position.
+    IllegalMove(String),
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        match self{
+            UciError::Io(err)=>write!(f, "engine i/o failed: {}", err),
+            UciError::Exited=>write!(f, "engine exited"),
+            UciError::Timeout(reply)=>write!(f, "engine did not send {} in time", reply),
+            UciError::IllegalMove(mv)=>write!(f, "engine played illegal move '{}'", mv),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+impl From<io::Error> for UciError{
+    fn from(err:io::Error)->UciError{
+        UciError::Io(err)
+    }
+}
+
+/// An option the engine declared during the handshake.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UciOption {
+    pub name: String,
+    /// `check`, `spin`, `combo`, `button` or `string`.
+    pub kind: String,
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    /// The choices of a `combo` option.
+    pub vars: Vec<String>,
+}
+
+// Option names and values may hold spaces, so each runs up to the next keyword.
+fn parse_option(line:&str)->Option<UciOption>{
+    let words:Vec<&str>=line.split_whitespace().collect();
+    if words.first()!=Some(&"option"){return None;}
+    let keywords=["name", "type", "default", "min", "max", "var"];
+    let mut out=UciOption::default();
+    let mut idx=1;
+    while idx<words.len(){
+        let keyword=words[idx];
+        let end=(idx+1..words.len()).find(|i| keywords.contains(&words[*i])).unwrap_or(words.len());
+        let value=words[idx+1..end].join(" ");
+        match keyword{
+            "name"=>out.name=value,
+            "type"=>out.kind=value,
+            "default"=>out.default=Some(value),
+            "min"=>out.min=value.parse().ok(),
+            "max"=>out.max=value.parse().ok(),
+            "var"=>out.vars.push(value),
+            _=>{}
+        }
+        idx=end;
+    }
+    if out.name.is_empty() {None} else {Some(out)}
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UciScore {
+    Centipawns(i32),
+    /// Moves to mate, negative when the engine is getting mated.
+    Mate(i32),
+}
+
+impl UciScore{
+    /// The score in the built-in search's convention: centipawns, or `MATE_SCORE`
+    /// less the plies to mate.
+    pub fn to_score(&self)->i32{
+        match *self{
+            UciScore::Centipawns(cp)=>cp,
+            UciScore::Mate(moves) if moves>0=>MATE_SCORE-(2*moves-1),
+            UciScore::Mate(moves)=>-(MATE_SCORE+2*moves),
+        }
+    }
+}
+
+/// One `info` line of a search.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UciInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    /// Which line of a multi-PV search this is, from 1.
+    pub multipv: Option<u32>,
+    pub score: Option<UciScore>,
+    /// The score is only a bound: at least it with `lowerbound`, at most with `upperbound`.
+    pub lowerbound: bool,
+    pub upperbound: bool,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<Duration>,
+    pub pv: Vec<Move>,
+    pub string: Option<String>,
+}
+
+impl UciInfo{
+    /// Parses an `info` line for a search of `board`. The principal variation stops
+    /// at the first move that is not legal.
+    pub fn parse(line:&str, board:&ChessBoard)->Option<UciInfo>{
+        let mut words=line.split_whitespace();
+        if words.next()!=Some("info"){return None;}
+        let mut out=UciInfo::default();
+        let words:Vec<&str>=words.collect();
+        let mut idx=0;
+        let number=|idx:usize| words.get(idx+1).and_then(|word| word.parse::<u64>().ok());
+        while idx<words.len(){
+            match words[idx]{
+                "depth"=>out.depth=number(idx).map(|value| value as u32),
+                "seldepth"=>out.seldepth=number(idx).map(|value| value as u32),
+                "multipv"=>out.multipv=number(idx).map(|value| value as u32),
+                "nodes"=>out.nodes=number(idx),
+                "nps"=>out.nps=number(idx),
+                "time"=>out.time=number(idx).map(Duration::from_millis),
+                "lowerbound"=>out.lowerbound=true,
+                "upperbound"=>out.upperbound=true,
+                "score"=>{
+                    let value=words.get(idx+2).and_then(|word| word.parse::<i32>().ok());
+                    out.score=match (words.get(idx+1), value){
+                        (Some(&"cp"), Some(value))=>Some(UciScore::Centipawns(value)),
+                        (Some(&"mate"), Some(value))=>Some(UciScore::Mate(value)),
+                        _=>None,
+                    };
+                    idx+=2;
+                }
+                "string"=>{
+                    out.string=Some(words[idx+1..].join(" "));
+                    break;
+                }
+                "pv"=>{
+                    let mut after=*board;
+                    for uci in words[idx+1..].iter(){
+                        let Some(mv)=after.parse_uci(uci) else {break;};
+                        after.make_move(mv);
+                        out.pv.push(mv);
+                    }
+                    // The pv runs to the end of the line.
+                    break;
+                }
+                _=>{}
+            }
+            idx+=1;
+        }
+        Some(out)
+    }
+}
+
+/// Arguments of a `go` command beyond the plain search limits.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct GoParams {
+    pub limits: SearchLimits,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    /// Search for a mate in this many moves.
+    pub mate: Option<u32>,
+    /// Search until `stop`.
+    pub infinite: bool,
+    /// Search the expected reply until `ponderhit` or `stop`.
+    pub ponder: bool,
+}
+
+impl GoParams{
+    fn to_command(self)->String{
+        let mut out="go".to_string();
+        if self.ponder{
+            out.push_str(" ponder");
+        }
+        let times=[("wtime", self.wtime), ("btime", self.btime), ("winc", self.winc), ("binc", self.binc), ("movetime", self.limits.movetime)];
+        for (name, time) in times{
+            if let Some(time)=time{
+                out.push_str(&format!(" {} {}", name, time.as_millis()));
+            }
+        }
+        let counts=[("movestogo", self.movestogo.map(u64::from)), ("depth", self.limits.depth.map(u64::from)), ("nodes", self.limits.nodes), ("mate", self.mate.map(u64::from))];
+        for (name, count) in counts{
+            if let Some(count)=count{
+                out.push_str(&format!(" {} {}", name, count));
+            }
+        }
+        if self.infinite{
+            out.push_str(" infinite");
+        }
+        out
+    }
+}
+
+/// The end of a search, with every `info` line it sent.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BestMove {
+    /// `None` if the engine had no move, as in mate or stalemate.
+    pub best_move: Option<Move>,
+    pub ponder: Option<Move>,
+    pub infos: Vec<UciInfo>,
+}
+
+impl BestMove{
+    /// The deepest info of the main line, or of line `multipv`.
+    pub fn last_info(&self, multipv:u32)->Option<&UciInfo>{
+        self.infos.iter().rev().find(|info| info.multipv.unwrap_or(1)==multipv&&(info.score.is_some()||!info.pv.is_empty()))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum UciEvent {
+    Info(UciInfo),
+    BestMove{best_move: Option<Move>, ponder: Option<Move>},
+}
+
+/// A running UCI engine. Output is read on a separate thread, so waits can time out
+/// and a search can be stopped while it runs.
+pub struct UciEngine {
+    name: String,
+    author: String,
+    options: Vec<UciOption>,
+    child: Child,
+    input: ChildStdin,
+    lines: Receiver<String>,
+    // The position of the last `position` command.
+    board: ChessBoard,
+    /// How long to wait for `uciok` and `readyok`.
+    pub timeout: Duration,
+}
+
+impl UciEngine{
+    /// Starts the engine and performs the `uci` handshake.
+    pub fn spawn(path:&str, args:&[String])->Result<UciEngine, UciError>{
+        let mut child=Command::new(path).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let (Some(input), Some(output))=(child.stdin.take(), child.stdout.take()) else {
+            return Err(UciError::Exited);
+        };
+        let (sender, lines)=mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(output).lines(){
+                let Ok(line)=line else {break;};
+                if sender.send(line).is_err(){break;}
+            }
+        });
+        let mut engine=UciEngine{
+            name: path.to_string(),
+            author: String::new(),
+            options: Vec::new(),
+            child,
+            input,
+            lines,
+            board: new_board(),
+            timeout: Duration::from_secs(10),
+        };
+        engine.send("uci")?;
+        loop{
+            let line=engine.read_line(Some(engine.timeout), "uciok")?;
+            if let Some(name)=line.strip_prefix("id name "){
+                engine.name=name.trim().to_string();
+            }
+            else if let Some(author)=line.strip_prefix("id author "){
+                engine.author=author.trim().to_string();
+            }
+            else if let Some(option)=parse_option(&line){
+                engine.options.push(option);
+            }
+            else if line.trim()=="uciok"{
+                break;
+            }
+        }
+        Ok(engine)
+    }
+
+    pub fn name(&self)->&str{
+        &self.name
+    }
+
+    pub fn author(&self)->&str{
+        &self.author
+    }
+
+    pub fn options(&self)->&[UciOption]{
+        &self.options
+    }
+
+    /// Sends a raw command line.
+    pub fn send(&mut self, command:&str)->Result<(), UciError>{
+        let sent=writeln!(self.input, "{}", command).and_then(|_| self.input.flush());
+        match sent{
+            Err(err) if err.kind()==io::ErrorKind::BrokenPipe=>Err(UciError::Exited),
+            sent=>Ok(sent?),
+        }
+    }
+
+    fn read_line(&mut self, timeout:Option<Duration>, waiting_for:&'static str)->Result<String, UciError>{
+        match timeout{
+            Some(timeout)=>match self.lines.recv_timeout(timeout){
+                Ok(line)=>Ok(line),
+                Err(RecvTimeoutError::Timeout)=>Err(UciError::Timeout(waiting_for)),
+                Err(RecvTimeoutError::Disconnected)=>Err(UciError::Exited),
+            },
+            None=>self.lines.recv().map_err(|_| UciError::Exited),
+        }
+    }
+
+    /// Sets an option; buttons take no value.
+    pub fn set_option(&mut self, name:&str, value:Option<&str>)->Result<(), UciError>{
+        match value{
+            Some(value)=>self.send(&format!("setoption name {} value {}", name, value)),
+            None=>self.send(&format!("setoption name {}", name)),
+        }
+    }
+
+    /// Waits until the engine has dealt with every command sent so far.
+    pub fn is_ready(&mut self)->Result<(), UciError>{
+        self.send("isready")?;
+        while self.read_line(Some(self.timeout), "readyok")?.trim()!="readyok"{}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self)->Result<(), UciError>{
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    /// Sends the game's start position and moves.
+    pub fn set_position(&mut self, game:&Game)->Result<(), UciError>{
+        let start=game.start.to_fen();
+        let mut command=if start==START_FEN {"position startpos".to_string()} else {format!("position fen {}", start)};
+        if !game.moves.is_empty(){
+            command.push_str(" moves");
+            for mv in game.moves.iter(){
+                command.push(' ');
+                command.push_str(&mv.to_uci());
+            }
+        }
+        self.board=game.board;
+        self.send(&command)
+    }
+
+    /// Starts a search of the last position sent. Its output is read with
+    /// `read_event` or `wait_best_move`.
+    pub fn go(&mut self, params:&GoParams)->Result<(), UciError>{
+        self.send(&params.to_command())
+    }
+
+    pub fn stop(&mut self)->Result<(), UciError>{
+        self.send("stop")
+    }
+
+    /// The opponent played the expected move: the ponder search becomes a normal one.
+    pub fn ponderhit(&mut self)->Result<(), UciError>{
+        self.send("ponderhit")
+    }
+
+    /// The next `info` or `bestmove` line of the running search, or `None` if none
+    /// came within `timeout`. Other output is skipped.
+    pub fn read_event(&mut self, timeout:Option<Duration>)->Result<Option<UciEvent>, UciError>{
+        loop{
+            let line=match self.read_line(timeout, "search output"){
+                Ok(line)=>line,
+                Err(UciError::Timeout(_))=>return Ok(None),
+                Err(err)=>return Err(err),
+            };
+            if let Some(info)=UciInfo::parse(&line, &self.board){
+                return Ok(Some(UciEvent::Info(info)));
+            }
+            let mut words=line.split_whitespace();
+            if words.next()!=Some("bestmove"){continue;}
+            let best=words.next().unwrap_or("(none)");
+            if best=="(none)"||best=="0000"{
+                return Ok(Some(UciEvent::BestMove{best_move: None, ponder: None}));
+            }
+            let best_move=self.board.parse_uci(best).ok_or(UciError::IllegalMove(best.to_string()))?;
+            let mut after=self.board;
+            after.make_move(best_move);
+            let ponder=match (words.next(), words.next()){
+                (Some("ponder"), Some(ponder))=>after.parse_uci(ponder),
+                _=>None,
+            };
+            return Ok(Some(UciEvent::BestMove{best_move: Some(best_move), ponder}));
+        }
+    }
+
+    /// Reads the running search's output up to its best move, failing with
+    /// `UciError::Timeout` if that takes longer than `timeout`.
+    pub fn wait_best_move(&mut self, timeout:Option<Duration>)->Result<BestMove, UciError>{
+        let deadline=timeout.map(|timeout| Instant::now()+timeout);
+        let mut infos=Vec::new();
+        loop{
+            let remaining=deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            match self.read_event(remaining)?{
+                Some(UciEvent::Info(info))=>infos.push(info),
+                Some(UciEvent::BestMove{best_move, ponder})=>return Ok(BestMove{best_move, ponder, infos}),
+                None=>return Err(UciError::Timeout("bestmove")),
+            }
+        }
+    }
+
+    /// Searches the game's current position and waits for the result. A search
+    /// with a move time or clock must finish within it plus the engine's `timeout`;
+    /// other searches may take as long as they need.
+    pub fn search(&mut self, game:&Game, params:&GoParams)->Result<BestMove, UciError>{
+        self.set_position(game)?;
+        self.go(params)?;
+        let clock=if game.board.current_move==ChessColour::White {params.wtime} else {params.btime};
+        let limit=params.limits.movetime.or(clock).filter(|_| !params.infinite&&!params.ponder);
+        self.wait_best_move(limit.map(|limit| limit+self.timeout))
+    }
+}
+
+impl Drop for UciEngine{
+    fn drop(&mut self){
+        let _=self.send("quit");
+        // An engine that ignores `quit` is killed rather than waited on forever.
+        let deadline=Instant::now()+QUIT_TIMEOUT;
+        while Instant::now()<deadline{
+            if !matches!(self.child.try_wait(), Ok(None)){return;}
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _=self.child.kill();
+        let _=self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stub()->UciEngine{
+        UciEngine::spawn("sh", &[concat!(env!("CARGO_MANIFEST_DIR"), "/tests/uci_stub.sh").to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_parse_info(){
+        let board=new_board();
+        let info=UciInfo::parse("info depth 12 seldepth 18 multipv 2 score cp -35 upperbound nodes 123456 nps 987654 time 125 pv e2e4 e7e5 g1f3 x9x9 d2d4", &board).unwrap();
+        assert_eq!((info.depth, info.seldepth, info.multipv), (Some(12), Some(18), Some(2)));
+        assert_eq!((info.score, info.upperbound, info.lowerbound), (Some(UciScore::Centipawns(-35)), true, false));
+        assert_eq!((info.nodes, info.nps, info.time), (Some(123456), Some(987654), Some(Duration::from_millis(125))));
+        assert_eq!(info.pv.iter().map(|mv| mv.to_uci()).collect::<Vec<_>>(), ["e2e4", "e7e5", "g1f3"]);
+        let info=UciInfo::parse("info score mate -2 string mate in two", &board).unwrap();
+        assert_eq!((info.score, info.string.as_deref()), (Some(UciScore::Mate(-2)), Some("mate in two")));
+        assert_eq!(UciScore::Mate(-2).to_score(), -(MATE_SCORE-4));
+        assert_eq!(UciScore::Mate(1).to_score(), MATE_SCORE-1);
+        assert_eq!(UciInfo::parse("bestmove e2e4", &board), None);
+        let option=parse_option("option name Clear Hash type button").unwrap();
+        assert_eq!((option.name.as_str(), option.kind.as_str()), ("Clear Hash", "button"));
+    }
+    #[test]
+    fn test_handshake_and_search(){
+        let mut engine=stub();
+        assert_eq!((engine.name(), engine.author()), ("Stub Engine", "Skye"));
+        let hash=engine.options().iter().find(|option| option.name=="Hash").unwrap();
+        assert_eq!((hash.kind.as_str(), hash.default.as_deref(), hash.min, hash.max), ("spin", Some("16"), Some(1), Some(1024)));
+        let style=engine.options().iter().find(|option| option.name=="Style").unwrap();
+        assert_eq!(style.vars, ["Solid", "Normal", "Risky"]);
+        engine.set_option("Hash", Some("64")).unwrap();
+        engine.new_game().unwrap();
+
+        let mut game=Game::new();
+        let result=engine.search(&game, &GoParams{limits: SearchLimits{depth: Some(2), ..Default::default()}, ..Default::default()}).unwrap();
+        assert_eq!(result.best_move.map(|mv| mv.to_uci()), Some("e2e4".to_string()));
+        assert_eq!(result.ponder.map(|mv| mv.to_uci()), Some("e7e5".to_string()));
+        assert_eq!(result.infos[0].string.as_deref(), Some("hash 64"));
+        let main=result.last_info(1).unwrap();
+        assert_eq!((main.depth, main.score, main.lowerbound, main.pv.len()), (Some(2), Some(UciScore::Centipawns(25)), true, 2));
+        assert_eq!(result.last_info(2).unwrap().score, Some(UciScore::Mate(-3)));
+
+        game.make_move(result.best_move.unwrap());
+        let result=engine.search(&game, &GoParams::default()).unwrap();
+        assert_eq!(result.best_move.map(|mv| mv.to_uci()), Some("e7e5".to_string()));
+    }
+    #[test]
+    fn test_stop_and_ponderhit(){
+        let mut engine=stub();
+        let game=Game::new();
+        engine.set_position(&game).unwrap();
+        engine.go(&GoParams{infinite: true, ..Default::default()}).unwrap();
+        let mut infos=0;
+        while let Some(event)=engine.read_event(Some(Duration::from_millis(300))).unwrap(){
+            assert!(matches!(event, UciEvent::Info(_)));
+            infos+=1;
+        }
+        assert_eq!(infos, 4);
+        engine.stop().unwrap();
+        assert_eq!(engine.wait_best_move(None).unwrap().best_move.map(|mv| mv.to_uci()), Some("e2e4".to_string()));
+
+        engine.go(&GoParams{ponder: true, wtime: Some(Duration::from_secs(60)), btime: Some(Duration::from_secs(60)), ..Default::default()}).unwrap();
+        assert!(engine.read_event(Some(Duration::from_secs(5))).unwrap().is_some());
+        engine.ponderhit().unwrap();
+        assert!(engine.wait_best_move(None).unwrap().best_move.is_some());
+        assert_eq!(GoParams{ponder: true, wtime: Some(Duration::from_secs(1)), movestogo: Some(20), ..Default::default()}.to_command(), "go ponder wtime 1000 movestogo 20");
+    }
+    #[test]
+    fn test_faulty_engines(){
+        let game=Game::new();
+        let params=GoParams{limits: SearchLimits{movetime: Some(Duration::from_millis(100)), ..Default::default()}, ..Default::default()};
+        let mut engine=stub();
+        engine.set_option("Fault", Some("hang")).unwrap();
+        engine.timeout=Duration::from_millis(200);
+        assert!(matches!(engine.search(&game, &params), Err(UciError::Timeout("bestmove"))));
+        let start=Instant::now();
+        drop(engine);
+        assert!(start.elapsed()<QUIT_TIMEOUT*3);
+
+        let mut engine=stub();
+        engine.set_option("Fault", Some("illegal")).unwrap();
+        assert!(matches!(engine.search(&game, &params), Err(UciError::IllegalMove(mv)) if mv=="a1a8"));
+        engine.set_option("Fault", Some("crash")).unwrap();
+        assert!(matches!(engine.search(&game, &params), Err(UciError::Exited)));
+        assert!(matches!(engine.is_ready(), Err(UciError::Exited)));
+    }
+}
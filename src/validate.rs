@@ -0,0 +1,185 @@
+use super::*;
+use std::fmt;
+
+const LIGHT_SQUARES:u64=0xAA55AA55AA55AA55;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ValidationError {
+    MissingKing(ChessColour),
+    TooManyKings(ChessColour),
+    PawnOnBackRank(u64),
+    /// The side that just moved has left its king in check.
+    OpponentInCheck,
+    /// More than two checkers, or two that no single move could have uncovered.
+    ImpossibleCheck,
+    InvalidCastling(ChessColour),
+    InvalidEnPassant,
+    /// More pieces of some kind than the missing pawns could have promoted to.
+    TooManyPieces(ChessColour),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f:&mut fmt::Formatter)->fmt::Result{
+        let side=|col:&ChessColour| if *col==ChessColour::White {"white"} else {"black"};
+        match self{
+            ValidationError::MissingKing(col)=>write!(f, "{} has no king", side(col)),
+            ValidationError::TooManyKings(col)=>write!(f, "{} has more than one king", side(col)),
+            ValidationError::PawnOnBackRank(pos)=>write!(f, "pawn on {}", get_square_name(*pos)),
+            ValidationError::OpponentInCheck=>write!(f, "the side not to move is in check"),
+            ValidationError::ImpossibleCheck=>write!(f, "the checks on the king cannot arise in a game"),
+            ValidationError::InvalidCastling(col)=>write!(f, "{} castling rights do not match its king and rooks", side(col)),
+            ValidationError::InvalidEnPassant=>write!(f, "en passant square does not follow a double pawn push"),
+            ValidationError::TooManyPieces(col)=>write!(f, "{} has more pieces than promotions allow", side(col)),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+fn is_slider(kind:ChessPieceKind)->bool{
+    matches!(kind, ChessPieceKind::Rook|ChessPieceKind::Bishop|ChessPieceKind::Queen)
+}
+
+// Each missing pawn can have promoted into one piece beyond the starting set.
+fn check_piece_counts(col:ChessColour, board:ChessBoard)->Result<(), ValidationError>{
+    let count=|kind:ChessPieceKind| get_kind_map(kind, col, board).count_ones();
+    let extra=|count:u32, start:u32| count.saturating_sub(start);
+    let bishops=get_kind_map(ChessPieceKind::Bishop, col, board);
+    let promoted=extra(count(ChessPieceKind::Queen), 1)
+        +extra(count(ChessPieceKind::Rook), 2)
+        +extra(count(ChessPieceKind::Knight), 2)
+        +extra((bishops&LIGHT_SQUARES).count_ones(), 1)
+        +extra((bishops&!LIGHT_SQUARES).count_ones(), 1);
+    let pawns=count(ChessPieceKind::Pawn);
+    if pawns>8||promoted>8-pawns{
+        return Err(ValidationError::TooManyPieces(col));
+    }
+    Ok(())
+}
+
+// Unmoved rooks stand for castling rights, so they and their king must be on the
+// back rank, on the standard files outside of Chess960.
+fn check_castling(col:ChessColour, board:ChessBoard)->Result<(), ValidationError>{
+    let back_rank=get_back_rank(col);
+    let king=board.pieces.iter().find(|p| !p.is_captured&&p.colour==col&&p.kind==ChessPieceKind::King);
+    for rook in board.pieces.iter().filter(|p| !p.is_captured&&p.colour==col&&p.kind==ChessPieceKind::Rook&&!p.has_moved){
+        let Some(king)=king.filter(|king| !king.has_moved&&(king.pos&back_rank)>0) else {
+            return Err(ValidationError::InvalidCastling(col));
+        };
+        let standard=board.chess960||(get_file(*king)==5&&matches!(get_file(*rook), 1|8));
+        if (rook.pos&back_rank)==0||!standard{
+            return Err(ValidationError::InvalidCastling(col));
+        }
+    }
+    Ok(())
+}
+
+// A pawn of the side that just moved showing a double step must have passed over an
+// empty square from an empty one.
+fn check_en_passant(board:ChessBoard)->Result<(), ValidationError>{
+    let col=get_op_col(board.current_move);
+    let occupied=get_all_piece_map(board);
+    let pushed:Vec<&ChessPiece>=board.pieces.iter()
+        .filter(|p| !p.is_captured&&p.colour==col&&p.kind==ChessPieceKind::Pawn&&p.prev_pos!=p.pos&&p.prev_pos!=0)
+        .filter(|p| if col==ChessColour::White {p.pos>>16==p.prev_pos} else {p.pos<<16==p.prev_pos})
+        .collect();
+    for pawn in pushed.iter(){
+        let passed=if col==ChessColour::White {pawn.pos>>8} else {pawn.pos<<8};
+        if ((passed|pawn.prev_pos)&occupied)>0{
+            return Err(ValidationError::InvalidEnPassant);
+        }
+    }
+    if pushed.len()>1{
+        return Err(ValidationError::InvalidEnPassant);
+    }
+    Ok(())
+}
+
+impl ChessBoard{
+    /// Checks that the position could have come from a game: one king a side, no
+    /// pawns on the back ranks, only the side to move in check and by at most two
+    /// pieces, castling and en passant rights that fit the board, and no more pieces
+    /// than promotions could have produced.
+    pub fn validate(&self)->Result<(), ValidationError>{
+        let board=*self;
+        for col in [ChessColour::White, ChessColour::Black]{
+            match get_kind_map(ChessPieceKind::King, col, board).count_ones(){
+                0=>return Err(ValidationError::MissingKing(col)),
+                1=>{}
+                _=>return Err(ValidationError::TooManyKings(col)),
+            }
+        }
+        let pawns=get_kind_map(ChessPieceKind::Pawn, ChessColour::White, board)|get_kind_map(ChessPieceKind::Pawn, ChessColour::Black, board);
+        let back_ranks=get_back_rank(ChessColour::White)|get_back_rank(ChessColour::Black);
+        if (pawns&back_ranks)>0{
+            let pos=pawns&back_ranks;
+            return Err(ValidationError::PawnOnBackRank(pos&pos.wrapping_neg()));
+        }
+        for col in [ChessColour::White, ChessColour::Black]{
+            check_piece_counts(col, board)?;
+        }
+        if is_checked(get_op_col(board.current_move), board){
+            return Err(ValidationError::OpponentInCheck);
+        }
+        let checkers=board.checkers();
+        if checkers.count_ones()>2{
+            return Err(ValidationError::ImpossibleCheck);
+        }
+        if checkers.count_ones()==2{
+            // At least one check of a double check is uncovered, and only sliders can
+            // be uncovered.
+            let sliders=board.pieces.iter().filter(|p| !p.is_captured&&(p.pos&checkers)>0&&is_slider(p.kind)).count();
+            if sliders==0{
+                return Err(ValidationError::ImpossibleCheck);
+            }
+        }
+        for col in [ChessColour::White, ChessColour::Black]{
+            check_castling(col, board)?;
+        }
+        check_en_passant(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(fen:&str)->Result<(), ValidationError>{
+        ChessBoard::from_fen(fen).unwrap().validate()
+    }
+
+    #[test]
+    fn test_material(){
+        assert_eq!(new_board().validate(), Ok(()));
+        assert_eq!(validate("8/8/8/8/8/8/8/4K3 w - - 0 1"), Err(ValidationError::MissingKing(ChessColour::Black)));
+        assert_eq!(validate("k7/8/8/8/8/8/8/3KK3 w - - 0 1"), Err(ValidationError::TooManyKings(ChessColour::White)));
+        assert_eq!(validate("k6P/8/8/8/8/8/8/4K3 w - - 0 1"), Err(ValidationError::PawnOnBackRank(get_square_from_name("h8").unwrap())));
+        // Ten knights need every pawn to have promoted.
+        assert_eq!(validate("k7/8/8/8/8/NNNN4/NNNNNN2/4K3 w - - 0 1"), Ok(()));
+        assert_eq!(validate("k7/8/8/8/8/NNNN4/NNNNNNP1/4K3 w - - 0 1"), Err(ValidationError::TooManyPieces(ChessColour::White)));
+        // Two bishops on light squares cost a promotion.
+        assert_eq!(validate("k7/8/8/8/8/8/PPPPPPPP/1B1BK3 w - - 0 1"), Err(ValidationError::TooManyPieces(ChessColour::White)));
+    }
+    #[test]
+    fn test_checks(){
+        assert_eq!(validate("k7/8/8/8/8/8/8/R3K3 b - - 0 1"), Ok(()));
+        assert_eq!(validate("k7/8/8/8/8/8/8/R3K3 w - - 0 1"), Err(ValidationError::OpponentInCheck));
+        assert_eq!(validate("k7/1P6/8/8/8/8/8/R3K3 b - - 0 1"), Ok(()));
+        assert_eq!(validate("k7/1P6/1N6/8/8/8/8/R3K3 b - - 0 1"), Err(ValidationError::ImpossibleCheck));
+        assert_eq!(validate("k7/1P6/1N6/8/8/8/8/4K3 b - - 0 1"), Err(ValidationError::ImpossibleCheck));
+    }
+    #[test]
+    fn test_rights(){
+        let mut board=new_board();
+        board.make_move(board.parse_san("e4").unwrap());
+        assert_eq!(board.validate(), Ok(()));
+        assert_eq!(validate("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"), Ok(()));
+        assert_eq!(validate("rnbqkbnr/pppppppp/8/8/4P3/4N3/PPPP1PPP/R1BQKBNR b KQkq e3 0 1"), Err(ValidationError::InvalidEnPassant));
+        let mut board=ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(board.validate(), Ok(()));
+        for piece in board.pieces.iter_mut().filter(|p| p.kind==ChessPieceKind::King&&p.colour==ChessColour::White){
+            piece.has_moved=true;
+        }
+        assert_eq!(board.validate(), Err(ValidationError::InvalidCastling(ChessColour::White)));
+    }
+}
@@ -0,0 +1,72 @@
+#!/bin/sh
+# A stand-in UCI engine for the client tests. It answers 1. e4 from the start
+# position and 1... e5 from anywhere else, reports the Hash option it was given,
+# and holds infinite and ponder searches until `stop` or `ponderhit`. The Fault
+# option makes it misbehave on `go`: `illegal` answers an illegal move, `crash`
+# exits, and `hang` goes silent and ignores `quit`.
+hash=16
+fault=none
+position=startpos
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Stub Engine"
+            echo "id author Skye"
+            echo "option name Hash type spin default 16 min 1 max 1024"
+            echo "option name Ponder type check default false"
+            echo "option name Style type combo default Normal var Solid var Normal var Risky"
+            echo "option name Clear Hash type button"
+            echo "uciok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        "setoption name Hash value "*)
+            hash=${line#setoption name Hash value }
+            ;;
+        "setoption name Fault value "*)
+            fault=${line#setoption name Fault value }
+            ;;
+        "position startpos")
+            position=startpos
+            ;;
+        position*)
+            position=other
+            ;;
+        go*)
+            case "$fault" in
+                crash) exit 1 ;;
+                hang) continue ;;
+            esac
+            if [ "$position" = startpos ]; then
+                best=e2e4
+                ponder=e7e5
+            else
+                best=e7e5
+                ponder=g1f3
+            fi
+            echo "info string hash $hash"
+            echo "info depth 1 seldepth 2 multipv 1 score cp 30 nodes 20 nps 2000 time 10 pv $best"
+            echo "info depth 2 seldepth 3 multipv 1 score cp 25 lowerbound nodes 400 nps 4000 time 100 pv $best $ponder"
+            echo "info depth 2 multipv 2 score mate -3 nodes 400 pv d2d4"
+            case "$line" in
+                *infinite*|*ponder*)
+                    while read -r next; do
+                        case "$next" in
+                            stop|ponderhit) break ;;
+                        esac
+                    done
+                    ;;
+            esac
+            if [ "$fault" = illegal ]; then
+                best=a1a8
+            fi
+            echo "bestmove $best ponder $ponder"
+            ;;
+        quit)
+            if [ "$fault" != hang ]; then
+                exit 0
+            fi
+            ;;
+    esac
+done